@@ -69,7 +69,7 @@ fn main() -> Result<()> {
 
             // seek to the instruction pointer and write
             mem_file.seek(SeekFrom::Start(prog_counter))?;
-            mem_file.write_all(&payload)?;
+            mem_file.write_all(payload)?;
             log::info!(
                 "injected malicious code (breakpoint) at {:#x}",
                 prog_counter
@@ -7,7 +7,7 @@ use std::process;
 use anyhow::Result;
 use log::info;
 use mio::{Events, Interest, Poll, Token};
-use syscall::{Signal, SignalFd, SignalSet, signal_block};
+use syscall::{SfdFlags, Signal, SignalFd, SignalInfo, SignalSet, signal_block};
 
 const SIGNAL: Token = Token(42);
 
@@ -18,7 +18,10 @@ fn main() -> Result<()> {
 
     let mut poll = Poll::new()?;
 
-    let mut sigfd = SignalFd::new(SignalSet::fill()?)?;
+    let mut sigfd = SignalFd::with_flags(
+        SignalSet::fill()?,
+        SfdFlags::SFD_NONBLOCK | SfdFlags::SFD_CLOEXEC,
+    )?;
 
     poll.registry()
         .register(&mut sigfd, SIGNAL, Interest::READABLE)?;
@@ -27,18 +30,31 @@ fn main() -> Result<()> {
 
     let mut events = Events::with_capacity(3);
 
+    let mut signals = [SignalInfo::default(); 8];
+
     loop {
         poll.poll(&mut events, None)?;
 
         for evt in events.iter() {
             match evt.token() {
-                SIGNAL => match sigfd.read_signal()? {
-                    Signal::SIGTERM => {
-                        info!("got signal TERM");
-                        return Ok(());
+                SIGNAL => loop {
+                    // drain all pending signals, the fd is edge triggered
+                    let num = sigfd.read_signals(&mut signals)?;
+
+                    if num == 0 {
+                        break;
                     }
-                    sig => {
-                        info!("got signal `{sig:?}`");
+
+                    for info in &signals[..num] {
+                        match info.signal {
+                            Signal::SIGTERM => {
+                                info!("got signal TERM from pid {}", info.pid);
+                                return Ok(());
+                            }
+                            sig => {
+                                info!("got signal `{sig:?}` from pid {}", info.pid);
+                            }
+                        }
                     }
                 },
                 _ => {
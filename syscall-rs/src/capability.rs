@@ -0,0 +1,678 @@
+//!
+//! This file is part of syscall-rs
+//!
+
+use std::{fmt, fs, io, str::FromStr};
+
+use libc::{c_int, c_ulong};
+
+use crate::{Error, Result, libc_bitflags, prctl::prctl};
+
+const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+/// `struct __user_cap_header_struct`
+#[repr(C)]
+struct CapHeader {
+    version: u32,
+    pid: c_int,
+}
+
+/// `struct __user_cap_data_struct`
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct CapData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+macro_rules! capabilities {
+    ($($(#[$doc:meta])* $name:ident = $value:expr,)*) => {
+        /// Linux capability
+        #[non_exhaustive]
+        #[repr(u8)]
+        #[allow(non_camel_case_types)]
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub enum Capability {
+            $($(#[$doc])* $name = $value,)*
+        }
+
+        impl Capability {
+            /// All capabilities known to this crate, in ascending order
+            pub const ALL: &[Capability] = &[$(Capability::$name),*];
+
+            /// Return the name of the capability, e.g. `"CAP_NET_ADMIN"`
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $(Capability::$name => stringify!($name),)*
+                }
+            }
+        }
+    };
+}
+
+capabilities! {
+    /// Make arbitrary changes to file UIDs and GIDs
+    CAP_CHOWN = 0,
+    /// Bypass file read, write and execute permission checks
+    CAP_DAC_OVERRIDE = 1,
+    /// Bypass file read and directory read and execute permission checks
+    CAP_DAC_READ_SEARCH = 2,
+    /// Bypass permission checks requiring the file UID to match
+    CAP_FOWNER = 3,
+    /// Don't clear set-user-ID and set-group-ID bits when a file is modified
+    CAP_FSETID = 4,
+    /// Bypass permission checks for sending signals
+    CAP_KILL = 5,
+    /// Make arbitrary manipulations of process GIDs
+    CAP_SETGID = 6,
+    /// Make arbitrary manipulations of process UIDs
+    CAP_SETUID = 7,
+    /// Change capability bounding and inheritable sets
+    CAP_SETPCAP = 8,
+    /// Set the immutable and append-only file attributes
+    CAP_LINUX_IMMUTABLE = 9,
+    /// Bind sockets to ports below 1024
+    CAP_NET_BIND_SERVICE = 10,
+    /// Unused, socket broadcasting and multicast listening
+    CAP_NET_BROADCAST = 11,
+    /// Perform network administration, e.g. configure interfaces
+    CAP_NET_ADMIN = 12,
+    /// Use raw and packet sockets
+    CAP_NET_RAW = 13,
+    /// Lock memory
+    CAP_IPC_LOCK = 14,
+    /// Bypass permission checks for System V IPC objects
+    CAP_IPC_OWNER = 15,
+    /// Load and unload kernel modules
+    CAP_SYS_MODULE = 16,
+    /// Perform I/O port operations and access `/dev/mem`
+    CAP_SYS_RAWIO = 17,
+    /// Use `chroot(2)`
+    CAP_SYS_CHROOT = 18,
+    /// Trace arbitrary processes
+    CAP_SYS_PTRACE = 19,
+    /// Use `acct(2)`
+    CAP_SYS_PACCT = 20,
+    /// Perform a range of system administration operations
+    CAP_SYS_ADMIN = 21,
+    /// Use `reboot(2)` and `kexec_load(2)`
+    CAP_SYS_BOOT = 22,
+    /// Raise nice values and change scheduling of arbitrary processes
+    CAP_SYS_NICE = 23,
+    /// Override resource limits
+    CAP_SYS_RESOURCE = 24,
+    /// Set the system clock
+    CAP_SYS_TIME = 25,
+    /// Use `vhangup(2)` and privileged terminal ioctls
+    CAP_SYS_TTY_CONFIG = 26,
+    /// Create special files using `mknod(2)`
+    CAP_MKNOD = 27,
+    /// Establish leases on arbitrary files
+    CAP_LEASE = 28,
+    /// Write records to the kernel audit log
+    CAP_AUDIT_WRITE = 29,
+    /// Configure kernel auditing
+    CAP_AUDIT_CONTROL = 30,
+    /// Set file capabilities
+    CAP_SETFCAP = 31,
+    /// Override mandatory access control
+    CAP_MAC_OVERRIDE = 32,
+    /// Configure mandatory access control
+    CAP_MAC_ADMIN = 33,
+    /// Perform privileged `syslog(2)` operations
+    CAP_SYSLOG = 34,
+    /// Trigger wake ups of the system
+    CAP_WAKE_ALARM = 35,
+    /// Block system suspend
+    CAP_BLOCK_SUSPEND = 36,
+    /// Read the kernel audit log via multicast netlink
+    CAP_AUDIT_READ = 37,
+    /// Use performance monitoring
+    CAP_PERFMON = 38,
+    /// Use privileged BPF operations
+    CAP_BPF = 39,
+    /// Use checkpoint and restore related operations
+    CAP_CHECKPOINT_RESTORE = 40,
+}
+
+fn invalid(msg: String) -> Error {
+    Error::Syscall(io::Error::new(io::ErrorKind::InvalidData, msg))
+}
+
+impl FromStr for Capability {
+    type Err = Error;
+
+    /// Parse a capability name, with or without `CAP_` prefix and ignoring
+    /// case, e.g. `"CAP_NET_ADMIN"` or `"net_admin"`
+    fn from_str(s: &str) -> Result<Capability> {
+        let name = s.to_ascii_uppercase();
+        let name = name.strip_prefix("CAP_").unwrap_or(&name);
+
+        Capability::ALL
+            .iter()
+            .find(|cap| &cap.as_str()[4..] == name)
+            .copied()
+            .ok_or_else(|| invalid(format!("unknown capability: {}", s)))
+    }
+}
+
+impl TryFrom<u8> for Capability {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Capability> {
+        Capability::ALL
+            .get(value as usize)
+            .copied()
+            .ok_or_else(|| invalid(format!("unknown capability: {}", value)))
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A set of capabilities
+///
+/// The set may contain capabilities of a newer kernel that have no
+/// [`Capability`] variant, e.g. when parsed from `/proc/<pid>/status`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Capabilities(u64);
+
+impl Capabilities {
+    /// Return an empty set
+    pub fn empty() -> Capabilities {
+        Capabilities(0)
+    }
+
+    /// Return a set of all capabilities in [`Capability::ALL`]
+    pub fn all() -> Capabilities {
+        Capability::ALL.iter().copied().collect()
+    }
+
+    /// Return a set from its bit mask, bit `n` being capability `n`
+    pub fn from_bits(bits: u64) -> Capabilities {
+        Capabilities(bits)
+    }
+
+    /// Return the bit mask of the set
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+
+    /// Return `true` if the set is empty
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Return `true` if the set contains `cap`
+    pub fn contains(&self, cap: Capability) -> bool {
+        self.0 & (1 << cap as u8) != 0
+    }
+
+    /// Add `cap` to the set
+    pub fn insert(&mut self, cap: Capability) {
+        self.0 |= 1 << cap as u8;
+    }
+
+    /// Remove `cap` from the set
+    pub fn remove(&mut self, cap: Capability) {
+        self.0 &= !(1 << cap as u8);
+    }
+
+    /// Iterate over the known capabilities in the set
+    pub fn iter(&self) -> impl Iterator<Item = Capability> + '_ {
+        Capability::ALL
+            .iter()
+            .copied()
+            .filter(|&cap| self.contains(cap))
+    }
+}
+
+impl FromIterator<Capability> for Capabilities {
+    fn from_iter<I: IntoIterator<Item = Capability>>(iter: I) -> Capabilities {
+        let mut caps = Capabilities::empty();
+
+        for cap in iter {
+            caps.insert(cap);
+        }
+
+        caps
+    }
+}
+
+impl fmt::Display for Capabilities {
+    /// Format the set as comma separated list of capability names
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (n, cap) in self.iter().enumerate() {
+            if n > 0 {
+                f.write_str(",")?;
+            }
+            f.write_str(cap.as_str())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Effective, permitted and inheritable capabilities of a thread
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CapSet {
+    /// Capabilities used for permission checks
+    pub effective: Capabilities,
+    /// Limiting superset of the effective capabilities
+    pub permitted: Capabilities,
+    /// Capabilities preserved across `execve(2)`
+    pub inheritable: Capabilities,
+}
+
+impl CapSet {
+    /// Return the capabilities of the thread `tid`, `0` for the calling
+    /// thread
+    pub fn get(tid: libc::pid_t) -> Result<CapSet> {
+        let mut header = CapHeader {
+            version: LINUX_CAPABILITY_VERSION_3,
+            pid: tid,
+        };
+        let mut data = [CapData::default(); 2];
+
+        syscall!(syscall(
+            libc::SYS_capget,
+            &mut header as *mut CapHeader,
+            data.as_mut_ptr()
+        ))?;
+
+        let join = |lo: u32, hi: u32| Capabilities((hi as u64) << 32 | lo as u64);
+
+        Ok(CapSet {
+            effective: join(data[0].effective, data[1].effective),
+            permitted: join(data[0].permitted, data[1].permitted),
+            inheritable: join(data[0].inheritable, data[1].inheritable),
+        })
+    }
+
+    /// Set the capabilities of the calling thread
+    ///
+    /// Capabilities can only be added to the effective and inheritable
+    /// sets if they are in the permitted set, and the permitted set can only
+    /// shrink.
+    pub fn set(&self) -> Result<()> {
+        let mut header = CapHeader {
+            version: LINUX_CAPABILITY_VERSION_3,
+            pid: 0,
+        };
+        let split = |caps: Capabilities| (caps.0 as u32, (caps.0 >> 32) as u32);
+
+        let (eff, perm, inh) = (
+            split(self.effective),
+            split(self.permitted),
+            split(self.inheritable),
+        );
+        let data = [
+            CapData {
+                effective: eff.0,
+                permitted: perm.0,
+                inheritable: inh.0,
+            },
+            CapData {
+                effective: eff.1,
+                permitted: perm.1,
+                inheritable: inh.1,
+            },
+        ];
+
+        syscall!(syscall(
+            libc::SYS_capset,
+            &mut header as *mut CapHeader,
+            data.as_ptr()
+        ))
+        .map(|_| ())
+    }
+}
+
+/// Capability sets of a process, as shown in `/proc/<pid>/status`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProcessCaps {
+    /// `CapInh`
+    pub inheritable: Capabilities,
+    /// `CapPrm`
+    pub permitted: Capabilities,
+    /// `CapEff`
+    pub effective: Capabilities,
+    /// `CapBnd`
+    pub bounding: Capabilities,
+    /// `CapAmb`
+    pub ambient: Capabilities,
+}
+
+impl ProcessCaps {
+    /// Return the capability sets of process `pid`
+    pub fn of(pid: libc::pid_t) -> Result<ProcessCaps> {
+        fs::read_to_string(format!("/proc/{}/status", pid))?.parse()
+    }
+}
+
+impl FromStr for ProcessCaps {
+    type Err = Error;
+
+    /// Parse the capability lines of `/proc/<pid>/status`, ignoring all
+    /// other lines
+    ///
+    /// The ambient set is empty if `CapAmb` is missing, as is the case for
+    /// kernels older than 4.3.
+    fn from_str(s: &str) -> Result<ProcessCaps> {
+        let mut caps = ProcessCaps::default();
+        let mut found = 0;
+
+        for line in s.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+
+            let set = match key {
+                "CapInh" => &mut caps.inheritable,
+                "CapPrm" => &mut caps.permitted,
+                "CapEff" => &mut caps.effective,
+                "CapBnd" => &mut caps.bounding,
+                "CapAmb" => &mut caps.ambient,
+                _ => continue,
+            };
+
+            let bits = u64::from_str_radix(value.trim(), 16)
+                .map_err(|_| invalid(format!("invalid capability mask: {}", line)))?;
+            *set = Capabilities(bits);
+
+            if key != "CapAmb" {
+                found += 1;
+            }
+        }
+
+        if found != 4 {
+            return Err(invalid("missing capability sets".to_string()));
+        }
+
+        Ok(caps)
+    }
+}
+
+/// Add `cap` to the ambient set of the calling thread
+///
+/// `cap` must be in the permitted and inheritable sets.
+pub fn ambient_raise(cap: Capability) -> Result<()> {
+    prctl(
+        libc::PR_CAP_AMBIENT,
+        libc::PR_CAP_AMBIENT_RAISE as c_ulong,
+        cap as c_ulong,
+    )
+    .map(|_| ())
+}
+
+/// Remove `cap` from the ambient set of the calling thread
+pub fn ambient_lower(cap: Capability) -> Result<()> {
+    prctl(
+        libc::PR_CAP_AMBIENT,
+        libc::PR_CAP_AMBIENT_LOWER as c_ulong,
+        cap as c_ulong,
+    )
+    .map(|_| ())
+}
+
+/// Clear the ambient set of the calling thread
+pub fn ambient_clear() -> Result<()> {
+    prctl(
+        libc::PR_CAP_AMBIENT,
+        libc::PR_CAP_AMBIENT_CLEAR_ALL as c_ulong,
+        0,
+    )
+    .map(|_| ())
+}
+
+/// Return `true` if `cap` is in the ambient set of the calling thread
+pub fn ambient_is_set(cap: Capability) -> Result<bool> {
+    prctl(
+        libc::PR_CAP_AMBIENT,
+        libc::PR_CAP_AMBIENT_IS_SET as c_ulong,
+        cap as c_ulong,
+    )
+    .map(|res| res == 1)
+}
+
+/// Drop `cap` from the bounding set of the calling thread
+///
+/// This requires `CAP_SETPCAP`.
+pub fn bounding_drop(cap: Capability) -> Result<()> {
+    prctl(libc::PR_CAPBSET_DROP, cap as c_ulong, 0).map(|_| ())
+}
+
+/// Return `true` if `cap` is in the bounding set of the calling thread
+pub fn bounding_is_set(cap: Capability) -> Result<bool> {
+    prctl(libc::PR_CAPBSET_READ, cap as c_ulong, 0).map(|res| res == 1)
+}
+
+/// Set whether the calling thread keeps its permitted capabilities when all
+/// of its UIDs change to non-zero values
+///
+/// The setting is cleared on `execve(2)`.
+pub fn set_keepcaps(keep: bool) -> Result<()> {
+    prctl(libc::PR_SET_KEEPCAPS, keep as c_ulong, 0).map(|_| ())
+}
+
+/// Return the keep capabilities setting of the calling thread
+pub fn keepcaps() -> Result<bool> {
+    prctl(libc::PR_GET_KEEPCAPS, 0, 0).map(|res| res == 1)
+}
+
+libc_bitflags! {
+    /// Security bits, changing the special treatment of UID 0
+    pub struct SecureBits: c_int {
+        /// Don't grant capabilities to UID 0 on `execve(2)`
+        SECBIT_NOROOT;
+        /// Lock [`SecureBits::SECBIT_NOROOT`]
+        SECBIT_NOROOT_LOCKED;
+        /// Don't adjust capabilities when UIDs change from or to 0
+        SECBIT_NO_SETUID_FIXUP;
+        /// Lock [`SecureBits::SECBIT_NO_SETUID_FIXUP`]
+        SECBIT_NO_SETUID_FIXUP_LOCKED;
+        /// Keep permitted capabilities when all UIDs change to non-zero,
+        /// like `PR_SET_KEEPCAPS`
+        SECBIT_KEEP_CAPS;
+        /// Lock [`SecureBits::SECBIT_KEEP_CAPS`]
+        SECBIT_KEEP_CAPS_LOCKED;
+        /// Disallow raising ambient capabilities
+        SECBIT_NO_CAP_AMBIENT_RAISE;
+        /// Lock [`SecureBits::SECBIT_NO_CAP_AMBIENT_RAISE`]
+        SECBIT_NO_CAP_AMBIENT_RAISE_LOCKED;
+    }
+}
+
+/// Return the security bits of the calling thread
+pub fn securebits() -> Result<SecureBits> {
+    prctl(libc::PR_GET_SECUREBITS, 0, 0).map(SecureBits::from_bits_truncate)
+}
+
+/// Set the security bits of the calling thread
+///
+/// This requires `CAP_SETPCAP`. Locked bits can't be changed anymore.
+pub fn set_securebits(bits: SecureBits) -> Result<()> {
+    prctl(libc::PR_SET_SECUREBITS, bits.bits() as c_ulong, 0).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::fs::MetadataExt};
+
+    use anyhow::{Result, ensure};
+
+    use super::{
+        CapSet, Capabilities, Capability, ProcessCaps, SecureBits, ambient_clear, ambient_is_set,
+        ambient_lower, ambient_raise, bounding_drop, bounding_is_set, keepcaps, securebits,
+        set_keepcaps, set_securebits,
+    };
+    use crate::{no_new_privs, set_no_new_privs, test_util::in_child};
+
+    const STATUS: &str = "\
+Name:\tcat
+Umask:\t0022
+State:\tR (running)
+CapInh:\t0000000000000000
+CapPrm:\t000001ffffffffff
+CapEff:\t000001ffffffffff
+CapBnd:\t000001ffffffffff
+CapAmb:\t0000000000002000
+NoNewPrivs:\t0
+";
+
+    #[test]
+    fn cap_names() -> Result<()> {
+        for (n, &cap) in Capability::ALL.iter().enumerate() {
+            assert_eq!(cap as usize, n);
+            assert_eq!(Capability::try_from(n as u8)?, cap);
+            assert_eq!(cap.to_string().parse::<Capability>()?, cap);
+        }
+
+        assert_eq!(
+            "net_admin".parse::<Capability>()?,
+            Capability::CAP_NET_ADMIN
+        );
+        assert_eq!(
+            "SYS_ADMIN".parse::<Capability>()?,
+            Capability::CAP_SYS_ADMIN
+        );
+        assert!("CAP_FOO".parse::<Capability>().is_err());
+        assert!(Capability::try_from(64).is_err());
+
+        let caps = [Capability::CAP_KILL, Capability::CAP_BPF]
+            .into_iter()
+            .collect::<Capabilities>();
+        assert_eq!(caps.bits(), 1 << 5 | 1 << 39);
+        assert_eq!(caps.to_string(), "CAP_KILL,CAP_BPF");
+        assert_eq!(Capabilities::all().bits(), (1 << 41) - 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn cap_status() -> Result<()> {
+        let caps = STATUS.parse::<ProcessCaps>()?;
+
+        assert_eq!(caps.inheritable, Capabilities::empty());
+        assert_eq!(caps.permitted, Capabilities::all());
+        assert_eq!(caps.effective, Capabilities::all());
+        assert_eq!(caps.bounding, Capabilities::all());
+        assert_eq!(
+            caps.ambient.iter().collect::<Vec<_>>(),
+            [Capability::CAP_NET_RAW]
+        );
+
+        let caps = STATUS
+            .replace("CapAmb:\t0000000000002000\n", "")
+            .parse::<ProcessCaps>()?;
+        assert_eq!(caps.ambient, Capabilities::empty());
+        assert_eq!(caps.bounding, Capabilities::all());
+
+        assert!("CapInh:\t0\n".parse::<ProcessCaps>().is_err());
+        assert!(
+            STATUS
+                .replace("0000000000002000", "xyz")
+                .parse::<ProcessCaps>()
+                .is_err()
+        );
+
+        // the kernel reports the same sets either way
+        let caps = ProcessCaps::of(std::process::id() as i32)?;
+        let set = CapSet::get(0)?;
+
+        assert_eq!(caps.effective, set.effective);
+        assert_eq!(caps.permitted, set.permitted);
+        assert_eq!(caps.inheritable, set.inheritable);
+
+        Ok(())
+    }
+
+    #[test]
+    fn cap_set() -> Result<()> {
+        if !CapSet::get(0)?.effective.contains(Capability::CAP_SETPCAP) {
+            return Ok(());
+        }
+
+        let file = tempfile::NamedTempFile::new()?;
+
+        in_child(|| {
+            bounding_drop(Capability::CAP_SYS_BOOT)?;
+            ensure!(!bounding_is_set(Capability::CAP_SYS_BOOT)?);
+            ensure!(bounding_is_set(Capability::CAP_CHOWN)?);
+            ensure!(
+                !ProcessCaps::of(std::process::id() as i32)?
+                    .bounding
+                    .contains(Capability::CAP_SYS_BOOT)
+            );
+
+            let mut set = CapSet::get(0)?;
+            set.inheritable.insert(Capability::CAP_NET_RAW);
+            set.effective.remove(Capability::CAP_CHOWN);
+            set.set()?;
+
+            let err = std::os::unix::fs::chown(file.path(), Some(1000), None).unwrap_err();
+            ensure!(err.raw_os_error() == Some(libc::EPERM), "{}", err);
+
+            ambient_raise(Capability::CAP_NET_RAW)?;
+            ensure!(ambient_is_set(Capability::CAP_NET_RAW)?);
+            ambient_lower(Capability::CAP_NET_RAW)?;
+            ensure!(!ambient_is_set(Capability::CAP_NET_RAW)?);
+            ambient_raise(Capability::CAP_NET_RAW)?;
+            ambient_clear()?;
+            ensure!(!ambient_is_set(Capability::CAP_NET_RAW)?);
+
+            set_securebits(SecureBits::SECBIT_NO_CAP_AMBIENT_RAISE)?;
+            ensure!(securebits()? == SecureBits::SECBIT_NO_CAP_AMBIENT_RAISE);
+            ensure!(ambient_raise(Capability::CAP_NET_RAW).is_err());
+
+            set_no_new_privs()?;
+            ensure!(no_new_privs()?);
+
+            Ok(())
+        })?;
+
+        ensure!(fs::metadata(file.path())?.uid() == 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn cap_keepcaps() -> Result<()> {
+        let permitted = CapSet::get(0)?.permitted;
+        if !permitted.contains(Capability::CAP_SETUID) {
+            return Ok(());
+        }
+
+        in_child(|| {
+            set_keepcaps(true)?;
+            ensure!(keepcaps()?);
+
+            syscall!(setresuid(1000, 1000, 1000))?;
+
+            // the permitted set survives the UID change, the effective
+            // set is cleared
+            let mut set = CapSet::get(0)?;
+            ensure!(set.permitted == permitted);
+            ensure!(set.effective.is_empty());
+
+            set.permitted = [Capability::CAP_NET_ADMIN].into_iter().collect();
+            set.effective = set.permitted;
+            set.set()?;
+            ensure!(
+                CapSet::get(0)?
+                    .effective
+                    .contains(Capability::CAP_NET_ADMIN)
+            );
+
+            // permitted capabilities can't be regained
+            set.permitted = permitted;
+            ensure!(set.set().is_err());
+
+            Ok(())
+        })
+    }
+}
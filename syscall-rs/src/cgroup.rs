@@ -0,0 +1,1008 @@
+//!
+//! This file is part of syscall-rs
+//!
+
+use std::{
+    fs,
+    io::Write,
+    os::unix::prelude::{AsFd, AsRawFd, BorrowedFd, RawFd},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use mio::{Interest, Registry, Token, event};
+
+use crate::{Error, Inotify, InotifyFlags, Result, WatchMask};
+
+/// Control group of the cgroup v2 hierarchy
+///
+/// A [`Cgroup`] is a directory below the cgroup2 mountpoint, and all
+/// operations are plain reads and writes of its interface files.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    /// Return the root of the cgroup v2 hierarchy
+    pub fn root() -> Result<Cgroup> {
+        Ok(Cgroup::open(cgroup_mountpoint()?))
+    }
+
+    /// Return the cgroup of process `pid`, or of the calling process if
+    /// `pid` is `None`
+    pub fn of<P>(pid: P) -> Result<Cgroup>
+    where
+        P: Into<Option<libc::pid_t>>,
+    {
+        let file = match pid.into() {
+            Some(pid) => format!("/proc/{}/cgroup", pid),
+            None => "/proc/self/cgroup".to_string(),
+        };
+
+        let path = fs::read_to_string(file)?
+            .lines()
+            .find_map(|line| line.strip_prefix("0::").map(str::to_string))
+            .ok_or_else(|| invalid("no cgroup v2 membership"))?;
+
+        let mut root = cgroup_mountpoint()?;
+
+        match path.trim_start_matches('/') {
+            "" => {}
+            path => root.push(path),
+        }
+
+        Ok(Cgroup::open(root))
+    }
+
+    /// Return the cgroup at `path`, without checking whether it exists
+    pub fn open<P: AsRef<Path>>(path: P) -> Cgroup {
+        Cgroup {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Return the path of the cgroup
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Create the child cgroup `name`
+    pub fn create(&self, name: &str) -> Result<Cgroup> {
+        let child = Cgroup::open(self.path.join(name));
+
+        fs::create_dir(&child.path)?;
+
+        Ok(child)
+    }
+
+    /// Remove the cgroup, which must not have any processes or children
+    pub fn remove(self) -> Result<()> {
+        Ok(fs::remove_dir(&self.path)?)
+    }
+
+    /// Return the child cgroups
+    pub fn children(&self) -> Result<Vec<Cgroup>> {
+        let mut children = Vec::new();
+
+        for entry in fs::read_dir(&self.path)? {
+            let entry = entry?;
+
+            if entry.file_type()?.is_dir() {
+                children.push(Cgroup::open(entry.path()));
+            }
+        }
+
+        Ok(children)
+    }
+
+    /// Move the process `pid` with all its threads into the cgroup
+    pub fn add_process(&self, pid: libc::pid_t) -> Result<()> {
+        self.write("cgroup.procs", &pid.to_string())
+    }
+
+    /// Move the thread `tid` into the cgroup, which must be threaded
+    pub fn add_thread(&self, tid: libc::pid_t) -> Result<()> {
+        self.write("cgroup.threads", &tid.to_string())
+    }
+
+    /// Return the processes of the cgroup
+    pub fn processes(&self) -> Result<Vec<libc::pid_t>> {
+        self.ids("cgroup.procs")
+    }
+
+    /// Return the threads of the cgroup
+    pub fn threads(&self) -> Result<Vec<libc::pid_t>> {
+        self.ids("cgroup.threads")
+    }
+
+    /// Kill all processes of the cgroup and its children
+    pub fn kill(&self) -> Result<()> {
+        self.write("cgroup.kill", "1")
+    }
+
+    /// Return the controllers available to the cgroup
+    pub fn controllers(&self) -> Result<Vec<String>> {
+        self.words("cgroup.controllers")
+    }
+
+    /// Return the controllers enabled for the children of the cgroup
+    pub fn subtree_control(&self) -> Result<Vec<String>> {
+        self.words("cgroup.subtree_control")
+    }
+
+    /// Enable `controllers`, e.g. `memory`, for the children of the cgroup
+    pub fn enable_controllers(&self, controllers: &[&str]) -> Result<()> {
+        let value = controllers
+            .iter()
+            .map(|c| format!("+{}", c))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        self.write("cgroup.subtree_control", &value)
+    }
+
+    /// Disable `controllers` for the children of the cgroup
+    pub fn disable_controllers(&self, controllers: &[&str]) -> Result<()> {
+        let value = controllers
+            .iter()
+            .map(|c| format!("-{}", c))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        self.write("cgroup.subtree_control", &value)
+    }
+
+    /// Set the memory limit in bytes, or remove it if `max` is `None`
+    pub fn set_memory_max(&self, max: Option<u64>) -> Result<()> {
+        self.write("memory.max", &limit(max))
+    }
+
+    /// Return the memory limit in bytes, `None` if there is no limit
+    pub fn memory_max(&self) -> Result<Option<u64>> {
+        parse_limit(&self.read("memory.max")?)
+    }
+
+    /// Set the maximum number of processes, or remove the limit if `max` is
+    /// `None`
+    pub fn set_pids_max(&self, max: Option<u64>) -> Result<()> {
+        self.write("pids.max", &limit(max))
+    }
+
+    /// Return the maximum number of processes, `None` if there is no limit
+    pub fn pids_max(&self) -> Result<Option<u64>> {
+        parse_limit(&self.read("pids.max")?)
+    }
+
+    /// Limit the CPU time to `quota` per `period`, or remove the limit if
+    /// `quota` is `None`
+    pub fn set_cpu_max(&self, quota: Option<Duration>, period: Duration) -> Result<()> {
+        let quota = limit(quota.map(|q| q.as_micros() as u64));
+
+        self.write(
+            "cpu.max",
+            &format!("{} {}", quota, period.as_micros() as u64),
+        )
+    }
+
+    /// Return the CPU time quota and period, the quota is `None` if there is
+    /// no limit
+    pub fn cpu_max(&self) -> Result<(Option<Duration>, Duration)> {
+        let value = self.read("cpu.max")?;
+
+        let (quota, period) = value
+            .trim()
+            .split_once(' ')
+            .ok_or_else(|| invalid("invalid cpu.max"))?;
+
+        let quota = parse_limit(quota)?.map(Duration::from_micros);
+        let period = Duration::from_micros(parse_u64(period)?);
+
+        Ok((quota, period))
+    }
+
+    /// Set the I/O limits of the block device `dev`, given as major and
+    /// minor number
+    pub fn set_io_max(&self, dev: (u32, u32), max: &IoMax) -> Result<()> {
+        self.write("io.max", &format!("{}:{} {}", dev.0, dev.1, max))
+    }
+
+    /// Return the I/O limits of all block devices with limits
+    pub fn io_max(&self) -> Result<Vec<((u32, u32), IoMax)>> {
+        self.read("io.max")?
+            .lines()
+            .map(|line| {
+                let (dev, max) = line
+                    .split_once(' ')
+                    .ok_or_else(|| invalid("invalid io.max"))?;
+                let (major, minor) = dev
+                    .split_once(':')
+                    .ok_or_else(|| invalid("invalid io.max"))?;
+
+                Ok((
+                    (parse_u64(major)? as u32, parse_u64(minor)? as u32),
+                    max.parse()?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Return the `cgroup.events` of the cgroup
+    pub fn events(&self) -> Result<CgroupEvents> {
+        self.read("cgroup.events")?.parse()
+    }
+
+    /// Return the `cpu.stat` of the cgroup
+    pub fn cpu_stat(&self) -> Result<CpuStat> {
+        self.read("cpu.stat")?.parse()
+    }
+
+    /// Return the `memory.stat` of the cgroup
+    pub fn memory_stat(&self) -> Result<MemoryStat> {
+        self.read("memory.stat")?.parse()
+    }
+
+    /// Return the `memory.events` of the cgroup
+    pub fn memory_events(&self) -> Result<MemoryEvents> {
+        self.read("memory.events")?.parse()
+    }
+
+    /// Return the pressure stall information of `resource`
+    pub fn pressure(&self, resource: Resource) -> Result<Pressure> {
+        self.read(resource.pressure_file())?.parse()
+    }
+
+    /// Return a watch for changes of the `cgroup.events` of the cgroup,
+    /// e.g. whether the cgroup is populated
+    pub fn watch(&self) -> Result<CgroupWatch> {
+        let inotify = Inotify::new(InotifyFlags::IN_CLOEXEC | InotifyFlags::IN_NONBLOCK)?;
+
+        inotify.add_watch(self.path.join("cgroup.events"), WatchMask::IN_MODIFY)?;
+
+        Ok(CgroupWatch {
+            inotify,
+            cgroup: self.clone(),
+        })
+    }
+
+    /// Read the interface file `file`
+    pub fn read(&self, file: &str) -> Result<String> {
+        Ok(fs::read_to_string(self.path.join(file))?)
+    }
+
+    /// Write `value` to the interface file `file`
+    ///
+    /// Interface files must be written using a single `write(2)`.
+    pub fn write(&self, file: &str, value: &str) -> Result<()> {
+        let mut f = fs::OpenOptions::new()
+            .write(true)
+            .open(self.path.join(file))?;
+
+        if f.write(value.as_bytes())? != value.len() {
+            return Err(invalid("short write of cgroup file"));
+        }
+
+        Ok(())
+    }
+
+    fn ids(&self, file: &str) -> Result<Vec<libc::pid_t>> {
+        self.read(file)?
+            .lines()
+            .map(|id| id.parse().map_err(|_| invalid("invalid process ID")))
+            .collect()
+    }
+
+    fn words(&self, file: &str) -> Result<Vec<String>> {
+        Ok(self
+            .read(file)?
+            .split_whitespace()
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+/// Watch for changes of `cgroup.events`, created by [`Cgroup::watch()`]
+///
+/// A [`CgroupWatch`] is readable once `cgroup.events` changed, and can
+/// therefore be registered with a [`mio::Poll`].
+#[derive(Debug)]
+pub struct CgroupWatch {
+    inotify: Inotify,
+    cgroup: Cgroup,
+}
+
+impl CgroupWatch {
+    /// Return the current [`CgroupEvents`], if `cgroup.events` changed since
+    /// the last call
+    pub fn changed(&self) -> Result<Option<CgroupEvents>> {
+        if self.inotify.read_events()?.is_empty() {
+            return Ok(None);
+        }
+
+        self.cgroup.events().map(Some)
+    }
+}
+
+impl AsRawFd for CgroupWatch {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inotify.as_raw_fd()
+    }
+}
+
+impl AsFd for CgroupWatch {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inotify.as_fd()
+    }
+}
+
+impl event::Source for CgroupWatch {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> std::io::Result<()> {
+        self.inotify.register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> std::io::Result<()> {
+        self.inotify.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> std::io::Result<()> {
+        self.inotify.deregister(registry)
+    }
+}
+
+/// Resources with pressure stall information
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resource {
+    /// CPU time
+    Cpu,
+    /// Memory
+    Memory,
+    /// Block I/O
+    Io,
+}
+
+impl Resource {
+    fn pressure_file(&self) -> &'static str {
+        match self {
+            Resource::Cpu => "cpu.pressure",
+            Resource::Memory => "memory.pressure",
+            Resource::Io => "io.pressure",
+        }
+    }
+}
+
+/// I/O limits of a block device, `None` means no limit
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IoMax {
+    /// Read bytes per second
+    pub rbps: Option<u64>,
+    /// Written bytes per second
+    pub wbps: Option<u64>,
+    /// Read operations per second
+    pub riops: Option<u64>,
+    /// Write operations per second
+    pub wiops: Option<u64>,
+}
+
+impl std::fmt::Display for IoMax {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "rbps={} wbps={} riops={} wiops={}",
+            limit(self.rbps),
+            limit(self.wbps),
+            limit(self.riops),
+            limit(self.wiops)
+        )
+    }
+}
+
+impl FromStr for IoMax {
+    type Err = Error;
+
+    /// Parse [`IoMax`] from a string like `rbps=1048576 wbps=max`
+    fn from_str(s: &str) -> Result<IoMax> {
+        let mut max = IoMax::default();
+
+        for kv in s.split_whitespace() {
+            let (key, value) = kv
+                .split_once('=')
+                .ok_or_else(|| invalid("invalid io.max"))?;
+
+            let field = match key {
+                "rbps" => &mut max.rbps,
+                "wbps" => &mut max.wbps,
+                "riops" => &mut max.riops,
+                "wiops" => &mut max.wiops,
+                _ => continue,
+            };
+
+            *field = parse_limit(value)?;
+        }
+
+        Ok(max)
+    }
+}
+
+/// Contents of `cgroup.events`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CgroupEvents {
+    /// The cgroup or one of its children has live processes
+    pub populated: bool,
+    /// The cgroup is frozen
+    pub frozen: bool,
+}
+
+impl FromStr for CgroupEvents {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<CgroupEvents> {
+        let mut events = CgroupEvents::default();
+
+        for (key, value) in keyed(s)? {
+            match key {
+                "populated" => events.populated = value != 0,
+                "frozen" => events.frozen = value != 0,
+                _ => {}
+            }
+        }
+
+        Ok(events)
+    }
+}
+
+/// Contents of `cpu.stat`
+///
+/// The throttling fields are only reported if the `cpu` controller is
+/// enabled, and are `0` otherwise.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CpuStat {
+    /// Total CPU time
+    pub usage: Duration,
+    /// CPU time in user mode
+    pub user: Duration,
+    /// CPU time in kernel mode
+    pub system: Duration,
+    /// Number of elapsed enforcement periods
+    pub nr_periods: u64,
+    /// Number of periods in which the cgroup has been throttled
+    pub nr_throttled: u64,
+    /// Total time the cgroup has been throttled
+    pub throttled: Duration,
+}
+
+impl FromStr for CpuStat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<CpuStat> {
+        let mut stat = CpuStat::default();
+
+        for (key, value) in keyed(s)? {
+            match key {
+                "usage_usec" => stat.usage = Duration::from_micros(value),
+                "user_usec" => stat.user = Duration::from_micros(value),
+                "system_usec" => stat.system = Duration::from_micros(value),
+                "nr_periods" => stat.nr_periods = value,
+                "nr_throttled" => stat.nr_throttled = value,
+                "throttled_usec" => stat.throttled = Duration::from_micros(value),
+                _ => {}
+            }
+        }
+
+        Ok(stat)
+    }
+}
+
+/// Contents of `memory.stat`
+///
+/// Sizes are in bytes. Fields not reported by the kernel are `0`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryStat {
+    /// Anonymous memory
+    pub anon: u64,
+    /// Page cache
+    pub file: u64,
+    /// Kernel memory
+    pub kernel: u64,
+    /// Kernel stacks
+    pub kernel_stack: u64,
+    /// Slab allocations
+    pub slab: u64,
+    /// Network buffers
+    pub sock: u64,
+    /// Shared memory, e.g. tmpfs
+    pub shmem: u64,
+    /// Mapped page cache
+    pub file_mapped: u64,
+    /// Dirty page cache
+    pub file_dirty: u64,
+    /// Page cache under writeback
+    pub file_writeback: u64,
+    /// Anonymous transparent huge pages
+    pub anon_thp: u64,
+    /// Number of page faults
+    pub pgfault: u64,
+    /// Number of major page faults
+    pub pgmajfault: u64,
+}
+
+impl FromStr for MemoryStat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<MemoryStat> {
+        let mut stat = MemoryStat::default();
+
+        for (key, value) in keyed(s)? {
+            let field = match key {
+                "anon" => &mut stat.anon,
+                "file" => &mut stat.file,
+                "kernel" => &mut stat.kernel,
+                "kernel_stack" => &mut stat.kernel_stack,
+                "slab" => &mut stat.slab,
+                "sock" => &mut stat.sock,
+                "shmem" => &mut stat.shmem,
+                "file_mapped" => &mut stat.file_mapped,
+                "file_dirty" => &mut stat.file_dirty,
+                "file_writeback" => &mut stat.file_writeback,
+                "anon_thp" => &mut stat.anon_thp,
+                "pgfault" => &mut stat.pgfault,
+                "pgmajfault" => &mut stat.pgmajfault,
+                _ => continue,
+            };
+
+            *field = value;
+        }
+
+        Ok(stat)
+    }
+}
+
+/// Contents of `memory.events`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryEvents {
+    /// Reclaims below the `memory.low` boundary
+    pub low: u64,
+    /// Throttling due to exceeding `memory.high`
+    pub high: u64,
+    /// Allocations about to exceed `memory.max`
+    pub max: u64,
+    /// Allocations failed due to exceeding `memory.max`
+    pub oom: u64,
+    /// Processes killed by the OOM killer
+    pub oom_kill: u64,
+}
+
+impl FromStr for MemoryEvents {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<MemoryEvents> {
+        let mut events = MemoryEvents::default();
+
+        for (key, value) in keyed(s)? {
+            let field = match key {
+                "low" => &mut events.low,
+                "high" => &mut events.high,
+                "max" => &mut events.max,
+                "oom" => &mut events.oom,
+                "oom_kill" => &mut events.oom_kill,
+                _ => continue,
+            };
+
+            *field = value;
+        }
+
+        Ok(events)
+    }
+}
+
+/// Pressure stall information of tasks stalled on a resource
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PressureStat {
+    /// Percentage of time stalled during the last 10 seconds
+    pub avg10: f64,
+    /// Percentage of time stalled during the last 60 seconds
+    pub avg60: f64,
+    /// Percentage of time stalled during the last 300 seconds
+    pub avg300: f64,
+    /// Total time stalled
+    pub total: Duration,
+}
+
+impl FromStr for PressureStat {
+    type Err = Error;
+
+    /// Parse [`PressureStat`] from a string like
+    /// `avg10=0.00 avg60=0.00 avg300=0.00 total=0`
+    fn from_str(s: &str) -> Result<PressureStat> {
+        let mut stat = PressureStat::default();
+
+        for kv in s.split_whitespace() {
+            let (key, value) = kv
+                .split_once('=')
+                .ok_or_else(|| invalid("invalid pressure"))?;
+
+            let avg = || {
+                value
+                    .parse::<f64>()
+                    .map_err(|_| invalid("invalid pressure"))
+            };
+
+            match key {
+                "avg10" => stat.avg10 = avg()?,
+                "avg60" => stat.avg60 = avg()?,
+                "avg300" => stat.avg300 = avg()?,
+                "total" => stat.total = Duration::from_micros(parse_u64(value)?),
+                _ => {}
+            }
+        }
+
+        Ok(stat)
+    }
+}
+
+/// Contents of a `*.pressure` file
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Pressure {
+    /// Some tasks are stalled
+    pub some: PressureStat,
+    /// All non-idle tasks are stalled at the same time, not reported for the
+    /// CPU in older kernels
+    pub full: Option<PressureStat>,
+}
+
+impl FromStr for Pressure {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Pressure> {
+        let mut pressure = Pressure::default();
+
+        for line in s.lines() {
+            match line.split_once(' ') {
+                Some(("some", stat)) => pressure.some = stat.parse()?,
+                Some(("full", stat)) => pressure.full = Some(stat.parse()?),
+                _ => return Err(invalid("invalid pressure")),
+            }
+        }
+
+        Ok(pressure)
+    }
+}
+
+/// Return the mountpoint of the cgroup v2 hierarchy
+pub fn cgroup_mountpoint() -> Result<PathBuf> {
+    let mounts = fs::read_to_string("/proc/self/mounts")?;
+
+    mounts
+        .lines()
+        .find_map(|line| {
+            let mut fields = line.split(' ');
+            let (_, path, fstype) = (fields.next()?, fields.next()?, fields.next()?);
+
+            (fstype == "cgroup2").then(|| PathBuf::from(unescape(path)))
+        })
+        .ok_or_else(|| invalid("cgroup2 is not mounted"))
+}
+
+/// Undo the octal escaping of whitespace and backslashes in mount paths
+fn unescape(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    let mut rest = path;
+
+    while let Some(pos) = rest.find('\\') {
+        out.push_str(&rest[..pos]);
+
+        match rest
+            .get(pos + 1..pos + 4)
+            .and_then(|oct| u8::from_str_radix(oct, 8).ok())
+        {
+            Some(c) => {
+                out.push(c as char);
+                rest = &rest[pos + 4..];
+            }
+            None => {
+                out.push('\\');
+                rest = &rest[pos + 1..];
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Parse a flat keyed file, i.e. lines of `key value`
+fn keyed(s: &str) -> Result<Vec<(&str, u64)>> {
+    s.lines()
+        .map(|line| {
+            let (key, value) = line
+                .split_once(' ')
+                .ok_or_else(|| invalid("invalid keyed file"))?;
+
+            Ok((key, parse_u64(value)?))
+        })
+        .collect()
+}
+
+fn limit(max: Option<u64>) -> String {
+    max.map_or_else(|| "max".to_string(), |max| max.to_string())
+}
+
+fn parse_limit(s: &str) -> Result<Option<u64>> {
+    match s.trim() {
+        "max" => Ok(None),
+        s => parse_u64(s).map(Some),
+    }
+}
+
+fn parse_u64(s: &str) -> Result<u64> {
+    s.trim().parse().map_err(|_| invalid("invalid number"))
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::Syscall(std::io::Error::new(std::io::ErrorKind::InvalidData, msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path, time::Duration};
+
+    use anyhow::Result;
+    use mio::{Events, Interest, Poll, Token};
+
+    use super::unescape;
+    use crate::{
+        Cgroup, CgroupEvents, CpuStat, Error, IoMax, MemoryEvents, MemoryStat, Pressure,
+        PressureStat, Resource, Signal, WaitStatus, cgroup_mountpoint, wait,
+    };
+
+    /// Forked child sleeping until it is killed, on drop at the latest
+    struct Child {
+        pid: libc::pid_t,
+        reaped: bool,
+    }
+
+    impl Child {
+        fn spawn() -> Result<Child> {
+            match syscall!(fork())? {
+                // parent
+                pid if pid != 0 => Ok(Child { pid, reaped: false }),
+                // child
+                _ => loop {
+                    std::thread::sleep(Duration::from_millis(5));
+                },
+            }
+        }
+
+        fn wait(&mut self) -> Result<WaitStatus> {
+            let status = wait(self.pid)?;
+            self.reaped = true;
+
+            Ok(status)
+        }
+    }
+
+    impl Drop for Child {
+        fn drop(&mut self) {
+            if !self.reaped {
+                unsafe { libc::kill(self.pid, libc::SIGKILL) };
+                let _ = wait(self.pid);
+            }
+        }
+    }
+
+    /// Create the cgroup `name` below `parent`, `None` if this is not
+    /// permitted
+    fn create(parent: &Cgroup, name: &str) -> Result<Option<Cgroup>> {
+        match parent.create(name) {
+            Ok(cg) => Ok(Some(cg)),
+            Err(Error::Syscall(err))
+                if matches!(err.raw_os_error(), Some(libc::EACCES | libc::EPERM)) =>
+            {
+                Ok(None)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    const MEMORY_STAT: &str = "\
+anon 1048576
+file 8192
+kernel 4096
+kernel_stack 16384
+sock 0
+shmem 4096
+file_mapped 0
+anon_thp 0
+pgfault 1234
+pgmajfault 5
+";
+
+    const PRESSURE: &str = "\
+some avg10=1.50 avg60=0.25 avg300=0.00 total=123456
+full avg10=0.00 avg60=0.00 avg300=0.00 total=42
+";
+
+    #[test]
+    fn cgroup_parse() -> Result<()> {
+        let stat: MemoryStat = MEMORY_STAT.parse()?;
+        assert_eq!(stat.anon, 1048576);
+        assert_eq!(stat.kernel_stack, 16384);
+        assert_eq!(stat.pgmajfault, 5);
+        assert_eq!(stat.slab, 0);
+
+        let stat: CpuStat = "usage_usec 30\nuser_usec 20\nsystem_usec 10\n".parse()?;
+        assert_eq!(stat.usage, Duration::from_micros(30));
+        assert_eq!(stat.nr_throttled, 0);
+
+        let events: MemoryEvents = "low 0\nhigh 1\nmax 2\noom 3\noom_kill 4\n".parse()?;
+        assert_eq!(events.oom_kill, 4);
+
+        let events: CgroupEvents = "populated 1\nfrozen 0\n".parse()?;
+        assert!(events.populated && !events.frozen);
+
+        let pressure: Pressure = PRESSURE.parse()?;
+        assert_eq!(
+            pressure.some,
+            PressureStat {
+                avg10: 1.5,
+                avg60: 0.25,
+                avg300: 0.0,
+                total: Duration::from_micros(123456),
+            }
+        );
+        assert_eq!(pressure.full.unwrap().total, Duration::from_micros(42));
+
+        let max: IoMax = "rbps=1048576 wbps=max riops=max wiops=100".parse()?;
+        assert_eq!(max.rbps, Some(1048576));
+        assert_eq!(max.wbps, None);
+        assert_eq!(max.wiops, Some(100));
+        assert_eq!(max.to_string(), "rbps=1048576 wbps=max riops=max wiops=100");
+
+        assert!("populated".parse::<CgroupEvents>().is_err());
+        assert!("some avg10=x".parse::<Pressure>().is_err());
+
+        assert_eq!(unescape(r"/mnt/with\040space\\"), r"/mnt/with space\\");
+
+        Ok(())
+    }
+
+    #[test]
+    fn cgroup_limits() -> Result<()> {
+        // a plain directory stands in for a cgroup with all controllers
+        let dir = tempfile::tempdir()?;
+        for file in ["memory.max", "pids.max", "cpu.max", "io.max"] {
+            fs::write(dir.path().join(file), "")?;
+        }
+
+        let cg = Cgroup::open(dir.path());
+        let read = |file: &str| fs::read_to_string(dir.path().join(file)).unwrap();
+
+        cg.set_memory_max(Some(64 << 20))?;
+        assert_eq!(read("memory.max"), "67108864");
+        assert_eq!(cg.memory_max()?, Some(64 << 20));
+
+        cg.set_pids_max(None)?;
+        assert_eq!(read("pids.max"), "max");
+        assert_eq!(cg.pids_max()?, None);
+
+        cg.set_cpu_max(Some(Duration::from_millis(50)), Duration::from_millis(100))?;
+        assert_eq!(read("cpu.max"), "50000 100000");
+        assert_eq!(
+            cg.cpu_max()?,
+            (Some(Duration::from_millis(50)), Duration::from_millis(100))
+        );
+
+        let max = IoMax {
+            wbps: Some(1 << 20),
+            ..Default::default()
+        };
+        cg.set_io_max((8, 16), &max)?;
+        assert_eq!(
+            read("io.max"),
+            "8:16 rbps=max wbps=1048576 riops=max wiops=max"
+        );
+        assert_eq!(cg.io_max()?, vec![((8, 16), max)]);
+
+        // interface files are never created
+        assert!(cg.write("cgroup.procs", "1").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn cgroup_procs() -> Result<()> {
+        let Ok(root) = Cgroup::root() else {
+            return Ok(());
+        };
+
+        assert_eq!(root.path(), cgroup_mountpoint()?);
+        assert!(Cgroup::of(None)?.path().starts_with(root.path()));
+
+        let parent = Cgroup::of(None)?;
+        let Some(cg) = create(&parent, &format!("syscall-rs-{}", std::process::id()))? else {
+            return Ok(());
+        };
+        assert!(parent.children()?.contains(&cg));
+
+        let mut watch = cg.watch()?;
+        let mut poll = Poll::new()?;
+        let mut events = Events::with_capacity(1);
+        poll.registry()
+            .register(&mut watch, Token(3), Interest::READABLE)?;
+
+        assert_eq!(cg.processes()?, vec![]);
+        assert!(!cg.events()?.populated);
+
+        let mut child = Child::spawn()?;
+        let pid = child.pid;
+
+        cg.add_process(pid)?;
+        assert_eq!(cg.processes()?, vec![pid]);
+        assert_eq!(Cgroup::of(pid)?, cg);
+
+        poll.poll(&mut events, Some(Duration::from_secs(5)))?;
+        assert!(!events.is_empty());
+        assert_eq!(watch.changed()?.map(|e| e.populated), Some(true));
+
+        let stat = cg.cpu_stat()?;
+        assert!(stat.usage >= stat.user);
+
+        if Path::new("/proc/pressure").exists() {
+            assert!(cg.pressure(Resource::Cpu)?.some.avg300 >= 0.0);
+        }
+
+        syscall!(kill(pid, libc::SIGKILL))?;
+        assert_eq!(
+            child.wait()?,
+            WaitStatus::Signaled(pid, Signal::SIGKILL, false)
+        );
+
+        poll.poll(&mut events, Some(Duration::from_secs(5)))?;
+        assert_eq!(watch.changed()?.map(|e| e.populated), Some(false));
+        assert_eq!(watch.changed()?, None);
+
+        cg.remove()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn cgroup_kill() -> Result<()> {
+        let Ok(parent) = Cgroup::of(None) else {
+            return Ok(());
+        };
+
+        let Some(cg) = create(&parent, &format!("syscall-rs-kill-{}", std::process::id()))? else {
+            return Ok(());
+        };
+
+        let mut child = Child::spawn()?;
+        let pid = child.pid;
+
+        cg.add_process(pid)?;
+        cg.kill()?;
+
+        assert_eq!(
+            child.wait()?,
+            WaitStatus::Signaled(pid, Signal::SIGKILL, false)
+        );
+
+        // the cgroup is depopulated asynchronously
+        while cg.events()?.populated {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        cg.remove()?;
+
+        Ok(())
+    }
+}
@@ -1,84 +1,1198 @@
-#![allow(unused)]
+//!
+//! This file is part of syscall-rs
+//!
 
-use std::path::Path;
+use std::{
+    ffi::{CStr, OsStr},
+    fmt,
+    io::{self, IoSliceMut},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    slice,
+};
 
-use elf::{ElfBytes, endian::AnyEndian};
+use ::elf::{
+    ElfBytes, abi,
+    endian::{AnyEndian, EndianParse},
+    file::{Class, FileHeader, parse_ident},
+    note::{Note, NoteIterator},
+    parse::ParseError,
+    segment::SegmentTable,
+    string_table::StringTable,
+    symbol::SymbolTable,
+};
+use bitflags::bitflags;
+use libc::{c_int, c_void};
 
-use crate::Result;
+use crate::{Error, MapPath, MemoryMaps, RemoteIoVec, Result, read_process_memory};
 
 /// Extract the Build ID from an ELF binary.
 ///
-/// This function parses the ELF headers of the given binary path and searches for
-/// the `.note.gnu.build-id` section. If found, it extracts the Build ID as a
-/// hexadecimal string.
+/// This function parses the ELF headers of the given binary path and searches its
+/// notes for the `NT_GNU_BUILD_ID` note, see [`Elf::build_id()`]. If found, it
+/// returns the Build ID as a hexadecimal string.
 pub fn build_id(binary: &Path) -> Result<Option<String>> {
-    let data = std::fs::read(binary)?;
-    let file = ElfBytes::<AnyEndian>::minimal_parse(&data)?;
+    Ok(Elf::open(binary)?.build_id()?.map(|id| id.to_string()))
+}
 
-    // get the section headers and the section header string table
-    let (headers, strtab) = file.section_headers_with_strtab()?;
+/// GNU Build ID of an ELF object
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BuildId(pub Vec<u8>);
 
-    let headers = match headers {
-        Some(h) => h,
-        None => return Ok(None),
-    };
+impl BuildId {
+    /// Return the raw bytes of the Build ID
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
 
-    let strtab = match strtab {
-        Some(s) => s,
-        None => return Ok(None),
-    };
+impl fmt::Display for BuildId {
+    /// Format the Build ID as lowercase hex string, like `readelf -n` does
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{b:02x}"))
+    }
+}
+
+/// Note of an ELF object, found in a `SHT_NOTE` section or `PT_NOTE` segment
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ElfNote {
+    /// `NT_GNU_BUILD_ID`, unique identifier of the build
+    BuildId(BuildId),
+    /// `NT_GNU_ABI_TAG`, operating system and minimum kernel version
+    AbiTag {
+        /// Operating system, e.g. `ELF_NOTE_GNU_ABI_TAG_OS_LINUX`
+        os: u32,
+        /// Earliest compatible kernel version
+        version: (u32, u32, u32),
+    },
+    /// `NT_GNU_PROPERTY_TYPE_0`, program properties
+    Properties(Vec<GnuProperty>),
+    /// Any other note
+    Other {
+        /// Note owner, e.g. `GNU` or `stapsdt`
+        name: String,
+        /// Note type
+        kind: u64,
+        /// Note descriptor
+        desc: Vec<u8>,
+    },
+}
 
-    let endian = file.ehdr.endianness;
+/// Program property of a `NT_GNU_PROPERTY_TYPE_0` note
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GnuProperty {
+    /// `GNU_PROPERTY_X86_FEATURE_1_AND`, i.e. `IBT` (`0x1`) and `SHSTK` (`0x2`)
+    X86Features(u32),
+    /// `GNU_PROPERTY_AARCH64_FEATURE_1_AND`, i.e. `BTI` (`0x1`) and `PAC` (`0x2`)
+    Aarch64Features(u32),
+    /// Any other property
+    Other {
+        /// Property type
+        kind: u32,
+        /// Property data
+        data: Vec<u8>,
+    },
+}
 
-    // iterate over all section headers
-    for shdr in headers.iter() {
-        // check if the section name is ".note.gnu.build-id"
-        if let Ok(".note.gnu.build-id") = strtab.get(shdr.sh_name as usize) {
-            // get raw section data
-            let note_data = file.section_data(&shdr)?.0;
+/// Type of a [`Segment`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SegmentKind {
+    /// `PT_LOAD`, loadable segment
+    Load,
+    /// `PT_DYNAMIC`, dynamic linking information
+    Dynamic,
+    /// `PT_INTERP`, program interpreter path
+    Interp,
+    /// `PT_NOTE`, auxiliary information
+    Note,
+    /// `PT_PHDR`, the program header table itself
+    Phdr,
+    /// `PT_TLS`, thread-local storage template
+    Tls,
+    /// `PT_GNU_EH_FRAME`, exception handling frame table
+    GnuEhFrame,
+    /// `PT_GNU_STACK`, stack permissions
+    GnuStack,
+    /// `PT_GNU_RELRO`, read-only after relocation
+    GnuRelro,
+    /// `PT_GNU_PROPERTY`, program properties
+    GnuProperty,
+    /// Any other segment type
+    Other(u32),
+}
 
-            let is_le = matches!(endian, AnyEndian::Little);
+impl From<u32> for SegmentKind {
+    fn from(kind: u32) -> Self {
+        match kind {
+            abi::PT_LOAD => SegmentKind::Load,
+            abi::PT_DYNAMIC => SegmentKind::Dynamic,
+            abi::PT_INTERP => SegmentKind::Interp,
+            abi::PT_NOTE => SegmentKind::Note,
+            abi::PT_PHDR => SegmentKind::Phdr,
+            abi::PT_TLS => SegmentKind::Tls,
+            abi::PT_GNU_EH_FRAME => SegmentKind::GnuEhFrame,
+            abi::PT_GNU_STACK => SegmentKind::GnuStack,
+            abi::PT_GNU_RELRO => SegmentKind::GnuRelro,
+            abi::PT_GNU_PROPERTY => SegmentKind::GnuProperty,
+            kind => SegmentKind::Other(kind),
+        }
+    }
+}
 
-            // helper to read u32 based on endianness
-            let read_u32 = |b: &[u8]| {
-                let bytes: [u8; 4] = b.try_into().unwrap();
-                if is_le {
-                    u32::from_le_bytes(bytes)
-                } else {
-                    u32::from_be_bytes(bytes)
-                }
-            };
+bitflags! {
+    /// Access permissions of a [`Segment`]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct SegmentFlags: u32 {
+        /// Segment is executable
+        const PF_X = abi::PF_X;
+        /// Segment is writable
+        const PF_W = abi::PF_W;
+        /// Segment is readable
+        const PF_R = abi::PF_R;
+    }
+}
+
+/// Program header of an ELF object
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Segment {
+    /// Segment type
+    pub kind: SegmentKind,
+    /// Access permissions
+    pub flags: SegmentFlags,
+    /// Offset of the segment in the file
+    pub offset: u64,
+    /// Virtual address of the segment in memory
+    pub vaddr: u64,
+    /// Size of the segment in the file
+    pub filesz: u64,
+    /// Size of the segment in memory
+    pub memsz: u64,
+    /// Alignment of the segment
+    pub align: u64,
+}
+
+bitflags! {
+    /// Flags of the `DT_FLAGS` dynamic entry
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct DynFlags: u64 {
+        /// Object may use `$ORIGIN`
+        const DF_ORIGIN = abi::DF_ORIGIN as u64;
+        /// Symbol resolution starts with the object itself
+        const DF_SYMBOLIC = abi::DF_SYMBOLIC as u64;
+        /// Object contains text relocations
+        const DF_TEXTREL = abi::DF_TEXTREL as u64;
+        /// Resolve all symbols at load time
+        const DF_BIND_NOW = abi::DF_BIND_NOW as u64;
+        /// Object uses the static TLS model
+        const DF_STATIC_TLS = abi::DF_STATIC_TLS as u64;
+    }
+}
+
+bitflags! {
+    /// Flags of the `DT_FLAGS_1` dynamic entry
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct DynFlags1: u64 {
+        /// Resolve all symbols at load time
+        const DF_1_NOW = abi::DF_1_NOW as u64;
+        /// Object can not be unloaded
+        const DF_1_NODELETE = abi::DF_1_NODELETE as u64;
+        /// Object can not be opened with `dlopen(3)`
+        const DF_1_NOOPEN = abi::DF_1_NOOPEN as u64;
+        /// Object uses `$ORIGIN`
+        const DF_1_ORIGIN = abi::DF_1_ORIGIN as u64;
+        /// Ignore the default library search path
+        const DF_1_NODEFLIB = abi::DF_1_NODEFLIB as u64;
+        /// Object is a position independent executable
+        const DF_1_PIE = abi::DF_1_PIE as u64;
+    }
+}
+
+/// Dynamic linking information of an ELF object
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Dynamic {
+    /// Required shared libraries, `DT_NEEDED`
+    pub needed: Vec<String>,
+    /// Shared object name, `DT_SONAME`
+    pub soname: Option<String>,
+    /// Library search path, `DT_RPATH`
+    pub rpath: Vec<String>,
+    /// Library search path, `DT_RUNPATH`
+    pub runpath: Vec<String>,
+    /// `DT_FLAGS`
+    pub flags: DynFlags,
+    /// `DT_FLAGS_1`
+    pub flags_1: DynFlags1,
+    /// Presence of the legacy `DT_BIND_NOW` entry
+    pub bind_now: bool,
+}
+
+impl Dynamic {
+    /// Return `true` if all symbols are resolved at load time
+    pub fn is_bind_now(&self) -> bool {
+        self.bind_now
+            || self.flags.contains(DynFlags::DF_BIND_NOW)
+            || self.flags_1.contains(DynFlags1::DF_1_NOW)
+    }
+}
+
+/// Type of a [`Symbol`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+    /// `STT_NOTYPE`
+    NoType,
+    /// `STT_OBJECT`, data object
+    Object,
+    /// `STT_FUNC`, function
+    Func,
+    /// `STT_SECTION`, section
+    Section,
+    /// `STT_FILE`, source file
+    File,
+    /// `STT_TLS`, thread-local data object
+    Tls,
+    /// `STT_GNU_IFUNC`, indirect function
+    IFunc,
+    /// Any other symbol type
+    Other(u8),
+}
+
+impl From<u8> for SymbolKind {
+    fn from(kind: u8) -> Self {
+        match kind {
+            abi::STT_NOTYPE => SymbolKind::NoType,
+            abi::STT_OBJECT => SymbolKind::Object,
+            abi::STT_FUNC => SymbolKind::Func,
+            abi::STT_SECTION => SymbolKind::Section,
+            abi::STT_FILE => SymbolKind::File,
+            abi::STT_TLS => SymbolKind::Tls,
+            abi::STT_GNU_IFUNC => SymbolKind::IFunc,
+            kind => SymbolKind::Other(kind),
+        }
+    }
+}
+
+/// Binding of a [`Symbol`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolBinding {
+    /// `STB_LOCAL`, not visible outside the object
+    Local,
+    /// `STB_GLOBAL`, visible to all objects
+    Global,
+    /// `STB_WEAK`, global with lower precedence
+    Weak,
+    /// `STB_GNU_UNIQUE`, unique within the process
+    Unique,
+    /// Any other binding
+    Other(u8),
+}
+
+impl From<u8> for SymbolBinding {
+    fn from(bind: u8) -> Self {
+        match bind {
+            abi::STB_LOCAL => SymbolBinding::Local,
+            abi::STB_GLOBAL => SymbolBinding::Global,
+            abi::STB_WEAK => SymbolBinding::Weak,
+            abi::STB_GNU_UNIQUE => SymbolBinding::Unique,
+            bind => SymbolBinding::Other(bind),
+        }
+    }
+}
+
+/// Symbol of an ELF object
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    /// Symbol name
+    pub name: String,
+    /// Symbol value, usually an address
+    pub value: u64,
+    /// Size of the symbol in bytes
+    pub size: u64,
+    /// Symbol type
+    pub kind: SymbolKind,
+    /// Symbol binding
+    pub binding: SymbolBinding,
+    /// Symbol is defined in this object, as opposed to being imported
+    pub defined: bool,
+}
+
+/// Link to a separate debug info file, `.gnu_debuglink`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DebugLink {
+    /// File name of the debug info file
+    pub file: String,
+    /// CRC32 checksum of the debug info file
+    pub crc: u32,
+}
+
+/// RELRO protection level of an ELF object
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Relro {
+    /// No `PT_GNU_RELRO` segment
+    None,
+    /// `PT_GNU_RELRO`, but the GOT is writable due to lazy binding
+    Partial,
+    /// `PT_GNU_RELRO` and immediate binding
+    Full,
+}
+
+/// Hardening properties of an ELF object
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Hardening {
+    /// Object is position independent
+    pub pie: bool,
+    /// Stack is not executable
+    pub nx_stack: bool,
+    /// RELRO protection level
+    pub relro: Relro,
+    /// All symbols are resolved at load time
+    pub bind_now: bool,
+}
+
+/// ELF object
+///
+/// Owns the contents of an ELF file and provides typed access to its
+/// headers, notes, dynamic section and symbols.
+#[derive(Clone)]
+pub struct Elf {
+    data: Vec<u8>,
+}
+
+impl Elf {
+    /// Read the ELF object at `path`
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Elf> {
+        Elf::from_bytes(std::fs::read(path)?)
+    }
+
+    /// Return the ELF object contained in `data`
+    pub fn from_bytes(data: Vec<u8>) -> Result<Elf> {
+        ElfBytes::<AnyEndian>::minimal_parse(&data)?;
+
+        Ok(Elf { data })
+    }
+
+    /// Return the raw contents of the ELF object
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn file(&self) -> Result<ElfBytes<'_, AnyEndian>> {
+        Ok(ElfBytes::minimal_parse(&self.data)?)
+    }
+
+    /// Return the object type, e.g. `ET_EXEC` or `ET_DYN`
+    pub fn kind(&self) -> Result<u16> {
+        Ok(self.file()?.ehdr.e_type)
+    }
+
+    /// Return the target architecture, e.g. `EM_X86_64`
+    pub fn machine(&self) -> Result<u16> {
+        Ok(self.file()?.ehdr.e_machine)
+    }
+
+    /// Return the entry point address
+    pub fn entry(&self) -> Result<u64> {
+        Ok(self.file()?.ehdr.e_entry)
+    }
 
-            let mut pos = 0;
+    /// Return the program headers
+    pub fn segments(&self) -> Result<Vec<Segment>> {
+        let Some(phdrs) = self.file()?.segments() else {
+            return Ok(Vec::new());
+        };
 
-            // iterate over section notes
-            while pos + 12 <= note_data.len() {
-                // read note header: namesz, descsz, type
-                let n_namesz = read_u32(&note_data[pos..pos + 4]) as usize;
-                let n_descsz = read_u32(&note_data[pos + 4..pos + 8]) as usize;
-                let n_type = read_u32(&note_data[pos + 8..pos + 12]);
-                pos += 12;
+        Ok(phdrs
+            .iter()
+            .map(|phdr| Segment {
+                kind: phdr.p_type.into(),
+                flags: SegmentFlags::from_bits_truncate(phdr.p_flags),
+                offset: phdr.p_offset,
+                vaddr: phdr.p_vaddr,
+                filesz: phdr.p_filesz,
+                memsz: phdr.p_memsz,
+                align: phdr.p_align,
+            })
+            .collect())
+    }
+
+    /// Return the program interpreter, i.e. the dynamic loader, if any
+    pub fn interpreter(&self) -> Result<Option<PathBuf>> {
+        let file = self.file()?;
+
+        let Some(phdrs) = file.segments() else {
+            return Ok(None);
+        };
+
+        match phdrs.iter().find(|phdr| phdr.p_type == abi::PT_INTERP) {
+            Some(phdr) => Ok(Some(cstr(file.segment_data(&phdr)?)?.into())),
+            None => Ok(None),
+        }
+    }
+
+    /// Return all notes
+    ///
+    /// Notes are read from the `SHT_NOTE` sections, or from the `PT_NOTE`
+    /// segments in case the section headers have been stripped.
+    pub fn notes(&self) -> Result<Vec<ElfNote>> {
+        let file = self.file()?;
+
+        raw_notes(&file)?
+            .into_iter()
+            .map(|note| to_note(note, file.ehdr.endianness, file.ehdr.class))
+            .collect()
+    }
 
-                // create 4-byte aligned sizes
-                let name_align = (n_namesz + 3) & !3;
-                let desc_align = (n_descsz + 3) & !3;
+    /// Return the GNU Build ID, if any
+    ///
+    /// Unlike [`Elf::notes()`], this doesn't fail because of a malformed
+    /// note of another type.
+    pub fn build_id(&self) -> Result<Option<BuildId>> {
+        Ok(raw_notes(&self.file()?)?
+            .into_iter()
+            .find_map(|note| match note {
+                Note::GnuBuildId(id) => Some(BuildId(id.0.to_vec())),
+                _ => None,
+            }))
+    }
+
+    /// Return the dynamic linking information, if the object is dynamically
+    /// linked
+    pub fn dynamic(&self) -> Result<Option<Dynamic>> {
+        let file = self.file()?;
+        let common = file.find_common_data()?;
+
+        let (Some(table), Some(strs)) = (common.dynamic, common.dynsyms_strs) else {
+            return Ok(None);
+        };
+
+        let mut dynamic = Dynamic::default();
+
+        for entry in table.iter() {
+            match entry.d_tag {
+                abi::DT_NEEDED => dynamic.needed.push(string(&strs, entry.d_val())?),
+                abi::DT_SONAME => dynamic.soname = Some(string(&strs, entry.d_val())?),
+                abi::DT_RPATH => dynamic.rpath = paths(&string(&strs, entry.d_val())?),
+                abi::DT_RUNPATH => dynamic.runpath = paths(&string(&strs, entry.d_val())?),
+                abi::DT_FLAGS => dynamic.flags = DynFlags::from_bits_truncate(entry.d_val()),
+                abi::DT_FLAGS_1 => dynamic.flags_1 = DynFlags1::from_bits_truncate(entry.d_val()),
+                abi::DT_BIND_NOW => dynamic.bind_now = true,
+                _ => {}
+            }
+        }
+
+        Ok(Some(dynamic))
+    }
 
-                if pos + name_align + desc_align > note_data.len() {
-                    break;
+    /// Return the symbols of `.symtab`, or of `.dynsym` in case the object
+    /// has been stripped
+    pub fn symbols(&self) -> Result<Vec<Symbol>> {
+        let file = self.file()?;
+        let common = file.find_common_data()?;
+
+        let tables = [
+            (common.symtab, common.symtab_strs),
+            (common.dynsyms, common.dynsyms_strs),
+        ];
+
+        for (table, strs) in tables {
+            if let (Some(table), Some(strs)) = (table, strs) {
+                return symbols(&table, &strs).collect();
+            }
+        }
+
+        Ok(Vec::new())
+    }
+
+    /// Lookup the symbol `name` in `.symtab` and then in `.dynsym`
+    ///
+    /// A symbol defined in the object takes precedence over an undefined
+    /// one of the same name.
+    pub fn symbol(&self, name: &str) -> Result<Option<Symbol>> {
+        let file = self.file()?;
+        let common = file.find_common_data()?;
+
+        let tables = [
+            (common.symtab, common.symtab_strs),
+            (common.dynsyms, common.dynsyms_strs),
+        ];
+
+        let mut undefined = None;
+
+        for (table, strs) in tables {
+            let (Some(table), Some(strs)) = (table, strs) else {
+                continue;
+            };
+
+            for sym in symbols(&table, &strs) {
+                let sym = sym?;
+
+                if sym.name != name {
+                    continue;
                 }
 
-                // check for NT_GNU_BUILD_ID (type 3) and name "GNU\0"
-                if n_type == 3 && n_namesz == 4 && &note_data[pos..pos + 4] == b"GNU\0" {
-                    let desc = &note_data[pos + name_align..pos + name_align + n_descsz];
-                    // convert build ID bytes to hex string
-                    let hex: String = desc.iter().map(|b| format!("{:02x}", b)).collect();
-                    return Ok(Some(hex));
+                if sym.defined {
+                    return Ok(Some(sym));
                 }
 
-                pos += name_align + desc_align;
+                undefined.get_or_insert(sym);
             }
         }
+
+        Ok(undefined)
+    }
+
+    /// Return the link to the separate debug info file, if any
+    pub fn debuglink(&self) -> Result<Option<DebugLink>> {
+        let file = self.file()?;
+
+        let Some(shdr) = file.section_header_by_name(".gnu_debuglink")? else {
+            return Ok(None);
+        };
+
+        let (data, _) = file.section_data(&shdr)?;
+
+        debuglink(data, file.ehdr.endianness).map(Some)
+    }
+
+    /// Return the hardening properties
+    pub fn hardening(&self) -> Result<Hardening> {
+        let segments = self.segments()?;
+        let dynamic = self.dynamic()?;
+
+        let bind_now = dynamic.as_ref().is_some_and(Dynamic::is_bind_now);
+        let interp = segments.iter().any(|s| s.kind == SegmentKind::Interp);
+        let pie_flag = dynamic
+            .as_ref()
+            .is_some_and(|d| d.flags_1.contains(DynFlags1::DF_1_PIE));
+
+        // without PT_GNU_STACK the kernel defaults to an executable stack
+        let nx_stack = segments
+            .iter()
+            .find(|s| s.kind == SegmentKind::GnuStack)
+            .is_some_and(|s| !s.flags.contains(SegmentFlags::PF_X));
+
+        let relro = match segments.iter().any(|s| s.kind == SegmentKind::GnuRelro) {
+            false => Relro::None,
+            true if bind_now => Relro::Full,
+            true => Relro::Partial,
+        };
+
+        Ok(Hardening {
+            pie: self.kind()? == abi::ET_DYN && (pie_flag || interp),
+            nx_stack,
+            relro,
+            bind_now,
+        })
+    }
+}
+
+impl fmt::Debug for Elf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Elf")
+            .field("len", &self.data.len())
+            .finish()
+    }
+}
+
+/// ELF object mapped into the address space of a process
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoadedObject {
+    /// What the object is backed by
+    pub path: MapPath,
+    /// Address the ELF header of the object is mapped at
+    pub base: u64,
+    /// GNU Build ID of the object, as found in memory
+    pub build_id: Option<BuildId>,
+}
+
+/// Return the ELF objects loaded into the calling process
+///
+/// The objects are enumerated with `dl_iterate_phdr(3)`, and their Build
+/// IDs are read from the mapped `PT_NOTE` segments. Unlike [`build_id()`],
+/// this reports the identity of the code actually running, even if the
+/// file on disk has been replaced or bind-mounted over after it was loaded.
+pub fn loaded_objects() -> Vec<LoadedObject> {
+    let mut objects: Vec<LoadedObject> = Vec::new();
+
+    unsafe {
+        libc::dl_iterate_phdr(
+            Some(loaded_object),
+            &mut objects as *mut Vec<LoadedObject> as *mut c_void,
+        )
+    };
+
+    objects
+}
+
+/// Return the ELF objects mapped into the process `pid`
+///
+/// Every file mapping at offset `0` which starts with an ELF header is
+/// reported. The Build ID is read from the mapped ELF headers using
+/// [`read_process_memory()`]. If the notes are not mapped, the object is
+/// read from `/proc/<pid>/map_files`, which always refers to the mapped
+/// file, even if it has been deleted or replaced since. Reading
+/// `map_files` requires `CAP_SYS_ADMIN` or `CAP_CHECKPOINT_RESTORE`, objects
+/// whose Build ID can't be read either way are reported without one.
+///
+/// The caller requires ptrace access to `pid`. Mappings which can't be read
+/// are skipped.
+pub fn process_objects(pid: libc::pid_t) -> Result<Vec<LoadedObject>> {
+    let mut objects = Vec::new();
+
+    for map in MemoryMaps::new(pid)? {
+        let map = map?;
+
+        let mapped = matches!(
+            map.path,
+            MapPath::File(_) | MapPath::Deleted(_) | MapPath::Vdso
+        );
+
+        if !mapped || map.offset != 0 || !map.perms.read {
+            continue;
+        }
+
+        // skip data files like the locale archive, and unreadable mappings
+        if !read_remote(pid, map.start, ELFMAG.len()).is_ok_and(|magic| magic == ELFMAG) {
+            continue;
+        }
+
+        let build_id = match remote_build_id(pid, map.start) {
+            Ok(build_id) => build_id,
+            Err(_) if map.path != MapPath::Vdso => Elf::open(format!(
+                "/proc/{pid}/map_files/{:x}-{:x}",
+                map.start, map.end
+            ))
+            .and_then(|elf| elf.build_id())
+            .unwrap_or_default(),
+            Err(_) => None,
+        };
+
+        objects.push(LoadedObject {
+            path: map.path,
+            base: map.start,
+            build_id,
+        });
+    }
+
+    Ok(objects)
+}
+
+/// `dl_iterate_phdr(3)` callback collecting a [`LoadedObject`]
+unsafe extern "C" fn loaded_object(
+    info: *mut libc::dl_phdr_info,
+    _size: usize,
+    data: *mut c_void,
+) -> c_int {
+    // safety: `data` points to the vector passed to dl_iterate_phdr()
+    let (info, objects) = unsafe { (&*info, &mut *(data as *mut Vec<LoadedObject>)) };
+
+    let phdrs = unsafe { slice::from_raw_parts(info.dlpi_phdr, info.dlpi_phnum as usize) };
+    let bias = info.dlpi_addr;
+
+    let name = if info.dlpi_name.is_null() {
+        b""
+    } else {
+        unsafe { CStr::from_ptr(info.dlpi_name) }.to_bytes()
+    };
+
+    let path = match name {
+        // the main program
+        b"" => std::env::current_exe()
+            .map(MapPath::File)
+            .unwrap_or(MapPath::Anonymous),
+        name if name.starts_with(b"linux-vdso") || name.starts_with(b"linux-gate") => MapPath::Vdso,
+        // paths need not be valid UTF-8
+        name => MapPath::File(OsStr::from_bytes(name).into()),
+    };
+
+    let first = phdrs
+        .iter()
+        .filter(|phdr| phdr.p_type == libc::PT_LOAD)
+        .map(|phdr| phdr.p_vaddr)
+        .min()
+        .unwrap_or_default();
+
+    let build_id = phdrs
+        .iter()
+        .filter(|phdr| phdr.p_type == libc::PT_NOTE)
+        .find_map(|phdr| {
+            let addr = bias.wrapping_add(phdr.p_vaddr);
+            // safety: PT_NOTE segments are part of a loaded PT_LOAD segment
+            let data = unsafe { slice::from_raw_parts(addr as *const u8, phdr.p_memsz as usize) };
+
+            find_build_id(NATIVE_ENDIAN, NATIVE_CLASS, phdr.p_align as usize, data)
+        });
+
+    objects.push(LoadedObject {
+        path,
+        base: bias.wrapping_add(first) & !(page_size() - 1),
+        build_id,
+    });
+
+    0
+}
+
+#[cfg(target_endian = "little")]
+const NATIVE_ENDIAN: AnyEndian = AnyEndian::Little;
+#[cfg(target_endian = "big")]
+const NATIVE_ENDIAN: AnyEndian = AnyEndian::Big;
+
+#[cfg(target_pointer_width = "64")]
+const NATIVE_CLASS: Class = Class::ELF64;
+#[cfg(target_pointer_width = "32")]
+const NATIVE_CLASS: Class = Class::ELF32;
+
+/// ELF magic number
+const ELFMAG: &[u8] = b"\x7fELF";
+
+/// Size of the larger ELF64 file header
+const EHDR_SIZE: usize = 64;
+
+/// Size of the larger ELF64 program header
+const PHDR_SIZE: usize = 56;
+
+/// Limit for the size of a `PT_NOTE` segment read from another process
+const NOTES_LIMIT: usize = 64 * 1024;
+
+/// Read the Build ID of the ELF object whose header is mapped at `base` in
+/// the process `pid`
+fn remote_build_id(pid: libc::pid_t, base: u64) -> Result<Option<BuildId>> {
+    let ehdr = read_remote(pid, base, EHDR_SIZE)?;
+
+    let ident = parse_ident::<AnyEndian>(&ehdr[..abi::EI_NIDENT])?;
+    let (endian, class) = (ident.0, ident.1);
+    let ehdr = FileHeader::parse_tail(ident, &ehdr[abi::EI_NIDENT..])?;
+
+    // the headers are controlled by the remote process, don't trust them
+    if ehdr.e_phentsize as usize > PHDR_SIZE {
+        return Err("invalid program header size".into());
+    }
+
+    let phdrs = read_remote(
+        pid,
+        base.wrapping_add(ehdr.e_phoff),
+        ehdr.e_phentsize as usize * ehdr.e_phnum as usize,
+    )?;
+    let phdrs = SegmentTable::new(endian, class, &phdrs);
+
+    // the ELF header is mapped at the page of the lowest PT_LOAD segment
+    let first = phdrs
+        .iter()
+        .filter(|phdr| phdr.p_type == abi::PT_LOAD)
+        .map(|phdr| phdr.p_vaddr)
+        .min()
+        .unwrap_or_default();
+    let bias = base.wrapping_sub(first & !(page_size() - 1));
+
+    for phdr in phdrs.iter().filter(|phdr| phdr.p_type == abi::PT_NOTE) {
+        if phdr.p_filesz > NOTES_LIMIT as u64 {
+            return Err("note segment too large".into());
+        }
+
+        let data = read_remote(pid, bias.wrapping_add(phdr.p_vaddr), phdr.p_filesz as usize)?;
+
+        if let Some(id) = find_build_id(endian, class, phdr.p_align as usize, &data) {
+            return Ok(Some(id));
+        }
     }
 
     Ok(None)
 }
+
+/// Read exactly `len` bytes at `addr` from the process `pid`
+fn read_remote(pid: libc::pid_t, addr: u64, len: usize) -> Result<Vec<u8>> {
+    let mut buf = vec![0; len];
+
+    let n = read_process_memory(
+        pid,
+        &[RemoteIoVec::new(addr as usize, len)],
+        &mut [IoSliceMut::new(&mut buf)],
+    )?;
+
+    if n < len {
+        return Err(Error::Syscall(io::ErrorKind::UnexpectedEof.into()));
+    }
+
+    Ok(buf)
+}
+
+fn find_build_id(endian: AnyEndian, class: Class, align: usize, data: &[u8]) -> Option<BuildId> {
+    NoteIterator::new(endian, class, align, data).find_map(|note| match note {
+        Note::GnuBuildId(id) => Some(BuildId(id.0.to_vec())),
+        _ => None,
+    })
+}
+
+fn page_size() -> u64 {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as u64 }
+}
+
+/// Return the raw notes of `file`, see [`Elf::notes()`]
+fn raw_notes<'a>(file: &ElfBytes<'a, AnyEndian>) -> Result<Vec<Note<'a>>> {
+    let mut notes = Vec::new();
+
+    if let Some(shdrs) = file.section_headers() {
+        for shdr in shdrs.iter().filter(|shdr| shdr.sh_type == abi::SHT_NOTE) {
+            notes.extend(file.section_data_as_notes(&shdr)?);
+        }
+    } else if let Some(phdrs) = file.segments() {
+        for phdr in phdrs.iter().filter(|phdr| phdr.p_type == abi::PT_NOTE) {
+            notes.extend(file.segment_data_as_notes(&phdr)?);
+        }
+    }
+
+    Ok(notes)
+}
+
+fn to_note(note: Note<'_>, endian: AnyEndian, class: Class) -> Result<ElfNote> {
+    Ok(match note {
+        Note::GnuBuildId(id) => ElfNote::BuildId(BuildId(id.0.to_vec())),
+        Note::GnuAbiTag(tag) => ElfNote::AbiTag {
+            os: tag.os,
+            version: (tag.major, tag.minor, tag.subminor),
+        },
+        Note::Unknown(any)
+            if any.name == abi::ELF_NOTE_GNU && any.n_type == abi::NT_GNU_PROPERTY_TYPE_0 =>
+        {
+            ElfNote::Properties(properties(any.desc, endian, class)?)
+        }
+        Note::Unknown(any) => ElfNote::Other {
+            name: any.name_str()?.to_string(),
+            kind: any.n_type,
+            desc: any.desc.to_vec(),
+        },
+    })
+}
+
+/// Parse the properties of a `NT_GNU_PROPERTY_TYPE_0` note descriptor
+///
+/// Each property is a `pr_type`, `pr_datasz` pair followed by the data,
+/// padded to 8 bytes for 64-bit objects and 4 bytes for 32-bit ones.
+fn properties(desc: &[u8], endian: AnyEndian, class: Class) -> Result<Vec<GnuProperty>> {
+    let align = match class {
+        Class::ELF64 => 8,
+        Class::ELF32 => 4,
+    };
+
+    let mut props = Vec::new();
+    let mut offset = 0;
+
+    while offset < desc.len() {
+        let kind = endian.parse_u32_at(&mut offset, desc)?;
+        let size = endian.parse_u32_at(&mut offset, desc)? as usize;
+
+        let data = desc
+            .get(offset..offset + size)
+            .ok_or(ParseError::SliceReadError((offset, offset + size)))?;
+
+        let features = || endian.parse_u32_at(&mut 0, data);
+
+        props.push(match kind {
+            GNU_PROPERTY_X86_FEATURE_1_AND => GnuProperty::X86Features(features()?),
+            abi::GNU_PROPERTY_AARCH64_FEATURE_1_AND => GnuProperty::Aarch64Features(features()?),
+            kind => GnuProperty::Other {
+                kind,
+                data: data.to_vec(),
+            },
+        });
+
+        offset = (offset + size).next_multiple_of(align);
+    }
+
+    Ok(props)
+}
+
+/// Missing from [`abi`]
+const GNU_PROPERTY_X86_FEATURE_1_AND: u32 = 0xc0000002;
+
+/// Parse the contents of a `.gnu_debuglink` section
+///
+/// The section contains the nul terminated file name, padded to 4 bytes,
+/// followed by the CRC32 checksum.
+fn debuglink(data: &[u8], endian: AnyEndian) -> Result<DebugLink> {
+    let file = cstr(data)?;
+    let mut offset = (file.len() + 1).next_multiple_of(4);
+
+    Ok(DebugLink {
+        file,
+        crc: endian.parse_u32_at(&mut offset, data)?,
+    })
+}
+
+fn symbols<'a, E: EndianParse>(
+    table: &'a SymbolTable<'_, E>,
+    strs: &'a StringTable<'_>,
+) -> impl Iterator<Item = Result<Symbol>> + 'a {
+    table
+        .iter()
+        // skip the reserved null symbol
+        .skip(1)
+        .map(|sym| {
+            Ok(Symbol {
+                name: strs.get(sym.st_name as usize)?.to_string(),
+                value: sym.st_value,
+                size: sym.st_size,
+                kind: sym.st_symtype().into(),
+                binding: sym.st_bind().into(),
+                defined: !sym.is_undefined(),
+            })
+        })
+}
+
+fn string(strs: &StringTable<'_>, offset: u64) -> Result<String> {
+    Ok(strs.get(offset as usize)?.to_string())
+}
+
+fn paths(s: &str) -> Vec<String> {
+    s.split(':').map(str::to_string).collect()
+}
+
+fn cstr(data: &[u8]) -> Result<String> {
+    let s = CStr::from_bytes_until_nul(data).map_err(|_| "missing nul terminator")?;
+
+    Ok(s.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Duration};
+
+    use ::elf::{abi, endian::AnyEndian, file::Class};
+    use anyhow::Result;
+
+    use super::{debuglink, properties};
+    use crate::{
+        Command, DebugLink, Elf, ElfNote, GnuProperty, MapPath, MemoryMaps, Relro, SegmentFlags,
+        SegmentKind, Signal, SymbolBinding, SymbolKind, build_id, loaded_objects, process_objects,
+    };
+
+    #[test]
+    fn elf_self() -> Result<()> {
+        let exe = std::env::current_exe()?;
+        let elf = Elf::open(&exe)?;
+
+        assert_eq!(elf.kind()?, abi::ET_DYN);
+        assert!(
+            elf.interpreter()?
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .contains("ld-")
+        );
+
+        let segments = elf.segments()?;
+        assert!(
+            segments.iter().any(|s| s.kind == SegmentKind::Load
+                && s.flags == SegmentFlags::PF_R | SegmentFlags::PF_X)
+        );
+
+        let id = elf.build_id()?.unwrap();
+        assert_eq!(build_id(&exe)?, Some(id.to_string()));
+
+        assert!(
+            elf.notes()?
+                .iter()
+                .any(|n| matches!(n, ElfNote::AbiTag { .. }))
+        );
+
+        let dynamic = elf.dynamic()?.unwrap();
+        assert!(dynamic.needed.iter().any(|lib| lib == "libc.so.6"));
+        assert_eq!(dynamic.soname, None);
+
+        let hardening = elf.hardening()?;
+        assert!(hardening.pie);
+        assert!(hardening.nx_stack);
+        assert_eq!(hardening.relro == Relro::Full, hardening.bind_now);
+
+        Ok(())
+    }
+
+    #[test]
+    fn elf_symbols() -> Result<()> {
+        let elf = Elf::open(std::env::current_exe()?)?;
+
+        let sym = elf.symbol("main")?.unwrap();
+        assert_eq!(sym.kind, SymbolKind::Func);
+        assert_eq!(sym.binding, SymbolBinding::Global);
+        assert!(sym.defined);
+
+        let sym = elf.symbol("malloc")?.unwrap();
+        assert!(!sym.defined);
+
+        assert!(elf.symbol("no_such_symbol")?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn elf_libc() -> Result<()> {
+        let libc = MemoryMaps::new(None)?
+            .filter_map(|map| match map.ok()?.path {
+                MapPath::File(path) if path.to_str()?.contains("libc.so") => Some(path),
+                _ => None,
+            })
+            .next()
+            .unwrap();
+
+        let elf = Elf::open(libc)?;
+
+        assert_eq!(elf.dynamic()?.unwrap().soname.as_deref(), Some("libc.so.6"));
+
+        let sym = elf.symbol("malloc")?.unwrap();
+        assert!(sym.defined);
+        assert_eq!(sym.kind, SymbolKind::Func);
+
+        Ok(())
+    }
+
+    #[test]
+    fn elf_debuglink() -> Result<()> {
+        let data = b"app.debug\0\0\0\x78\x56\x34\x12";
+
+        assert_eq!(
+            debuglink(data, AnyEndian::Little)?,
+            DebugLink {
+                file: "app.debug".into(),
+                crc: 0x12345678,
+            }
+        );
+
+        assert!(debuglink(b"app.debug", AnyEndian::Little).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn elf_properties() -> Result<()> {
+        let desc = [
+            0x02, 0x00, 0x00, 0xc0, // GNU_PROPERTY_X86_FEATURE_1_AND
+            0x04, 0x00, 0x00, 0x00, // pr_datasz
+            0x03, 0x00, 0x00, 0x00, // IBT | SHSTK
+            0x00, 0x00, 0x00, 0x00, // padding
+            0x01, 0x80, 0x00, 0xc0, // unknown
+            0x04, 0x00, 0x00, 0x00, // pr_datasz
+            0x01, 0x00, 0x00, 0x00, // data
+            0x00, 0x00, 0x00, 0x00, // padding
+        ];
+
+        assert_eq!(
+            properties(&desc, AnyEndian::Little, Class::ELF64)?,
+            vec![
+                GnuProperty::X86Features(3),
+                GnuProperty::Other {
+                    kind: 0xc0008001,
+                    data: vec![1, 0, 0, 0],
+                },
+            ]
+        );
+
+        // 32-bit objects don't pad to 8 bytes, even if the size is a multiple
+        let desc = [
+            0x02, 0x00, 0x00, 0xc0, // GNU_PROPERTY_X86_FEATURE_1_AND
+            0x04, 0x00, 0x00, 0x00, // pr_datasz
+            0x01, 0x00, 0x00, 0x00, // IBT
+            0x01, 0x80, 0x00, 0xc0, // unknown
+            0x04, 0x00, 0x00, 0x00, // pr_datasz
+            0x02, 0x00, 0x00, 0x00, // data
+        ];
+
+        assert_eq!(
+            properties(&desc, AnyEndian::Little, Class::ELF32)?,
+            vec![
+                GnuProperty::X86Features(1),
+                GnuProperty::Other {
+                    kind: 0xc0008001,
+                    data: vec![2, 0, 0, 0],
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn elf_invalid() {
+        assert!(Elf::from_bytes(b"\x7fELF".to_vec()).is_err());
+        assert!(Elf::from_bytes(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn loaded_objects_self() -> Result<()> {
+        let exe = std::env::current_exe()?;
+        let objects = loaded_objects();
+
+        let main = objects
+            .iter()
+            .find(|obj| obj.path == MapPath::File(exe.clone()))
+            .unwrap();
+        assert_eq!(main.build_id, Elf::open(&exe)?.build_id()?);
+
+        let libc = objects
+            .iter()
+            .find_map(|obj| match &obj.path {
+                MapPath::File(path) if path.to_str()?.contains("libc.so") => Some((path, obj)),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(libc.1.build_id, Elf::open(libc.0)?.build_id()?);
+
+        assert!(objects.iter().any(|obj| obj.path == MapPath::Vdso));
+
+        Ok(())
+    }
+
+    #[test]
+    fn process_objects_self() -> Result<()> {
+        let loaded = loaded_objects();
+        let mapped = process_objects(std::process::id() as libc::pid_t)?;
+
+        // every object loaded by the dynamic linker is mapped, with the same
+        // Build ID at the same address
+        for obj in loaded {
+            let found = mapped.iter().find(|m| m.base == obj.base).unwrap();
+            assert_eq!(found.build_id, obj.build_id);
+            assert!(found.build_id.is_some());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn process_objects_replaced() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("sleep");
+
+        fs::copy("/usr/bin/sleep", &path)?;
+        let original = Elf::open(&path)?.build_id()?;
+
+        let mut child = Command::new(&path).arg("10").spawn()?;
+
+        // wait for the exec to complete
+        let mut tries = 0;
+        while !process_objects(child.id())?
+            .iter()
+            .any(|obj| obj.path == MapPath::File(path.clone()))
+        {
+            assert!(tries < 100);
+            tries += 1;
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        // replace the binary on disk
+        let other = dir.path().join("true");
+        fs::copy("/usr/bin/true", &other)?;
+        fs::rename(&other, &path)?;
+        assert_ne!(Elf::open(&path)?.build_id()?, original);
+
+        let objects = process_objects(child.id())?;
+        let obj = objects
+            .iter()
+            .find(|obj| obj.path == MapPath::Deleted(path.clone()))
+            .unwrap();
+        assert_eq!(obj.build_id, original);
+
+        child.kill(Signal::SIGKILL)?;
+        child.wait()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,178 @@
+//!
+//! This file is part of syscall-rs
+//!
+
+use std::os::unix::prelude::{AsFd, AsRawFd, BorrowedFd, FromRawFd, RawFd};
+
+use libc::c_int;
+use mio::{Interest, Registry, Token, event, unix::SourceFd};
+
+use crate::{Error, FileDesc, Result, libc_bitflags};
+
+libc_bitflags! {
+    /// Flags for creating an [`EventFd`]
+    pub struct EfdFlags: c_int {
+        /// Close the event fd on `exec`
+        EFD_CLOEXEC;
+        /// Nonblocking reads and writes
+        EFD_NONBLOCK;
+        /// Reads decrement the counter by one instead of resetting it
+        EFD_SEMAPHORE;
+    }
+}
+
+/// File descriptor for event notification
+///
+/// An [`EventFd`] holds a 64-bit counter. [`EventFd::add()`] increments the
+/// counter, and the fd is readable as long as the counter is not zero. This
+/// makes it a cheap wakeup source for event loops, e.g. to wake up a
+/// [`mio::Poll`] from another thread.
+#[derive(Debug)]
+pub struct EventFd(FileDesc);
+
+impl EventFd {
+    /// Return a new [`EventFd`] with its counter set to `initval`
+    pub fn new(initval: u32, flags: EfdFlags) -> Result<EventFd> {
+        let fd = syscall!(eventfd(initval, flags.bits()))?;
+
+        Ok(EventFd(unsafe { FileDesc::from_raw_fd(fd) }))
+    }
+
+    /// Add `value` to the counter
+    ///
+    /// This will **block** if the counter would overflow, unless the
+    /// [`EventFd`] has been created using [`EfdFlags::EFD_NONBLOCK`].
+    pub fn add(&self, value: u64) -> Result<()> {
+        self.0.write(&value.to_ne_bytes())?;
+
+        Ok(())
+    }
+
+    /// Read the counter
+    ///
+    /// Return the counter value and reset it to zero. In semaphore mode,
+    /// return `1` and decrement the counter by one instead.
+    ///
+    /// This will **block** while the counter is zero, unless the [`EventFd`]
+    /// has been created using [`EfdFlags::EFD_NONBLOCK`], in which case it
+    /// fails with `EAGAIN`.
+    pub fn read(&self) -> Result<u64> {
+        let mut buf = [0u8; 8];
+
+        if self.0.read(&mut buf)? != buf.len() {
+            return Err(Error::Syscall(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "short eventfd read",
+            )));
+        }
+
+        Ok(u64::from_ne_bytes(buf))
+    }
+}
+
+impl AsRawFd for EventFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl AsFd for EventFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+impl event::Source for EventFd {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> std::io::Result<()> {
+        SourceFd(&self.0.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> std::io::Result<()> {
+        SourceFd(&self.0.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> std::io::Result<()> {
+        SourceFd(&self.0.as_raw_fd()).deregister(registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread, time::Duration};
+
+    use anyhow::Result;
+    use mio::{Events, Interest, Poll, Token};
+
+    use crate::{EfdFlags, EventFd};
+
+    #[test]
+    fn eventfd_counter() -> Result<()> {
+        let efd = EventFd::new(2, EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK)?;
+
+        efd.add(3)?;
+        efd.add(4)?;
+
+        assert_eq!(efd.read()?, 9);
+
+        assert_eq!(
+            format!("{}", efd.read().err().unwrap()),
+            "System call error: Resource temporarily unavailable (os error 11)"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn eventfd_semaphore() -> Result<()> {
+        let efd = EventFd::new(0, EfdFlags::EFD_SEMAPHORE | EfdFlags::EFD_NONBLOCK)?;
+
+        efd.add(2)?;
+
+        assert_eq!(efd.read()?, 1);
+        assert_eq!(efd.read()?, 1);
+        assert!(efd.read().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn eventfd_wakeup() -> Result<()> {
+        const WAKE: Token = Token(1);
+
+        let mut efd = EventFd::new(0, EfdFlags::EFD_NONBLOCK)?;
+
+        let mut poll = Poll::new()?;
+        let mut events = Events::with_capacity(1);
+
+        poll.registry()
+            .register(&mut efd, WAKE, Interest::READABLE)?;
+
+        poll.poll(&mut events, Some(Duration::from_millis(10)))?;
+        assert!(events.is_empty());
+
+        let efd = Arc::new(efd);
+        let waker = efd.clone();
+
+        thread::spawn(move || waker.add(1)).join().unwrap()?;
+
+        poll.poll(&mut events, Some(Duration::from_secs(5)))?;
+
+        let evt = events.iter().next().unwrap();
+        assert_eq!(evt.token(), WAKE);
+        assert!(evt.is_readable());
+
+        assert_eq!(efd.read()?, 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,648 @@
+//!
+//! This file is part of syscall-rs
+//!
+
+use std::{
+    ffi::{CString, OsString},
+    io::ErrorKind,
+    mem,
+    os::unix::{
+        ffi::{OsStrExt, OsStringExt},
+        prelude::{AsFd, AsRawFd, BorrowedFd, FromRawFd, RawFd},
+    },
+    path::Path,
+    ptr,
+};
+
+use libc::{c_int, c_uint};
+use mio::{Interest, Registry, Token, event, unix::SourceFd};
+
+use crate::{Error, FileDesc, OFlags, Result, libc_bitflags};
+
+/// Size of the buffer for reading events
+const EVENT_BUF_LEN: usize = 4096;
+
+libc_bitflags! {
+    /// Flags for creating a [`Fanotify`] instance
+    ///
+    /// Without a `FAN_CLASS_*` flag, the instance is a notification-only
+    /// listener.
+    pub struct FanInitFlags: c_uint {
+        /// Close the fanotify fd on `exec`
+        FAN_CLOEXEC;
+        /// Nonblocking reads
+        FAN_NONBLOCK;
+        /// Receive permission events for accessing the final content
+        FAN_CLASS_CONTENT;
+        /// Receive permission events before the content is final
+        FAN_CLASS_PRE_CONTENT;
+        /// Don't limit the number of queued events
+        FAN_UNLIMITED_QUEUE;
+        /// Don't limit the number of marks
+        FAN_UNLIMITED_MARKS;
+        /// Report the thread ID instead of the process ID
+        FAN_REPORT_TID;
+        /// Report file handles instead of file descriptors
+        FAN_REPORT_FID;
+        /// Report file handles of the parent directory
+        FAN_REPORT_DIR_FID;
+        /// Report names of directory entries, requires `FAN_REPORT_DIR_FID`
+        FAN_REPORT_NAME;
+        /// Report file handles of the target of directory entry events
+        FAN_REPORT_TARGET_FID;
+        /// Shorthand for `FAN_REPORT_DIR_FID | FAN_REPORT_NAME`
+        FAN_REPORT_DFID_NAME;
+    }
+}
+
+libc_bitflags! {
+    /// Flags for [`Fanotify::mark()`]
+    ///
+    /// Without `FAN_MARK_MOUNT` or `FAN_MARK_FILESYSTEM`, the mark applies to
+    /// the inode of the path.
+    pub struct MarkFlags: c_uint {
+        /// Add the events to the mark
+        FAN_MARK_ADD;
+        /// Remove the events from the mark
+        FAN_MARK_REMOVE;
+        /// Remove all marks of the given kind
+        FAN_MARK_FLUSH;
+        /// Don't dereference the path if it is a symbolic link
+        FAN_MARK_DONT_FOLLOW;
+        /// Fail if the path is not a directory
+        FAN_MARK_ONLYDIR;
+        /// Mark the mount containing the path
+        FAN_MARK_MOUNT;
+        /// Mark the filesystem containing the path
+        FAN_MARK_FILESYSTEM;
+        /// Add the events to the ignore mask
+        FAN_MARK_IGNORED_MASK;
+        /// The ignore mask survives modify events
+        FAN_MARK_IGNORED_SURV_MODIFY;
+        /// Inode mark that does not pin the inode in memory
+        FAN_MARK_EVICTABLE;
+        /// Add the events to the ignore mask, also for directories
+        FAN_MARK_IGNORE;
+    }
+}
+
+libc_bitflags! {
+    /// Events to mark and event types reported by [`Fanotify`]
+    pub struct MaskFlags: u64 {
+        /// File was accessed
+        FAN_ACCESS;
+        /// File was modified
+        FAN_MODIFY;
+        /// Metadata changed, requires `FAN_REPORT_FID`
+        FAN_ATTRIB;
+        /// File opened for writing was closed
+        FAN_CLOSE_WRITE;
+        /// File not opened for writing was closed
+        FAN_CLOSE_NOWRITE;
+        /// File was opened
+        FAN_OPEN;
+        /// File was opened for execution
+        FAN_OPEN_EXEC;
+        /// File was moved out of a marked directory, requires `FAN_REPORT_FID`
+        FAN_MOVED_FROM;
+        /// File was moved into a marked directory, requires `FAN_REPORT_FID`
+        FAN_MOVED_TO;
+        /// File was created in a marked directory, requires `FAN_REPORT_FID`
+        FAN_CREATE;
+        /// File was deleted from a marked directory, requires `FAN_REPORT_FID`
+        FAN_DELETE;
+        /// Marked file or directory was deleted, requires `FAN_REPORT_FID`
+        FAN_DELETE_SELF;
+        /// Marked file or directory was moved, requires `FAN_REPORT_FID`
+        FAN_MOVE_SELF;
+        /// File was renamed, requires `FAN_REPORT_DFID_NAME`
+        FAN_RENAME;
+        /// Event queue overflowed, events have been lost
+        FAN_Q_OVERFLOW;
+        /// Filesystem error
+        FAN_FS_ERROR;
+        /// Permission to open a file is requested
+        FAN_OPEN_PERM;
+        /// Permission to access a file is requested
+        FAN_ACCESS_PERM;
+        /// Permission to execute a file is requested
+        FAN_OPEN_EXEC_PERM;
+        /// Report events for the children of a marked directory
+        FAN_EVENT_ON_CHILD;
+        /// Report events for directories as well, the subject of the event
+        /// is a directory
+        FAN_ONDIR;
+        /// Any close event
+        FAN_CLOSE;
+        /// Any move event
+        FAN_MOVE;
+    }
+}
+
+/// Opaque handle of a file, see `name_to_handle_at(2)`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileHandle {
+    /// Filesystem specific handle type
+    pub handle_type: c_int,
+    /// Filesystem specific handle
+    pub bytes: Vec<u8>,
+}
+
+impl FileHandle {
+    /// Open the file referred to by this handle
+    ///
+    /// `mount` is any file on the filesystem the handle belongs to. This
+    /// requires the `CAP_DAC_READ_SEARCH` capability.
+    pub fn open<Fd: AsFd>(&self, mount: Fd, flags: OFlags) -> Result<FileDesc> {
+        // struct file_handle { u32 handle_bytes; int handle_type; u8 f_handle[]; }
+        let mut buf = Vec::with_capacity(8 + self.bytes.len());
+        buf.extend_from_slice(&(self.bytes.len() as u32).to_ne_bytes());
+        buf.extend_from_slice(&self.handle_type.to_ne_bytes());
+        buf.extend_from_slice(&self.bytes);
+
+        let fd = syscall!(syscall(
+            libc::SYS_open_by_handle_at,
+            mount.as_fd().as_raw_fd(),
+            buf.as_mut_ptr(),
+            flags.bits()
+        ))?;
+
+        Ok(unsafe { FileDesc::from_raw_fd(fd as RawFd) })
+    }
+}
+
+/// Kind of a [`FileId`] information record
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileIdKind {
+    /// `FAN_EVENT_INFO_TYPE_FID`, the object the event occurred for
+    Fid,
+    /// `FAN_EVENT_INFO_TYPE_DFID`, the parent directory
+    Dfid,
+    /// `FAN_EVENT_INFO_TYPE_DFID_NAME`, the parent directory and entry name
+    DfidName,
+    /// `FAN_EVENT_INFO_TYPE_OLD_DFID_NAME`, the source of a rename
+    OldDfidName,
+    /// `FAN_EVENT_INFO_TYPE_NEW_DFID_NAME`, the target of a rename
+    NewDfidName,
+}
+
+/// File identifier reported with `FAN_REPORT_FID` and related flags
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileId {
+    /// Kind of the record
+    pub kind: FileIdKind,
+    /// ID of the filesystem the object belongs to
+    pub fsid: [c_int; 2],
+    /// Handle of the object
+    pub handle: FileHandle,
+    /// Directory entry name, for the `*DfidName` kinds only
+    pub name: Option<OsString>,
+}
+
+/// Event read from a [`Fanotify`] instance
+#[derive(Debug)]
+pub struct FanotifyEvent {
+    /// Event types and flags
+    pub mask: MaskFlags,
+    /// Process or thread ID of the process causing the event
+    pub pid: libc::pid_t,
+    /// Open file descriptor of the object the event occurred for, `None` if
+    /// the instance reports file handles instead
+    pub fd: Option<FileDesc>,
+    /// File identifiers, if the instance reports file handles
+    pub fids: Vec<FileId>,
+}
+
+/// Fanotify instance for monitoring filesystem events of whole mounts or
+/// filesystems
+///
+/// Creating an instance requires the `CAP_SYS_ADMIN` capability, unless only
+/// inode marks and `FAN_REPORT_FID` are used.
+#[derive(Debug)]
+pub struct Fanotify(FileDesc);
+
+impl Fanotify {
+    /// Return a new [`Fanotify`] instance
+    ///
+    /// `event_flags` are the file status flags of the file descriptors
+    /// created for events, e.g. [`OFlags::O_CLOEXEC`].
+    pub fn new(flags: FanInitFlags, event_flags: OFlags) -> Result<Fanotify> {
+        let fd = syscall!(fanotify_init(flags.bits(), event_flags.bits() as c_uint))?;
+
+        Ok(Fanotify(unsafe { FileDesc::from_raw_fd(fd) }))
+    }
+
+    /// Add, remove or modify the mark of `path`
+    pub fn mark<P: AsRef<Path>>(&self, flags: MarkFlags, mask: MaskFlags, path: P) -> Result<()> {
+        let path = CString::new(path.as_ref().as_os_str().as_bytes())?;
+
+        syscall!(fanotify_mark(
+            self.0.as_raw_fd(),
+            flags.bits(),
+            mask.bits(),
+            libc::AT_FDCWD,
+            path.as_ptr()
+        ))
+        .map(|_| ())
+    }
+
+    /// Read pending events, up to one buffer's worth
+    ///
+    /// For a [`Fanotify`] instance created using [`FanInitFlags::FAN_NONBLOCK`],
+    /// this returns an empty vector if no event is pending. Otherwise, this
+    /// function will **block** until at least one event could be read.
+    pub fn read_events(&self) -> Result<Vec<FanotifyEvent>> {
+        let mut buf = [0u8; EVENT_BUF_LEN];
+
+        let len = match self.0.read(&mut buf) {
+            Ok(len) => len,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        parse_events(&buf[..len])
+    }
+}
+
+impl AsRawFd for Fanotify {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl AsFd for Fanotify {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+impl event::Source for Fanotify {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> std::io::Result<()> {
+        SourceFd(&self.0.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> std::io::Result<()> {
+        SourceFd(&self.0.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> std::io::Result<()> {
+        SourceFd(&self.0.as_raw_fd()).deregister(registry)
+    }
+}
+
+/// Parse a buffer of `struct fanotify_event_metadata`s
+///
+/// Each event is followed by optional information records up to its
+/// `event_len`. The file descriptors of the events are taken ownership of.
+fn parse_events(buf: &[u8]) -> Result<Vec<FanotifyEvent>> {
+    const META_LEN: usize = mem::size_of::<libc::fanotify_event_metadata>();
+
+    let meta = |offset: usize| {
+        // safety: bounds checked by the callers, the buffer is not
+        // necessarily aligned
+        unsafe {
+            ptr::read_unaligned(buf[offset..].as_ptr() as *const libc::fanotify_event_metadata)
+        }
+    };
+
+    // take ownership of all fds first, so none of them leaks on errors
+    let mut fds = Vec::new();
+    let mut offset = 0;
+
+    while offset + META_LEN <= buf.len() {
+        let meta = meta(offset);
+
+        fds.push((meta.fd >= 0).then(|| unsafe { FileDesc::from_raw_fd(meta.fd) }));
+
+        if (meta.event_len as usize) < META_LEN {
+            break;
+        }
+
+        offset += meta.event_len as usize;
+    }
+
+    let mut fds = fds.into_iter();
+    let mut events = Vec::new();
+    let mut offset = 0;
+
+    while offset + META_LEN <= buf.len() {
+        let meta = meta(offset);
+        let fd = fds.next().flatten();
+
+        if meta.vers != libc::FANOTIFY_METADATA_VERSION {
+            return Err(invalid("unsupported fanotify metadata version"));
+        }
+
+        if (meta.event_len as usize) < META_LEN {
+            return Err(invalid("invalid fanotify event length"));
+        }
+
+        let info = buf
+            .get(offset + meta.metadata_len as usize..offset + meta.event_len as usize)
+            .ok_or_else(|| invalid("truncated fanotify event"))?;
+
+        events.push(FanotifyEvent {
+            mask: MaskFlags::from_bits_truncate(meta.mask),
+            pid: meta.pid,
+            fd,
+            fids: parse_info(info)?,
+        });
+
+        offset += meta.event_len as usize;
+    }
+
+    Ok(events)
+}
+
+/// Parse the information records of an event
+fn parse_info(mut buf: &[u8]) -> Result<Vec<FileId>> {
+    const HDR_LEN: usize = mem::size_of::<libc::fanotify_event_info_header>();
+
+    let mut fids = Vec::new();
+
+    while buf.len() >= HDR_LEN {
+        let hdr =
+            unsafe { ptr::read_unaligned(buf.as_ptr() as *const libc::fanotify_event_info_header) };
+
+        let len = hdr.len as usize;
+        let record = buf
+            .get(HDR_LEN..len)
+            .ok_or_else(|| invalid("truncated fanotify info record"))?;
+
+        let kind = match hdr.info_type {
+            libc::FAN_EVENT_INFO_TYPE_FID => Some(FileIdKind::Fid),
+            libc::FAN_EVENT_INFO_TYPE_DFID => Some(FileIdKind::Dfid),
+            libc::FAN_EVENT_INFO_TYPE_DFID_NAME => Some(FileIdKind::DfidName),
+            libc::FAN_EVENT_INFO_TYPE_OLD_DFID_NAME => Some(FileIdKind::OldDfidName),
+            libc::FAN_EVENT_INFO_TYPE_NEW_DFID_NAME => Some(FileIdKind::NewDfidName),
+            // e.g. pidfd or error records
+            _ => None,
+        };
+
+        if let Some(kind) = kind {
+            fids.push(parse_fid(kind, record)?);
+        }
+
+        buf = &buf[len..];
+    }
+
+    Ok(fids)
+}
+
+/// Parse a file identifier record, i.e. the fsid followed by a
+/// `struct file_handle` and an optional nul terminated name
+fn parse_fid(kind: FileIdKind, record: &[u8]) -> Result<FileId> {
+    let u32_at = |offset: usize| -> Result<[u8; 4]> {
+        record
+            .get(offset..offset + 4)
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| invalid("truncated fanotify file handle"))
+    };
+
+    let fsid = [
+        c_int::from_ne_bytes(u32_at(0)?),
+        c_int::from_ne_bytes(u32_at(4)?),
+    ];
+    let handle_bytes = u32::from_ne_bytes(u32_at(8)?) as usize;
+    let handle_type = c_int::from_ne_bytes(u32_at(12)?);
+
+    let bytes = record
+        .get(16..16 + handle_bytes)
+        .ok_or_else(|| invalid("truncated fanotify file handle"))?
+        .to_vec();
+
+    let name = match kind {
+        FileIdKind::DfidName | FileIdKind::OldDfidName | FileIdKind::NewDfidName => {
+            let name = &record[16 + handle_bytes..];
+            let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+            Some(OsString::from_vec(name[..len].to_vec()))
+        }
+        _ => None,
+    };
+
+    Ok(FileId {
+        kind,
+        fsid,
+        handle: FileHandle { handle_type, bytes },
+        name,
+    })
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::Syscall(std::io::Error::new(ErrorKind::InvalidData, msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        os::unix::prelude::{AsRawFd, IntoRawFd},
+        path::PathBuf,
+        time::Duration,
+    };
+
+    use anyhow::Result;
+    use mio::{Events, Interest, Poll, Token};
+
+    use super::parse_events;
+    use crate::{
+        Error, FanInitFlags, Fanotify, FileIdKind, MarkFlags, MaskFlags, OFlags, WaitStatus, pipe2,
+        wait,
+    };
+
+    fn fd_path(fd: &impl AsRawFd) -> Result<PathBuf> {
+        Ok(fs::read_link(format!("/proc/self/fd/{}", fd.as_raw_fd()))?)
+    }
+
+    #[test]
+    fn fanotify_parse() -> Result<()> {
+        let mut buf = Vec::new();
+
+        // fanotify_event_metadata with a single DFID_NAME record
+        buf.extend_from_slice(&52u32.to_ne_bytes()); // event_len
+        buf.push(libc::FANOTIFY_METADATA_VERSION);
+        buf.push(0);
+        buf.extend_from_slice(&24u16.to_ne_bytes()); // metadata_len
+        buf.extend_from_slice(&(libc::FAN_CREATE | libc::FAN_ONDIR).to_ne_bytes());
+        buf.extend_from_slice(&libc::FAN_NOFD.to_ne_bytes());
+        buf.extend_from_slice(&42i32.to_ne_bytes()); // pid
+
+        buf.push(libc::FAN_EVENT_INFO_TYPE_DFID_NAME);
+        buf.push(0);
+        buf.extend_from_slice(&28u16.to_ne_bytes()); // len
+        buf.extend_from_slice(&7i32.to_ne_bytes()); // fsid
+        buf.extend_from_slice(&8i32.to_ne_bytes());
+        buf.extend_from_slice(&4u32.to_ne_bytes()); // handle_bytes
+        buf.extend_from_slice(&1i32.to_ne_bytes()); // handle_type
+        buf.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        buf.extend_from_slice(b"dir\0");
+
+        let events = parse_events(&buf)?;
+        assert_eq!(events.len(), 1);
+
+        let event = &events[0];
+        assert_eq!(event.mask, MaskFlags::FAN_CREATE | MaskFlags::FAN_ONDIR);
+        assert_eq!(event.pid, 42);
+        assert!(event.fd.is_none());
+
+        assert_eq!(event.fids.len(), 1);
+        let fid = &event.fids[0];
+        assert_eq!(fid.kind, FileIdKind::DfidName);
+        assert_eq!(fid.fsid, [7, 8]);
+        assert_eq!(fid.handle.handle_type, 1);
+        assert_eq!(fid.handle.bytes, vec![0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(fid.name, Some("dir".into()));
+
+        // a record exceeding the event is rejected
+        buf[24 + 2] = 64;
+        assert!(parse_events(&buf).is_err());
+
+        // the fds of the following events are closed nevertheless
+        let (rx, tx) = pipe2(OFlags::O_CLOEXEC)?;
+        let event = buf[..24].to_vec();
+        buf.extend_from_slice(&event);
+        buf[52..56].copy_from_slice(&24u32.to_ne_bytes());
+        buf[52 + 16..52 + 20].copy_from_slice(&tx.into_raw_fd().to_ne_bytes());
+        assert!(parse_events(&buf).is_err());
+
+        // EOF once all write ends are closed
+        assert_eq!(rx.read(&mut [0u8; 1])?, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn fanotify_mount() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("file");
+
+        // mount marks require CAP_SYS_ADMIN
+        let mut fan = match Fanotify::new(
+            FanInitFlags::FAN_CLOEXEC | FanInitFlags::FAN_NONBLOCK,
+            OFlags::O_CLOEXEC,
+        ) {
+            Ok(fan) => fan,
+            Err(Error::Syscall(err)) if err.raw_os_error() == Some(libc::EPERM) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        fan.mark(
+            MarkFlags::FAN_MARK_ADD | MarkFlags::FAN_MARK_MOUNT,
+            MaskFlags::FAN_CLOSE_WRITE,
+            dir.path(),
+        )?;
+
+        let mut poll = Poll::new()?;
+        let mut events = Events::with_capacity(1);
+        poll.registry()
+            .register(&mut fan, Token(5), Interest::READABLE)?;
+
+        fs::write(&path, b"data")?;
+
+        poll.poll(&mut events, Some(Duration::from_secs(5)))?;
+        assert!(events.iter().next().unwrap().is_readable());
+
+        // other processes write to the same mount, so look for our file
+        let mut found = false;
+
+        for _ in 0..100 {
+            for event in fan.read_events()? {
+                let fd = event.fd.unwrap();
+
+                if fd_path(&fd)? == path {
+                    assert_eq!(event.mask, MaskFlags::FAN_CLOSE_WRITE);
+                    assert_eq!(event.pid, std::process::id() as libc::pid_t);
+                    found = true;
+                }
+            }
+
+            if found {
+                break;
+            }
+
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        assert!(found);
+
+        Ok(())
+    }
+
+    #[test]
+    fn fanotify_fid() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        let fan = Fanotify::new(
+            FanInitFlags::FAN_CLOEXEC
+                | FanInitFlags::FAN_NONBLOCK
+                | FanInitFlags::FAN_REPORT_DFID_NAME,
+            OFlags::empty(),
+        )?;
+
+        // filesystem marks require CAP_SYS_ADMIN
+        match fan.mark(
+            MarkFlags::FAN_MARK_ADD | MarkFlags::FAN_MARK_FILESYSTEM,
+            MaskFlags::FAN_CREATE | MaskFlags::FAN_ONDIR,
+            dir.path(),
+        ) {
+            Ok(()) => {}
+            Err(Error::Syscall(err)) if err.raw_os_error() == Some(libc::EPERM) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        }
+
+        // create the directory from a child, so the event has a distinct pid
+        let child = match syscall!(fork())? {
+            // parent
+            pid if pid != 0 => pid,
+            // child
+            _ => {
+                let _ = fs::create_dir(dir.path().join("created"));
+                std::process::exit(0);
+            }
+        };
+
+        assert_eq!(wait(child)?, WaitStatus::Exited(child, 0));
+
+        let mut found = false;
+
+        for _ in 0..100 {
+            for event in fan.read_events()? {
+                assert!(event.fd.is_none());
+
+                let fid = &event.fids[0];
+                if event.pid != child || fid.name.as_deref() != Some("created".as_ref()) {
+                    continue;
+                }
+
+                assert_eq!(event.mask, MaskFlags::FAN_CREATE | MaskFlags::FAN_ONDIR);
+                assert_eq!(fid.kind, FileIdKind::DfidName);
+
+                // the handle refers to the parent directory
+                let mount = fs::File::open(dir.path())?;
+                let parent = fid.handle.open(&mount, OFlags::O_CLOEXEC)?;
+                assert_eq!(fd_path(&parent)?, dir.path());
+
+                found = true;
+            }
+
+            if found {
+                break;
+            }
+
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        assert!(found);
+
+        Ok(())
+    }
+}
@@ -4,11 +4,16 @@
 
 use std::{
     cmp, fmt,
-    io::{self, IoSlice, IoSliceMut},
+    io::{self, IoSlice, IoSliceMut, Read, Write},
     mem::forget,
-    os::unix::prelude::{AsRawFd, FromRawFd, IntoRawFd, RawFd},
+    os::unix::prelude::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd},
 };
 
+use libc::c_int;
+use mio::{Interest, Registry, Token, event, unix::SourceFd};
+
+use crate::libc_bitflags;
+
 /// IO system call wrapper.
 ///
 /// Wrapper around `libc` system calls that checks `errno` on failure.
@@ -38,6 +43,51 @@ const READ_LIMIT: usize = libc::ssize_t::MAX as usize;
 /// Limit for the number of buffers in the `iov` buffer array
 const IOV_LIMIT: usize = libc::UIO_MAXIOV as usize;
 
+libc_bitflags! {
+    /// File access mode and file status flags
+    pub struct OFlags: c_int {
+        /// Open for writing only
+        O_WRONLY;
+        /// Open for reading and writing
+        O_RDWR;
+        /// Append on each write
+        O_APPEND;
+        /// Signal-driven I/O
+        O_ASYNC;
+        /// Close the file descriptor on `exec`
+        O_CLOEXEC;
+        /// Bypass the page cache
+        O_DIRECT;
+        /// Synchronized I/O data integrity completion
+        O_DSYNC;
+        /// Don't update the file last access time
+        O_NOATIME;
+        /// Nonblocking I/O
+        O_NONBLOCK;
+        /// Synchronized I/O file integrity completion
+        O_SYNC;
+    }
+}
+
+libc_bitflags! {
+    /// Per-call flags for [`FileDesc::preadv2()`]
+    pub struct RwfFlags: c_int {
+        /// High priority request, poll if possible
+        RWF_HIPRI;
+        /// Per-IO `O_DSYNC`
+        RWF_DSYNC;
+        /// Per-IO `O_SYNC`
+        RWF_SYNC;
+        /// Fail with `EAGAIN` instead of blocking on data not in the page cache
+        RWF_NOWAIT;
+        /// Per-IO `O_APPEND`
+        RWF_APPEND;
+    }
+}
+
+/// Owned file descriptor
+///
+/// The file descriptor is closed when the [`FileDesc`] is dropped.
 pub struct FileDesc(RawFd);
 
 impl FileDesc {
@@ -71,7 +121,7 @@ impl FileDesc {
         Ok(res as usize)
     }
 
-    pub fn write_vectored(&self, bufs: &mut [IoSlice]) -> io::Result<usize> {
+    pub fn write_vectored(&self, bufs: &[IoSlice]) -> io::Result<usize> {
         let res = iocall!(writev(
             self.as_raw_fd(),
             bufs.as_ptr() as *const libc::iovec,
@@ -80,6 +130,195 @@ impl FileDesc {
 
         Ok(res as usize)
     }
+
+    /// Read from `offset` without changing the file offset
+    pub fn pread(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        let res = iocall!(pread64(
+            self.as_raw_fd(),
+            buf.as_mut_ptr() as *mut libc::c_void,
+            cmp::min(buf.len(), READ_LIMIT),
+            offset as libc::off64_t
+        ))?;
+
+        Ok(res as usize)
+    }
+
+    /// Write at `offset` without changing the file offset
+    pub fn pwrite(&self, buf: &[u8], offset: u64) -> io::Result<usize> {
+        let res = iocall!(pwrite64(
+            self.as_raw_fd(),
+            buf.as_ptr() as *const libc::c_void,
+            cmp::min(buf.len(), READ_LIMIT),
+            offset as libc::off64_t
+        ))?;
+
+        Ok(res as usize)
+    }
+
+    /// Read from `offset` into `bufs`, with per-call `flags`
+    ///
+    /// If `offset` is `None`, read from the current file offset and update it,
+    /// like [`FileDesc::read_vectored()`] does.
+    pub fn preadv2(
+        &self,
+        bufs: &mut [IoSliceMut],
+        offset: Option<u64>,
+        flags: RwfFlags,
+    ) -> io::Result<usize> {
+        let res = iocall!(preadv2(
+            self.as_raw_fd(),
+            bufs.as_ptr() as *const libc::iovec,
+            cmp::min(bufs.len() as libc::c_int, IOV_LIMIT as libc::c_int),
+            offset.map_or(-1, |offset| offset as libc::off_t),
+            flags.bits()
+        ))?;
+
+        Ok(res as usize)
+    }
+
+    /// Return a new [`FileDesc`] referring to the same open file description
+    ///
+    /// The new file descriptor has the close-on-exec flag set.
+    pub fn try_clone(&self) -> io::Result<FileDesc> {
+        let fd = iocall!(fcntl(self.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 3))?;
+
+        Ok(FileDesc(fd))
+    }
+
+    /// Return the file access mode and file status flags
+    pub fn flags(&self) -> io::Result<OFlags> {
+        let flags = iocall!(fcntl(self.as_raw_fd(), libc::F_GETFL))?;
+
+        Ok(OFlags::from_bits_truncate(flags))
+    }
+
+    /// Set the file status flags
+    ///
+    /// Note that only `O_APPEND`, `O_ASYNC`, `O_DIRECT`, `O_NOATIME` and
+    /// `O_NONBLOCK` can be changed, all other flags are ignored.
+    pub fn set_flags(&self, flags: OFlags) -> io::Result<()> {
+        iocall!(fcntl(self.as_raw_fd(), libc::F_SETFL, flags.bits())).map(|_| ())
+    }
+
+    /// Enable or disable nonblocking I/O
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        let mut flags = self.flags()?;
+        flags.set(OFlags::O_NONBLOCK, nonblocking);
+
+        self.set_flags(flags)
+    }
+
+    /// Return `true` if the file descriptor is closed on `exec`
+    pub fn cloexec(&self) -> io::Result<bool> {
+        let flags = iocall!(fcntl(self.as_raw_fd(), libc::F_GETFD))?;
+
+        Ok(flags & libc::FD_CLOEXEC != 0)
+    }
+
+    /// Enable or disable closing the file descriptor on `exec`
+    pub fn set_cloexec(&self, cloexec: bool) -> io::Result<()> {
+        let flags = iocall!(fcntl(self.as_raw_fd(), libc::F_GETFD))?;
+
+        let flags = if cloexec {
+            flags | libc::FD_CLOEXEC
+        } else {
+            flags & !libc::FD_CLOEXEC
+        };
+
+        iocall!(fcntl(self.as_raw_fd(), libc::F_SETFD, flags)).map(|_| ())
+    }
+}
+
+impl Read for FileDesc {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        FileDesc::read(self, buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> io::Result<usize> {
+        FileDesc::read_vectored(self, bufs)
+    }
+}
+
+impl Read for &FileDesc {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        FileDesc::read(self, buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> io::Result<usize> {
+        FileDesc::read_vectored(self, bufs)
+    }
+}
+
+impl Write for FileDesc {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        FileDesc::write(self, buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
+        FileDesc::write_vectored(self, bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Write for &FileDesc {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        FileDesc::write(self, buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
+        FileDesc::write_vectored(self, bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsFd for FileDesc {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // safety: the fd is owned by `self` and lives as long as the borrow
+        unsafe { BorrowedFd::borrow_raw(self.0) }
+    }
+}
+
+impl From<OwnedFd> for FileDesc {
+    fn from(fd: OwnedFd) -> Self {
+        FileDesc(fd.into_raw_fd())
+    }
+}
+
+impl From<FileDesc> for OwnedFd {
+    fn from(fd: FileDesc) -> Self {
+        // safety: ownership of the fd is transferred
+        unsafe { OwnedFd::from_raw_fd(fd.into_raw_fd()) }
+    }
+}
+
+impl event::Source for FileDesc {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> std::io::Result<()> {
+        SourceFd(&self.0).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> std::io::Result<()> {
+        SourceFd(&self.0).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> std::io::Result<()> {
+        SourceFd(&self.0).deregister(registry)
+    }
 }
 
 impl AsRawFd for FileDesc {
@@ -131,3 +370,130 @@ impl fmt::Debug for FileDesc {
         f.debug_tuple("FileDesc").field(&self.0).finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{IoSliceMut, Read, Seek, SeekFrom, Write},
+        os::unix::prelude::{AsRawFd, OwnedFd},
+        time::Duration,
+    };
+
+    use anyhow::Result;
+    use mio::{Events, Interest, Poll, Token};
+
+    use super::{OFlags, RwfFlags};
+    use crate::{FileDesc, pipe2};
+
+    fn tempfile() -> Result<FileDesc> {
+        Ok(OwnedFd::from(tempfile::tempfile()?).into())
+    }
+
+    #[test]
+    fn fd_read_write() -> Result<()> {
+        let mut fd = tempfile()?;
+
+        fd.write_all(b"hello, world")?;
+
+        let mut file = std::fs::File::from(OwnedFd::from(fd.try_clone()?));
+        file.seek(SeekFrom::Start(0))?;
+
+        // clones share the file offset
+        let mut buf = String::new();
+        (&fd).read_to_string(&mut buf)?;
+        assert_eq!(buf, "hello, world");
+
+        Ok(())
+    }
+
+    #[test]
+    fn fd_positional() -> Result<()> {
+        let fd = tempfile()?;
+
+        assert_eq!(fd.pwrite(b"0123456789", 0)?, 10);
+        assert_eq!(fd.pwrite(b"ab", 4)?, 2);
+
+        let mut buf = [0u8; 4];
+        assert_eq!(fd.pread(&mut buf, 2)?, 4);
+        assert_eq!(&buf, b"23ab");
+
+        let mut head = [0u8; 3];
+        let mut tail = [0u8; 3];
+        let n = fd.preadv2(
+            &mut [IoSliceMut::new(&mut head), IoSliceMut::new(&mut tail)],
+            Some(4),
+            RwfFlags::empty(),
+        )?;
+        assert_eq!(n, 6);
+        assert_eq!(&head, b"ab6");
+        assert_eq!(&tail, b"789");
+
+        // the file offset is left untouched
+        assert_eq!(fd.read(&mut buf)?, 4);
+        assert_eq!(&buf, b"0123");
+
+        Ok(())
+    }
+
+    #[test]
+    fn fd_flags() -> Result<()> {
+        let (rx, tx) = pipe2(OFlags::O_CLOEXEC)?;
+
+        assert!(rx.cloexec()?);
+        rx.set_cloexec(false)?;
+        assert!(!rx.cloexec()?);
+
+        assert!(!tx.flags()?.contains(OFlags::O_NONBLOCK));
+        assert!(tx.flags()?.contains(OFlags::O_WRONLY));
+
+        rx.set_nonblocking(true)?;
+        assert!(rx.flags()?.contains(OFlags::O_NONBLOCK));
+
+        let mut buf = [0u8; 1];
+        assert_eq!(
+            rx.read(&mut buf).err().unwrap().kind(),
+            std::io::ErrorKind::WouldBlock
+        );
+
+        let clone = rx.try_clone()?;
+        assert_ne!(clone.as_raw_fd(), rx.as_raw_fd());
+        assert!(clone.cloexec()?);
+        // status flags are shared with the open file description
+        assert!(clone.flags()?.contains(OFlags::O_NONBLOCK));
+
+        Ok(())
+    }
+
+    #[test]
+    fn fd_poll() -> Result<()> {
+        const PIPE: Token = Token(3);
+
+        let (mut rx, mut tx) = pipe2(OFlags::O_CLOEXEC)?;
+        rx.set_nonblocking(true)?;
+
+        let mut poll = Poll::new()?;
+        let mut events = Events::with_capacity(1);
+
+        poll.registry()
+            .register(&mut rx, PIPE, Interest::READABLE)?;
+
+        poll.poll(&mut events, Some(Duration::from_millis(10)))?;
+        assert!(events.is_empty());
+
+        tx.write_all(b"ping")?;
+
+        poll.poll(&mut events, Some(Duration::from_secs(5)))?;
+
+        let evt = events.iter().next().unwrap();
+        assert_eq!(evt.token(), PIPE);
+        assert!(evt.is_readable());
+
+        let mut buf = [0u8; 4];
+        rx.read_exact(&mut buf)?;
+        assert_eq!(&buf, b"ping");
+
+        poll.registry().deregister(&mut rx)?;
+
+        Ok(())
+    }
+}
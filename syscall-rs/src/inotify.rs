@@ -0,0 +1,389 @@
+//!
+//! This file is part of syscall-rs
+//!
+
+use std::{
+    ffi::{CString, OsString},
+    io::ErrorKind,
+    mem,
+    os::unix::{
+        ffi::{OsStrExt, OsStringExt},
+        prelude::{AsFd, AsRawFd, BorrowedFd, FromRawFd, RawFd},
+    },
+    path::Path,
+    ptr,
+};
+
+use libc::c_int;
+use mio::{Interest, Registry, Token, event, unix::SourceFd};
+
+use crate::{FileDesc, Result, libc_bitflags};
+
+/// Size of the buffer for reading events, large enough for at least one
+/// event with a name of `NAME_MAX` bytes
+const EVENT_BUF_LEN: usize = 4096;
+
+libc_bitflags! {
+    /// Flags for creating an [`Inotify`] instance
+    pub struct InotifyFlags: c_int {
+        /// Close the inotify fd on `exec`
+        IN_CLOEXEC;
+        /// Nonblocking reads
+        IN_NONBLOCK;
+    }
+}
+
+libc_bitflags! {
+    /// Events to watch for and event types reported by [`Inotify`]
+    pub struct WatchMask: u32 {
+        /// File was accessed
+        IN_ACCESS;
+        /// File was modified
+        IN_MODIFY;
+        /// Metadata changed
+        IN_ATTRIB;
+        /// File opened for writing was closed
+        IN_CLOSE_WRITE;
+        /// File not opened for writing was closed
+        IN_CLOSE_NOWRITE;
+        /// File was opened
+        IN_OPEN;
+        /// File was moved out of the watched directory
+        IN_MOVED_FROM;
+        /// File was moved into the watched directory
+        IN_MOVED_TO;
+        /// File was created in the watched directory
+        IN_CREATE;
+        /// File was deleted from the watched directory
+        IN_DELETE;
+        /// Watched file or directory was deleted
+        IN_DELETE_SELF;
+        /// Watched file or directory was moved
+        IN_MOVE_SELF;
+        /// Filesystem containing the watched object was unmounted
+        IN_UNMOUNT;
+        /// Event queue overflowed, events have been lost
+        IN_Q_OVERFLOW;
+        /// Watch was removed
+        IN_IGNORED;
+        /// Only watch the path if it is a directory
+        IN_ONLYDIR;
+        /// Don't dereference the path if it is a symbolic link
+        IN_DONT_FOLLOW;
+        /// Don't report events for children after they have been unlinked
+        IN_EXCL_UNLINK;
+        /// Fail if the path is already watched
+        IN_MASK_CREATE;
+        /// Add to the mask of an existing watch instead of replacing it
+        IN_MASK_ADD;
+        /// Subject of the event is a directory
+        IN_ISDIR;
+        /// Remove the watch after the first event
+        IN_ONESHOT;
+        /// Any close event
+        IN_CLOSE;
+        /// Any move event
+        IN_MOVE;
+        /// All events
+        IN_ALL_EVENTS;
+    }
+}
+
+/// Watch descriptor returned by [`Inotify::add_watch()`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WatchDescriptor(c_int);
+
+/// Event read from an [`Inotify`] instance
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InotifyEvent {
+    /// Watch the event occurred for, `-1` for [`WatchMask::IN_Q_OVERFLOW`]
+    pub wd: WatchDescriptor,
+    /// Event type and flags
+    pub mask: WatchMask,
+    /// Cookie connecting the `IN_MOVED_FROM` and `IN_MOVED_TO` events of a
+    /// rename, `0` otherwise
+    pub cookie: u32,
+    /// Name of the file within a watched directory the event occurred for
+    pub name: Option<OsString>,
+}
+
+impl InotifyEvent {
+    /// Return `true` if events have been lost due to a queue overflow
+    pub fn is_overflow(&self) -> bool {
+        self.mask.contains(WatchMask::IN_Q_OVERFLOW)
+    }
+}
+
+/// Inotify instance for monitoring filesystem events
+#[derive(Debug)]
+pub struct Inotify(FileDesc);
+
+impl Inotify {
+    /// Return a new [`Inotify`] instance
+    pub fn new(flags: InotifyFlags) -> Result<Inotify> {
+        let fd = syscall!(inotify_init1(flags.bits()))?;
+
+        Ok(Inotify(unsafe { FileDesc::from_raw_fd(fd) }))
+    }
+
+    /// Watch `path` for the events in `mask`
+    ///
+    /// If `path` is already watched, the existing watch descriptor is
+    /// returned and its mask is replaced, unless [`WatchMask::IN_MASK_ADD`]
+    /// is given.
+    pub fn add_watch<P: AsRef<Path>>(&self, path: P, mask: WatchMask) -> Result<WatchDescriptor> {
+        let path = CString::new(path.as_ref().as_os_str().as_bytes())?;
+
+        let wd = syscall!(inotify_add_watch(
+            self.0.as_raw_fd(),
+            path.as_ptr(),
+            mask.bits()
+        ))?;
+
+        Ok(WatchDescriptor(wd))
+    }
+
+    /// Remove the watch `wd`
+    ///
+    /// This generates an [`WatchMask::IN_IGNORED`] event for `wd`.
+    pub fn rm_watch(&self, wd: WatchDescriptor) -> Result<()> {
+        syscall!(inotify_rm_watch(self.0.as_raw_fd(), wd.0)).map(|_| ())
+    }
+
+    /// Read pending events, up to one buffer's worth
+    ///
+    /// For an [`Inotify`] instance created using [`InotifyFlags::IN_NONBLOCK`],
+    /// this returns an empty vector if no event is pending. Otherwise, this
+    /// function will **block** until at least one event could be read.
+    pub fn read_events(&self) -> Result<Vec<InotifyEvent>> {
+        let mut buf = [0u8; EVENT_BUF_LEN];
+
+        let len = match self.0.read(&mut buf) {
+            Ok(len) => len,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(parse_events(&buf[..len]))
+    }
+}
+
+impl AsRawFd for Inotify {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl AsFd for Inotify {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+impl event::Source for Inotify {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> std::io::Result<()> {
+        SourceFd(&self.0.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> std::io::Result<()> {
+        SourceFd(&self.0.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> std::io::Result<()> {
+        SourceFd(&self.0.as_raw_fd()).deregister(registry)
+    }
+}
+
+/// Parse a buffer of variable length `struct inotify_event`s
+///
+/// Each event is followed by `len` bytes holding the nul terminated and
+/// padded name, if any.
+fn parse_events(buf: &[u8]) -> Vec<InotifyEvent> {
+    const HDR_LEN: usize = mem::size_of::<libc::inotify_event>();
+
+    let mut events = Vec::new();
+    let mut offset = 0;
+
+    while offset + HDR_LEN <= buf.len() {
+        // safety: bounds checked above, the buffer is not necessarily aligned
+        let event =
+            unsafe { ptr::read_unaligned(buf[offset..].as_ptr() as *const libc::inotify_event) };
+
+        let start = offset + HDR_LEN;
+        let end = start + event.len as usize;
+
+        let Some(name) = buf.get(start..end) else {
+            break;
+        };
+
+        let name = match name.iter().position(|&b| b == 0).unwrap_or(name.len()) {
+            0 => None,
+            len => Some(OsString::from_vec(name[..len].to_vec())),
+        };
+
+        events.push(InotifyEvent {
+            wd: WatchDescriptor(event.wd),
+            mask: WatchMask::from_bits_truncate(event.mask),
+            cookie: event.cookie,
+            name,
+        });
+
+        offset = end;
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsString, fs, time::Duration};
+
+    use anyhow::Result;
+    use mio::{Events, Interest, Poll, Token};
+
+    use super::{WatchDescriptor, parse_events};
+    use crate::{Inotify, InotifyEvent, InotifyFlags, WatchMask};
+
+    fn event(wd: i32, mask: u32, cookie: u32, name: &[u8], len: u32) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&wd.to_ne_bytes());
+        buf.extend_from_slice(&mask.to_ne_bytes());
+        buf.extend_from_slice(&cookie.to_ne_bytes());
+        buf.extend_from_slice(&len.to_ne_bytes());
+        buf.extend_from_slice(name);
+        buf.resize(buf.len() + len as usize - name.len(), 0);
+        buf
+    }
+
+    #[test]
+    fn inotify_parse() {
+        let mut buf = event(1, libc::IN_CREATE, 0, b"config.toml", 16);
+        buf.extend(event(-1, libc::IN_Q_OVERFLOW, 0, b"", 0));
+        buf.extend(event(2, libc::IN_MOVED_FROM | libc::IN_ISDIR, 7, b"a", 16));
+        // truncated event is ignored
+        buf.extend(&event(3, libc::IN_DELETE, 0, b"b", 16)[..20]);
+
+        let events = parse_events(&buf);
+
+        assert_eq!(
+            events,
+            vec![
+                InotifyEvent {
+                    wd: WatchDescriptor(1),
+                    mask: WatchMask::IN_CREATE,
+                    cookie: 0,
+                    name: Some("config.toml".into()),
+                },
+                InotifyEvent {
+                    wd: WatchDescriptor(-1),
+                    mask: WatchMask::IN_Q_OVERFLOW,
+                    cookie: 0,
+                    name: None,
+                },
+                InotifyEvent {
+                    wd: WatchDescriptor(2),
+                    mask: WatchMask::IN_MOVED_FROM | WatchMask::IN_ISDIR,
+                    cookie: 7,
+                    name: Some("a".into()),
+                },
+            ]
+        );
+
+        assert!(events[1].is_overflow());
+    }
+
+    #[test]
+    fn inotify_watch() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        let inotify = Inotify::new(InotifyFlags::IN_CLOEXEC | InotifyFlags::IN_NONBLOCK)?;
+        let wd = inotify.add_watch(
+            dir.path(),
+            WatchMask::IN_CREATE | WatchMask::IN_MOVE | WatchMask::IN_DELETE,
+        )?;
+
+        assert!(inotify.read_events()?.is_empty());
+
+        fs::write(dir.path().join("old"), b"data")?;
+        fs::rename(dir.path().join("old"), dir.path().join("new"))?;
+        fs::create_dir(dir.path().join("sub"))?;
+        fs::remove_file(dir.path().join("new"))?;
+
+        let events = inotify.read_events()?;
+        assert!(events.iter().all(|e| e.wd == wd));
+
+        let kinds: Vec<_> = events
+            .iter()
+            .map(|e| (e.mask, e.name.clone().unwrap()))
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                (WatchMask::IN_CREATE, OsString::from("old")),
+                (WatchMask::IN_MOVED_FROM, OsString::from("old")),
+                (WatchMask::IN_MOVED_TO, OsString::from("new")),
+                (
+                    WatchMask::IN_CREATE | WatchMask::IN_ISDIR,
+                    OsString::from("sub")
+                ),
+                (WatchMask::IN_DELETE, OsString::from("new")),
+            ]
+        );
+
+        // both halves of the rename share a cookie
+        assert_ne!(events[1].cookie, 0);
+        assert_eq!(events[1].cookie, events[2].cookie);
+
+        inotify.rm_watch(wd)?;
+
+        let events = inotify.read_events()?;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].mask, WatchMask::IN_IGNORED);
+
+        Ok(())
+    }
+
+    #[test]
+    fn inotify_poll() -> Result<()> {
+        const WATCH: Token = Token(4);
+
+        let dir = tempfile::tempdir()?;
+
+        let mut inotify = Inotify::new(InotifyFlags::IN_NONBLOCK)?;
+        inotify.add_watch(dir.path(), WatchMask::IN_CLOSE_WRITE)?;
+
+        let mut poll = Poll::new()?;
+        let mut events = Events::with_capacity(1);
+
+        poll.registry()
+            .register(&mut inotify, WATCH, Interest::READABLE)?;
+
+        poll.poll(&mut events, Some(Duration::from_millis(10)))?;
+        assert!(events.is_empty());
+
+        fs::write(dir.path().join("file"), b"data")?;
+
+        poll.poll(&mut events, Some(Duration::from_secs(5)))?;
+
+        let evt = events.iter().next().unwrap();
+        assert_eq!(evt.token(), WATCH);
+        assert!(evt.is_readable());
+
+        let events = inotify.read_events()?;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].name, Some("file".into()));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1027 @@
+//!
+//! This file is part of syscall-rs
+//!
+
+use std::{
+    ffi::CStr,
+    io::{IoSlice, IoSliceMut},
+    mem,
+    num::NonZeroUsize,
+    os::unix::prelude::{AsFd, AsRawFd, BorrowedFd, FromRawFd, RawFd},
+    ptr,
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+};
+
+use bitflags::bitflags;
+use libc::{c_int, c_uint, c_void};
+use mio::{Interest, Registry, Token, event, unix::SourceFd};
+
+use crate::{Error, FileDesc, MapFlags, Mapping, ProtFlags, Result};
+
+// io_uring ABI, see `include/uapi/linux/io_uring.h`. libc does not provide it.
+
+const IORING_OFF_SQ_RING: libc::off_t = 0;
+const IORING_OFF_CQ_RING: libc::off_t = 0x8000000;
+const IORING_OFF_SQES: libc::off_t = 0x10000000;
+
+const IORING_SQ_NEED_WAKEUP: u32 = 1 << 0;
+
+const IORING_ENTER_GETEVENTS: c_uint = 1 << 0;
+const IORING_ENTER_SQ_WAKEUP: c_uint = 1 << 1;
+
+const IORING_REGISTER_BUFFERS: c_uint = 0;
+const IORING_UNREGISTER_BUFFERS: c_uint = 1;
+const IORING_REGISTER_FILES: c_uint = 2;
+const IORING_UNREGISTER_FILES: c_uint = 3;
+
+const IORING_OP_NOP: u8 = 0;
+const IORING_OP_READV: u8 = 1;
+const IORING_OP_WRITEV: u8 = 2;
+const IORING_OP_READ_FIXED: u8 = 4;
+const IORING_OP_WRITE_FIXED: u8 = 5;
+const IORING_OP_TIMEOUT: u8 = 11;
+const IORING_OP_ACCEPT: u8 = 13;
+const IORING_OP_CONNECT: u8 = 16;
+const IORING_OP_OPENAT: u8 = 18;
+const IORING_OP_CLOSE: u8 = 19;
+const IORING_OP_READ: u8 = 22;
+const IORING_OP_WRITE: u8 = 23;
+const IORING_OP_SEND: u8 = 26;
+const IORING_OP_RECV: u8 = 27;
+
+#[repr(C)]
+#[derive(Debug, Default)]
+struct SqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, Default)]
+struct CqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqringOffsets,
+    cq_off: CqringOffsets,
+}
+
+/// `struct io_uring_sqe`, with the unions flattened to their first member
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+struct RawSqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    op_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    file_index: u32,
+    addr3: u64,
+    pad: u64,
+}
+
+/// `struct io_uring_cqe`
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct RawCqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+bitflags! {
+    /// Flags for setting up an [`IoUring`]
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct UringFlags: u32 {
+        /// Busy-wait for I/O completion, requires `O_DIRECT` files
+        const IORING_SETUP_IOPOLL = 1 << 0;
+        /// Let a kernel thread poll the submission queue
+        const IORING_SETUP_SQPOLL = 1 << 1;
+        /// Clamp the number of entries instead of failing
+        const IORING_SETUP_CLAMP = 1 << 4;
+        /// Submit all entries, even if one of them fails
+        const IORING_SETUP_SUBMIT_ALL = 1 << 7;
+        /// Don't interrupt the task to run completion work
+        const IORING_SETUP_COOP_TASKRUN = 1 << 8;
+        /// Only a single task submits requests
+        const IORING_SETUP_SINGLE_ISSUER = 1 << 12;
+    }
+}
+
+bitflags! {
+    /// Features supported by the kernel, see [`IoUring::features()`]
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct UringFeatures: u32 {
+        /// Submission and completion rings share a single mapping
+        const IORING_FEAT_SINGLE_MMAP = 1 << 0;
+        /// Completions are never dropped on completion queue overflow
+        const IORING_FEAT_NODROP = 1 << 1;
+        /// Data of submitted entries need not remain valid after submission
+        const IORING_FEAT_SUBMIT_STABLE = 1 << 2;
+        /// An offset of `-1` means the current file position
+        const IORING_FEAT_RW_CUR_POS = 1 << 3;
+        /// Requests use the credentials of the submitting task
+        const IORING_FEAT_CUR_PERSONALITY = 1 << 4;
+        /// Internal polling is used for nonblocking files
+        const IORING_FEAT_FAST_POLL = 1 << 5;
+        /// `SQPOLL` works with non-registered files
+        const IORING_FEAT_SQPOLL_NONFIXED = 1 << 7;
+        /// Workers are native threads of the submitting task
+        const IORING_FEAT_NATIVE_WORKERS = 1 << 9;
+    }
+}
+
+bitflags! {
+    /// Flags of a submission queue entry
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct SqeFlags: u8 {
+        /// The fd is an index into the registered files
+        const IOSQE_FIXED_FILE = 1 << 0;
+        /// Start the request once all previous requests have completed
+        const IOSQE_IO_DRAIN = 1 << 1;
+        /// Start the next request once this request has completed
+        /// successfully
+        const IOSQE_IO_LINK = 1 << 2;
+        /// Like `IOSQE_IO_LINK`, but regardless of the result
+        const IOSQE_IO_HARDLINK = 1 << 3;
+        /// Always issue the request asynchronously
+        const IOSQE_ASYNC = 1 << 4;
+        /// Don't post a completion if the request succeeds
+        const IOSQE_CQE_SKIP_SUCCESS = 1 << 6;
+    }
+}
+
+bitflags! {
+    /// Flags for [`Sqe::timeout()`]
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct TimeoutFlags: u32 {
+        /// The timeout is an absolute time
+        const IORING_TIMEOUT_ABS = 1 << 0;
+        /// Use `CLOCK_BOOTTIME` instead of `CLOCK_MONOTONIC`
+        const IORING_TIMEOUT_BOOTTIME = 1 << 2;
+        /// Use `CLOCK_REALTIME` instead of `CLOCK_MONOTONIC`
+        const IORING_TIMEOUT_REALTIME = 1 << 3;
+        /// Complete an expired timeout with `0` instead of `-ETIME`
+        const IORING_TIMEOUT_ETIME_SUCCESS = 1 << 5;
+    }
+}
+
+/// Timeout value for [`Sqe::timeout()`], i.e. `struct __kernel_timespec`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timespec {
+    /// Seconds
+    pub sec: i64,
+    /// Nanoseconds
+    pub nsec: i64,
+}
+
+impl From<Duration> for Timespec {
+    fn from(d: Duration) -> Self {
+        Timespec {
+            sec: d.as_secs() as i64,
+            nsec: d.subsec_nanos() as i64,
+        }
+    }
+}
+
+/// Submission queue entry
+///
+/// An [`Sqe`] describes a single request. It refers to buffers and other
+/// arguments by raw pointer, which is why [`IoUring::push()`] is `unsafe`:
+/// everything an entry refers to must stay valid until the request has
+/// completed.
+///
+/// Offsets of `u64::MAX` mean the current file position, for files which
+/// are not seekable, e.g. pipes and sockets.
+#[derive(Clone, Copy, Debug)]
+pub struct Sqe(RawSqe);
+
+impl Sqe {
+    fn new(opcode: u8, fd: RawFd) -> Sqe {
+        Sqe(RawSqe {
+            opcode,
+            fd,
+            ..Default::default()
+        })
+    }
+
+    /// Request which does nothing
+    pub fn nop() -> Sqe {
+        Sqe::new(IORING_OP_NOP, -1)
+    }
+
+    /// Read from `fd` at `offset` into `buf`
+    pub fn read(fd: RawFd, buf: &mut [u8], offset: u64) -> Sqe {
+        let mut sqe = Sqe::new(IORING_OP_READ, fd);
+        sqe.0.addr = buf.as_mut_ptr() as u64;
+        sqe.0.len = buf.len() as u32;
+        sqe.0.off = offset;
+        sqe
+    }
+
+    /// Write `buf` to `fd` at `offset`
+    pub fn write(fd: RawFd, buf: &[u8], offset: u64) -> Sqe {
+        let mut sqe = Sqe::new(IORING_OP_WRITE, fd);
+        sqe.0.addr = buf.as_ptr() as u64;
+        sqe.0.len = buf.len() as u32;
+        sqe.0.off = offset;
+        sqe
+    }
+
+    /// Read from `fd` at `offset` into `bufs`
+    pub fn readv(fd: RawFd, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Sqe {
+        let mut sqe = Sqe::new(IORING_OP_READV, fd);
+        sqe.0.addr = bufs.as_mut_ptr() as u64;
+        sqe.0.len = bufs.len() as u32;
+        sqe.0.off = offset;
+        sqe
+    }
+
+    /// Write `bufs` to `fd` at `offset`
+    pub fn writev(fd: RawFd, bufs: &[IoSlice<'_>], offset: u64) -> Sqe {
+        let mut sqe = Sqe::new(IORING_OP_WRITEV, fd);
+        sqe.0.addr = bufs.as_ptr() as u64;
+        sqe.0.len = bufs.len() as u32;
+        sqe.0.off = offset;
+        sqe
+    }
+
+    /// Read from `fd` at `offset` into `buf`, which must be part of the
+    /// registered buffer at `index`
+    pub fn read_fixed(fd: RawFd, buf: &mut [u8], offset: u64, index: u16) -> Sqe {
+        let mut sqe = Sqe::read(fd, buf, offset);
+        sqe.0.opcode = IORING_OP_READ_FIXED;
+        sqe.0.buf_index = index;
+        sqe
+    }
+
+    /// Write `buf` to `fd` at `offset`, `buf` must be part of the
+    /// registered buffer at `index`
+    pub fn write_fixed(fd: RawFd, buf: &[u8], offset: u64, index: u16) -> Sqe {
+        let mut sqe = Sqe::write(fd, buf, offset);
+        sqe.0.opcode = IORING_OP_WRITE_FIXED;
+        sqe.0.buf_index = index;
+        sqe
+    }
+
+    /// Accept a connection on the listening socket `fd`
+    ///
+    /// `flags` are the `accept4(2)` flags, e.g. `SOCK_CLOEXEC`. The result
+    /// of the request is the fd of the accepted socket.
+    pub fn accept(fd: RawFd, flags: c_int) -> Sqe {
+        let mut sqe = Sqe::new(IORING_OP_ACCEPT, fd);
+        sqe.0.op_flags = flags as u32;
+        sqe
+    }
+
+    /// Connect the socket `fd` to `addr`
+    pub fn connect(fd: RawFd, addr: *const libc::sockaddr, len: libc::socklen_t) -> Sqe {
+        let mut sqe = Sqe::new(IORING_OP_CONNECT, fd);
+        sqe.0.addr = addr as u64;
+        sqe.0.off = len as u64;
+        sqe
+    }
+
+    /// Send `buf` on the socket `fd`, `flags` are the `send(2)` flags
+    pub fn send(fd: RawFd, buf: &[u8], flags: c_int) -> Sqe {
+        let mut sqe = Sqe::new(IORING_OP_SEND, fd);
+        sqe.0.addr = buf.as_ptr() as u64;
+        sqe.0.len = buf.len() as u32;
+        sqe.0.op_flags = flags as u32;
+        sqe
+    }
+
+    /// Receive from the socket `fd` into `buf`, `flags` are the `recv(2)`
+    /// flags
+    pub fn recv(fd: RawFd, buf: &mut [u8], flags: c_int) -> Sqe {
+        let mut sqe = Sqe::new(IORING_OP_RECV, fd);
+        sqe.0.addr = buf.as_mut_ptr() as u64;
+        sqe.0.len = buf.len() as u32;
+        sqe.0.op_flags = flags as u32;
+        sqe
+    }
+
+    /// Complete after `ts` has elapsed, or after `count` other requests
+    /// have completed, if `count` is not zero
+    ///
+    /// An expired timeout completes with `-ETIME`, a timeout satisfied by
+    /// `count` completions with `0`.
+    pub fn timeout(ts: &Timespec, count: u32, flags: TimeoutFlags) -> Sqe {
+        let mut sqe = Sqe::new(IORING_OP_TIMEOUT, -1);
+        sqe.0.addr = ts as *const Timespec as u64;
+        sqe.0.len = 1;
+        sqe.0.off = count as u64;
+        sqe.0.op_flags = flags.bits();
+        sqe
+    }
+
+    /// Open `path` relative to the directory `dirfd`
+    ///
+    /// `flags` are the `open(2)` flags, e.g. `O_CREAT`. The result of the
+    /// request is the fd of the opened file.
+    pub fn openat(dirfd: RawFd, path: &CStr, flags: c_int, mode: libc::mode_t) -> Sqe {
+        let mut sqe = Sqe::new(IORING_OP_OPENAT, dirfd);
+        sqe.0.addr = path.as_ptr() as u64;
+        sqe.0.len = mode;
+        sqe.0.op_flags = flags as u32;
+        sqe
+    }
+
+    /// Close `fd`
+    pub fn close(fd: RawFd) -> Sqe {
+        Sqe::new(IORING_OP_CLOSE, fd)
+    }
+
+    /// Set the flags of the entry
+    pub fn flags(mut self, flags: SqeFlags) -> Sqe {
+        self.0.flags = flags.bits();
+        self
+    }
+
+    /// Set the user data of the entry, which is passed back unchanged in
+    /// its [`Cqe`]
+    pub fn user_data(mut self, user_data: u64) -> Sqe {
+        self.0.user_data = user_data;
+        self
+    }
+}
+
+/// Completion queue entry
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cqe {
+    /// User data of the completed [`Sqe`]
+    pub user_data: u64,
+    /// Result of the request, a negated errno on failure
+    pub res: i32,
+    /// Request specific flags
+    pub flags: u32,
+}
+
+impl Cqe {
+    /// Return the result of the request, or the error it failed with
+    pub fn result(&self) -> Result<u32> {
+        if self.res < 0 {
+            Err(Error::Syscall(std::io::Error::from_raw_os_error(-self.res)))
+        } else {
+            Ok(self.res as u32)
+        }
+    }
+}
+
+/// An io_uring instance
+///
+/// Requests are queued using [`IoUring::push()`], passed to the kernel in a
+/// single system call using [`IoUring::submit()`], and their results are
+/// collected using [`IoUring::completions()`].
+///
+/// The ring fd becomes readable once completions are available, which makes
+/// an [`IoUring`] usable as event source for event loops.
+#[derive(Debug)]
+pub struct IoUring {
+    fd: FileDesc,
+    flags: UringFlags,
+    features: UringFeatures,
+    sq: SubmissionQueue,
+    cq: CompletionQueue,
+}
+
+#[derive(Debug)]
+struct SubmissionQueue {
+    _ring: Mapping,
+    sqes: Mapping,
+    head: *const AtomicU32,
+    tail: *const AtomicU32,
+    flags: *const AtomicU32,
+    mask: u32,
+    entries: u32,
+    /// Number of entries pushed, but not yet submitted
+    pending: u32,
+}
+
+#[derive(Debug)]
+struct CompletionQueue {
+    ring: Mapping,
+    head: *const AtomicU32,
+    tail: *const AtomicU32,
+    mask: u32,
+    cqes: usize,
+}
+
+// safety: the ring mappings are owned by the `IoUring`
+unsafe impl Send for IoUring {}
+
+impl IoUring {
+    /// Return a new [`IoUring`] with at least `entries` submission queue
+    /// entries
+    pub fn new(entries: u32, flags: UringFlags) -> Result<IoUring> {
+        let mut params = Params {
+            flags: flags.bits(),
+            ..Default::default()
+        };
+
+        let fd = syscall!(syscall(
+            libc::SYS_io_uring_setup,
+            entries,
+            &mut params as *mut Params
+        ))?;
+        let fd = unsafe { FileDesc::from_raw_fd(fd as RawFd) };
+
+        let sq_len = params.sq_off.array as usize + params.sq_entries as usize * 4;
+        let cq_len = params.cq_off.cqes as usize + params.cq_entries as usize * 16;
+        let sqes_len = params.sq_entries as usize * mem::size_of::<RawSqe>();
+
+        // safety: the rings are shared with the kernel, they are only ever
+        // accessed through raw pointers and atomics, never as slices
+        let map = |len: usize, offset| unsafe {
+            Mapping::file(
+                &fd,
+                offset,
+                NonZeroUsize::new(len).ok_or("empty io_uring mapping")?,
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                MapFlags::MAP_SHARED,
+            )
+        };
+
+        let sq_ring = map(sq_len, IORING_OFF_SQ_RING)?;
+        let cq_ring = map(cq_len, IORING_OFF_CQ_RING)?;
+        let sqes = map(sqes_len, IORING_OFF_SQES)?;
+
+        let at = |ring: &Mapping, offset: u32| unsafe {
+            ring.as_ptr().as_ptr().add(offset as usize) as *const AtomicU32
+        };
+
+        // the index array maps ring slots to entries, use the identity
+        let array = at(&sq_ring, params.sq_off.array) as *mut u32;
+        for i in 0..params.sq_entries {
+            unsafe { array.add(i as usize).write(i) };
+        }
+
+        let sq = SubmissionQueue {
+            head: at(&sq_ring, params.sq_off.head),
+            tail: at(&sq_ring, params.sq_off.tail),
+            flags: at(&sq_ring, params.sq_off.flags),
+            mask: unsafe { *(at(&sq_ring, params.sq_off.ring_mask) as *const u32) },
+            entries: params.sq_entries,
+            pending: 0,
+            sqes,
+            _ring: sq_ring,
+        };
+
+        let cq = CompletionQueue {
+            head: at(&cq_ring, params.cq_off.head),
+            tail: at(&cq_ring, params.cq_off.tail),
+            mask: unsafe { *(at(&cq_ring, params.cq_off.ring_mask) as *const u32) },
+            cqes: params.cq_off.cqes as usize,
+            ring: cq_ring,
+        };
+
+        Ok(IoUring {
+            fd,
+            flags,
+            features: UringFeatures::from_bits_truncate(params.features),
+            sq,
+            cq,
+        })
+    }
+
+    /// Return the features supported by the kernel
+    pub fn features(&self) -> UringFeatures {
+        self.features
+    }
+
+    /// Queue `sqe` for submission
+    ///
+    /// This fails if the submission queue is full, in which case queued
+    /// entries must be submitted first.
+    ///
+    /// ### Safety
+    ///
+    /// Buffers, paths and other arguments `sqe` refers to must stay valid
+    /// until the request has completed.
+    pub unsafe fn push(&mut self, sqe: &Sqe) -> Result<()> {
+        let sq = &mut self.sq;
+
+        let head = unsafe { (*sq.head).load(Ordering::Acquire) };
+        let tail = unsafe { (*sq.tail).load(Ordering::Relaxed) };
+
+        if tail.wrapping_sub(head) == sq.entries {
+            return Err("io_uring submission queue is full".into());
+        }
+
+        let slot = (tail & sq.mask) as usize;
+        unsafe {
+            (sq.sqes.as_ptr().as_ptr() as *mut RawSqe)
+                .add(slot)
+                .write(sqe.0);
+            (*sq.tail).store(tail.wrapping_add(1), Ordering::Release);
+        }
+        sq.pending += 1;
+
+        Ok(())
+    }
+
+    /// Submit all queued entries and return the number of entries submitted
+    pub fn submit(&mut self) -> Result<usize> {
+        self.enter(0, 0)
+    }
+
+    /// Submit all queued entries and wait for at least `want` completions
+    ///
+    /// Return the number of entries submitted.
+    pub fn submit_and_wait(&mut self, want: usize) -> Result<usize> {
+        self.enter(want as c_uint, IORING_ENTER_GETEVENTS)
+    }
+
+    fn enter(&mut self, min_complete: c_uint, mut flags: c_uint) -> Result<usize> {
+        let pending = self.sq.pending;
+
+        if self.flags.contains(UringFlags::IORING_SETUP_SQPOLL) {
+            // the kernel thread picks up entries by itself, unless it is idle
+            let sq_flags = unsafe { (*self.sq.flags).load(Ordering::Acquire) };
+            if sq_flags & IORING_SQ_NEED_WAKEUP != 0 {
+                flags |= IORING_ENTER_SQ_WAKEUP;
+            }
+
+            self.sq.pending = 0;
+
+            if flags == 0 {
+                return Ok(pending as usize);
+            }
+        }
+
+        let submitted = syscall!(syscall(
+            libc::SYS_io_uring_enter,
+            self.fd.as_raw_fd(),
+            pending as c_uint,
+            min_complete,
+            flags,
+            ptr::null::<c_void>(),
+            0usize
+        ))? as u32;
+
+        if self.flags.contains(UringFlags::IORING_SETUP_SQPOLL) {
+            return Ok(pending as usize);
+        }
+
+        self.sq.pending -= submitted.min(pending);
+
+        Ok(submitted as usize)
+    }
+
+    /// Return an iterator over the available completions
+    ///
+    /// Each completion is consumed once it has been returned by the
+    /// iterator.
+    pub fn completions(&mut self) -> Completions<'_> {
+        Completions { cq: &mut self.cq }
+    }
+
+    /// Register `bufs` as fixed buffers for [`Sqe::read_fixed()`] and
+    /// [`Sqe::write_fixed()`]
+    ///
+    /// ### Safety
+    ///
+    /// The buffers must stay valid until they are unregistered or the ring
+    /// is dropped.
+    pub unsafe fn register_buffers(&self, bufs: &[IoSliceMut<'_>]) -> Result<()> {
+        self.register(
+            IORING_REGISTER_BUFFERS,
+            bufs.as_ptr() as *const c_void,
+            bufs.len(),
+        )
+    }
+
+    /// Unregister all fixed buffers
+    pub fn unregister_buffers(&self) -> Result<()> {
+        self.register(IORING_UNREGISTER_BUFFERS, ptr::null(), 0)
+    }
+
+    /// Register `fds` as fixed files
+    ///
+    /// Entries using [`SqeFlags::IOSQE_FIXED_FILE`] refer to these files by
+    /// their index. The ring holds a reference to the files, so they may be
+    /// closed after registration.
+    pub fn register_files(&self, fds: &[RawFd]) -> Result<()> {
+        self.register(
+            IORING_REGISTER_FILES,
+            fds.as_ptr() as *const c_void,
+            fds.len(),
+        )
+    }
+
+    /// Unregister all fixed files
+    pub fn unregister_files(&self) -> Result<()> {
+        self.register(IORING_UNREGISTER_FILES, ptr::null(), 0)
+    }
+
+    fn register(&self, opcode: c_uint, arg: *const c_void, nr_args: usize) -> Result<()> {
+        syscall!(syscall(
+            libc::SYS_io_uring_register,
+            self.fd.as_raw_fd(),
+            opcode,
+            arg,
+            nr_args as c_uint
+        ))
+        .map(|_| ())
+    }
+}
+
+impl AsRawFd for IoUring {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AsFd for IoUring {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl event::Source for IoUring {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> std::io::Result<()> {
+        SourceFd(&self.fd.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> std::io::Result<()> {
+        SourceFd(&self.fd.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> std::io::Result<()> {
+        SourceFd(&self.fd.as_raw_fd()).deregister(registry)
+    }
+}
+
+/// Iterator over the completions of an [`IoUring`]
+#[derive(Debug)]
+pub struct Completions<'a> {
+    cq: &'a mut CompletionQueue,
+}
+
+impl Iterator for Completions<'_> {
+    type Item = Cqe;
+
+    fn next(&mut self) -> Option<Cqe> {
+        let cq = &mut self.cq;
+
+        let head = unsafe { (*cq.head).load(Ordering::Relaxed) };
+        let tail = unsafe { (*cq.tail).load(Ordering::Acquire) };
+
+        if head == tail {
+            return None;
+        }
+
+        let cqe = unsafe {
+            (cq.ring.as_ptr().as_ptr().add(cq.cqes) as *const RawCqe)
+                .add((head & cq.mask) as usize)
+                .read()
+        };
+
+        unsafe { (*cq.head).store(head.wrapping_add(1), Ordering::Release) };
+
+        Some(Cqe {
+            user_data: cqe.user_data,
+            res: cqe.res,
+            flags: cqe.flags,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::CString,
+        fs,
+        io::{IoSlice, IoSliceMut, Read, Seek, SeekFrom, Write},
+        mem,
+        net::{Ipv4Addr, TcpListener},
+        os::unix::prelude::{AsRawFd, FromRawFd},
+        time::{Duration, Instant},
+    };
+
+    use anyhow::Result;
+    use mio::{Events, Interest, Poll, Token};
+
+    use crate::{Cqe, FileDesc, IoUring, Sqe, SqeFlags, TimeoutFlags, Timespec, UringFlags};
+
+    fn wait_all(ring: &mut IoUring, n: usize) -> Result<Vec<Cqe>> {
+        ring.submit_and_wait(n)?;
+
+        let mut cqes: Vec<Cqe> = ring.completions().collect();
+        cqes.sort_by_key(|cqe| cqe.user_data);
+        assert_eq!(cqes.len(), n);
+
+        Ok(cqes)
+    }
+
+    #[test]
+    fn io_uring_nop() -> Result<()> {
+        let mut ring = IoUring::new(4, UringFlags::empty())?;
+
+        for i in 0..4 {
+            unsafe { ring.push(&Sqe::nop().user_data(i))? };
+        }
+
+        // the submission queue is full
+        assert!(unsafe { ring.push(&Sqe::nop()) }.is_err());
+
+        assert_eq!(ring.submit()?, 4);
+
+        let mut cqes = Vec::new();
+        while cqes.len() < 4 {
+            cqes.extend(ring.completions());
+        }
+
+        for (i, cqe) in cqes.iter().enumerate() {
+            assert_eq!(cqe.user_data, i as u64);
+            assert_eq!(cqe.result()?, 0);
+        }
+
+        assert_eq!(ring.completions().next(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn io_uring_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dirfd = fs::File::open(dir.path())?;
+        let path = CString::new("file")?;
+
+        let mut ring = IoUring::new(8, UringFlags::empty())?;
+
+        unsafe {
+            ring.push(
+                &Sqe::openat(
+                    dirfd.as_raw_fd(),
+                    &path,
+                    libc::O_RDWR | libc::O_CREAT | libc::O_CLOEXEC,
+                    0o600,
+                )
+                .user_data(1),
+            )?
+        };
+
+        let cqes = wait_all(&mut ring, 1)?;
+        let fd = cqes[0].result()? as i32;
+
+        // linked requests run in order
+        let (a, b) = (b"hello ".as_slice(), b"uring".as_slice());
+        let mut buf = [0u8; 11];
+        let (mut x, mut y) = ([0u8; 6], [0u8; 5]);
+        let bufs = [IoSlice::new(a), IoSlice::new(b)];
+
+        unsafe {
+            ring.push(
+                &Sqe::writev(fd, &bufs, 0)
+                    .flags(SqeFlags::IOSQE_IO_LINK)
+                    .user_data(1),
+            )?;
+            ring.push(
+                &Sqe::read(fd, &mut buf, 0)
+                    .flags(SqeFlags::IOSQE_IO_LINK)
+                    .user_data(2),
+            )?;
+            ring.push(&Sqe::write(fd, b"!", 11).user_data(3))?;
+        }
+
+        let cqes = wait_all(&mut ring, 3)?;
+        assert_eq!(cqes[0].result()?, 11);
+        assert_eq!(cqes[1].result()?, 11);
+        assert_eq!(cqes[2].result()?, 1);
+        assert_eq!(&buf, b"hello uring");
+
+        let mut bufs = [IoSliceMut::new(&mut x), IoSliceMut::new(&mut y)];
+
+        unsafe {
+            ring.push(
+                &Sqe::readv(fd, &mut bufs, 1)
+                    .flags(SqeFlags::IOSQE_IO_LINK)
+                    .user_data(1),
+            )?;
+            ring.push(&Sqe::close(fd).user_data(2))?;
+        }
+
+        let cqes = wait_all(&mut ring, 2)?;
+        assert_eq!(cqes[0].result()?, 11);
+        assert_eq!(cqes[1].result()?, 0);
+        assert_eq!((&x, &y), (b"ello u", b"ring!"));
+
+        assert_eq!(fs::read(dir.path().join("file"))?, b"hello uring!");
+
+        // errors are reported as negated errno
+        unsafe { ring.push(&Sqe::close(-1))? };
+        let cqes = wait_all(&mut ring, 1)?;
+        assert_eq!(cqes[0].res, -libc::EBADF);
+        assert!(cqes[0].result().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn io_uring_fixed() -> Result<()> {
+        let mut file = tempfile::tempfile()?;
+        file.write_all(b"fixed buffer")?;
+
+        let mut ring = IoUring::new(4, UringFlags::empty())?;
+
+        let mut mem = vec![0u8; 64];
+        unsafe { ring.register_buffers(&[IoSliceMut::new(&mut mem)])? };
+        ring.register_files(&[file.as_raw_fd()])?;
+
+        // read into the registered buffer, using the registered file
+        let (head, tail) = mem.split_at_mut(32);
+        unsafe {
+            ring.push(
+                &Sqe::read_fixed(0, &mut head[..5], 0, 0)
+                    .flags(SqeFlags::IOSQE_FIXED_FILE)
+                    .user_data(1),
+            )?;
+            ring.push(
+                &Sqe::read_fixed(0, &mut tail[..6], 6, 0)
+                    .flags(SqeFlags::IOSQE_FIXED_FILE)
+                    .user_data(2),
+            )?;
+        }
+
+        let cqes = wait_all(&mut ring, 2)?;
+        assert_eq!(cqes[0].result()?, 5);
+        assert_eq!(cqes[1].result()?, 6);
+        assert_eq!(&mem[..5], b"fixed");
+        assert_eq!(&mem[32..38], b"buffer");
+
+        unsafe {
+            ring.push(&Sqe::write_fixed(0, &mem[32..38], 0, 0).flags(SqeFlags::IOSQE_FIXED_FILE))?
+        };
+        let cqes = wait_all(&mut ring, 1)?;
+        assert_eq!(cqes[0].result()?, 6);
+
+        let mut content = String::new();
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_string(&mut content)?;
+        assert_eq!(content, "bufferbuffer");
+
+        ring.unregister_files()?;
+        ring.unregister_buffers()?;
+
+        // the buffer is no longer registered
+        unsafe { ring.push(&Sqe::read_fixed(file.as_raw_fd(), &mut mem[..1], 0, 0))? };
+        let cqes = wait_all(&mut ring, 1)?;
+        assert!(cqes[0].result().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn io_uring_timeout() -> Result<()> {
+        let mut ring = IoUring::new(4, UringFlags::empty())?;
+
+        let ts = Timespec::from(Duration::from_millis(20));
+        let start = Instant::now();
+
+        unsafe { ring.push(&Sqe::timeout(&ts, 0, TimeoutFlags::empty()))? };
+        let cqes = wait_all(&mut ring, 1)?;
+
+        assert_eq!(cqes[0].res, -libc::ETIME);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        // a timeout completes early, once `count` other requests completed
+        let ts = Timespec::from(Duration::from_secs(10));
+
+        unsafe {
+            ring.push(&Sqe::timeout(&ts, 1, TimeoutFlags::empty()).user_data(1))?;
+            ring.push(&Sqe::nop().user_data(2))?;
+        }
+
+        let cqes = wait_all(&mut ring, 2)?;
+        assert_eq!(cqes[0].res, 0);
+        assert_eq!(cqes[1].res, 0);
+        assert!(start.elapsed() < Duration::from_secs(10));
+
+        Ok(())
+    }
+
+    #[test]
+    fn io_uring_net() -> Result<()> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let port = listener.local_addr()?.port();
+
+        let sock = syscall!(socket(
+            libc::AF_INET,
+            libc::SOCK_STREAM | libc::SOCK_CLOEXEC,
+            0
+        ))?;
+        let sock = unsafe { FileDesc::from_raw_fd(sock) };
+
+        let addr = libc::sockaddr_in {
+            sin_family: libc::AF_INET as libc::sa_family_t,
+            sin_port: port.to_be(),
+            sin_addr: libc::in_addr {
+                s_addr: u32::from(Ipv4Addr::LOCALHOST).to_be(),
+            },
+            sin_zero: [0; 8],
+        };
+
+        let mut ring = IoUring::new(8, UringFlags::empty())?;
+
+        unsafe {
+            ring.push(&Sqe::accept(listener.as_raw_fd(), libc::SOCK_CLOEXEC).user_data(1))?;
+            ring.push(
+                &Sqe::connect(
+                    sock.as_raw_fd(),
+                    &addr as *const libc::sockaddr_in as *const libc::sockaddr,
+                    mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+                )
+                .user_data(2),
+            )?;
+        }
+
+        let cqes = wait_all(&mut ring, 2)?;
+        let conn = unsafe { FileDesc::from_raw_fd(cqes[0].result()? as i32) };
+        assert_eq!(cqes[1].result()?, 0);
+
+        let mut buf = [0u8; 16];
+
+        unsafe {
+            ring.push(&Sqe::recv(conn.as_raw_fd(), &mut buf, 0).user_data(1))?;
+            ring.push(&Sqe::send(sock.as_raw_fd(), b"ping", libc::MSG_NOSIGNAL).user_data(2))?;
+        }
+
+        let cqes = wait_all(&mut ring, 2)?;
+        assert_eq!(cqes[0].result()?, 4);
+        assert_eq!(cqes[1].result()?, 4);
+        assert_eq!(&buf[..4], b"ping");
+
+        Ok(())
+    }
+
+    #[test]
+    fn io_uring_poll() -> Result<()> {
+        const RING: Token = Token(7);
+
+        let mut ring = IoUring::new(2, UringFlags::empty())?;
+
+        let mut poll = Poll::new()?;
+        let mut events = Events::with_capacity(1);
+
+        poll.registry()
+            .register(&mut ring, RING, Interest::READABLE)?;
+
+        unsafe { ring.push(&Sqe::nop().user_data(42))? };
+        ring.submit()?;
+
+        poll.poll(&mut events, Some(Duration::from_secs(5)))?;
+
+        let evt = events.iter().next().unwrap();
+        assert_eq!(evt.token(), RING);
+        assert!(evt.is_readable());
+
+        assert_eq!(ring.completions().next().map(|cqe| cqe.user_data), Some(42));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,539 @@
+//!
+//! This file is part of syscall-rs
+//!
+
+use std::{
+    ffi::c_void,
+    fs::OpenOptions,
+    io, mem,
+    os::unix::{
+        fs::OpenOptionsExt,
+        prelude::{AsRawFd, FromRawFd},
+    },
+    path::Path,
+    ptr,
+};
+
+use bitflags::bitflags;
+use libc::c_uint;
+
+use crate::{Error, FileDesc, Result, set_no_new_privs};
+
+const LANDLOCK_CREATE_RULESET_VERSION: c_uint = 1 << 0;
+
+const LANDLOCK_RULE_PATH_BENEATH: c_uint = 1;
+const LANDLOCK_RULE_NET_PORT: c_uint = 2;
+
+/// `struct landlock_ruleset_attr`
+#[repr(C)]
+struct RulesetAttr {
+    handled_access_fs: u64,
+    handled_access_net: u64,
+    scoped: u64,
+}
+
+/// `struct landlock_path_beneath_attr`
+#[repr(C, packed)]
+struct PathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: i32,
+}
+
+/// `struct landlock_net_port_attr`
+#[repr(C)]
+struct NetPortAttr {
+    allowed_access: u64,
+    port: u64,
+}
+
+bitflags! {
+    /// Filesystem access rights
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct AccessFs: u64 {
+        /// Execute a file
+        const LANDLOCK_ACCESS_FS_EXECUTE = 1 << 0;
+        /// Open a file with write access
+        const LANDLOCK_ACCESS_FS_WRITE_FILE = 1 << 1;
+        /// Open a file with read access
+        const LANDLOCK_ACCESS_FS_READ_FILE = 1 << 2;
+        /// Open a directory or list its content
+        const LANDLOCK_ACCESS_FS_READ_DIR = 1 << 3;
+        /// Remove an empty directory or rename one
+        const LANDLOCK_ACCESS_FS_REMOVE_DIR = 1 << 4;
+        /// Unlink or rename a file
+        const LANDLOCK_ACCESS_FS_REMOVE_FILE = 1 << 5;
+        /// Create, rename or link a character device
+        const LANDLOCK_ACCESS_FS_MAKE_CHAR = 1 << 6;
+        /// Create or rename a directory
+        const LANDLOCK_ACCESS_FS_MAKE_DIR = 1 << 7;
+        /// Create, rename or link a regular file
+        const LANDLOCK_ACCESS_FS_MAKE_REG = 1 << 8;
+        /// Create, rename or link a UNIX domain socket
+        const LANDLOCK_ACCESS_FS_MAKE_SOCK = 1 << 9;
+        /// Create, rename or link a named pipe
+        const LANDLOCK_ACCESS_FS_MAKE_FIFO = 1 << 10;
+        /// Create, rename or link a block device
+        const LANDLOCK_ACCESS_FS_MAKE_BLOCK = 1 << 11;
+        /// Create, rename or link a symbolic link
+        const LANDLOCK_ACCESS_FS_MAKE_SYM = 1 << 12;
+        /// Link or rename a file from or to a different directory, ABI 2
+        const LANDLOCK_ACCESS_FS_REFER = 1 << 13;
+        /// Truncate a file, ABI 3
+        const LANDLOCK_ACCESS_FS_TRUNCATE = 1 << 14;
+        /// Use `ioctl(2)` on character and block devices, ABI 5
+        const LANDLOCK_ACCESS_FS_IOCTL_DEV = 1 << 15;
+    }
+}
+
+impl AccessFs {
+    /// Return the access rights supported by Landlock ABI `abi`
+    pub fn from_abi(abi: u32) -> AccessFs {
+        match abi {
+            0 => AccessFs::empty(),
+            1 => AccessFs::from_bits_truncate((1 << 13) - 1),
+            2 => AccessFs::from_bits_truncate((1 << 14) - 1),
+            3 | 4 => AccessFs::from_bits_truncate((1 << 15) - 1),
+            _ => AccessFs::all(),
+        }
+    }
+
+    /// Return the access rights that apply to files, as opposed to
+    /// directories
+    pub fn file() -> AccessFs {
+        AccessFs::LANDLOCK_ACCESS_FS_EXECUTE
+            | AccessFs::LANDLOCK_ACCESS_FS_WRITE_FILE
+            | AccessFs::LANDLOCK_ACCESS_FS_READ_FILE
+            | AccessFs::LANDLOCK_ACCESS_FS_TRUNCATE
+            | AccessFs::LANDLOCK_ACCESS_FS_IOCTL_DEV
+    }
+}
+
+bitflags! {
+    /// Network access rights, ABI 4
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct AccessNet: u64 {
+        /// Bind a TCP socket to a port
+        const LANDLOCK_ACCESS_NET_BIND_TCP = 1 << 0;
+        /// Connect a TCP socket to a port
+        const LANDLOCK_ACCESS_NET_CONNECT_TCP = 1 << 1;
+    }
+}
+
+impl AccessNet {
+    /// Return the access rights supported by Landlock ABI `abi`
+    pub fn from_abi(abi: u32) -> AccessNet {
+        if abi >= 4 {
+            AccessNet::all()
+        } else {
+            AccessNet::empty()
+        }
+    }
+}
+
+bitflags! {
+    /// IPC scopes restricted to the Landlock domain, ABI 6
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct Scope: u64 {
+        /// Deny connecting to abstract UNIX sockets created outside the domain
+        const LANDLOCK_SCOPE_ABSTRACT_UNIX_SOCKET = 1 << 0;
+        /// Deny sending signals to processes outside the domain
+        const LANDLOCK_SCOPE_SIGNAL = 1 << 1;
+    }
+}
+
+impl Scope {
+    /// Return the scopes supported by Landlock ABI `abi`
+    pub fn from_abi(abi: u32) -> Scope {
+        if abi >= 6 {
+            Scope::all()
+        } else {
+            Scope::empty()
+        }
+    }
+}
+
+/// Return the Landlock ABI version of the running kernel, `0` if Landlock is
+/// not supported or disabled
+pub fn landlock_abi() -> Result<u32> {
+    match syscall!(syscall(
+        libc::SYS_landlock_create_ruleset,
+        ptr::null::<RulesetAttr>(),
+        0,
+        LANDLOCK_CREATE_RULESET_VERSION
+    )) {
+        Ok(abi) => Ok(abi as u32),
+        Err(Error::Syscall(err))
+            if matches!(err.raw_os_error(), Some(libc::ENOSYS | libc::EOPNOTSUPP)) =>
+        {
+            Ok(0)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// How much of a [`Ruleset`] was enforced by [`Ruleset::restrict_self`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Enforcement {
+    /// All requested access rights and scopes are restricted
+    Full,
+    /// Some requested access rights or scopes are not supported by the
+    /// kernel and remain unrestricted
+    Partial,
+    /// Landlock is not supported or none of the requested access rights are,
+    /// nothing is restricted
+    None,
+}
+
+/// Result of [`Ruleset::restrict_self`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Restricted {
+    /// Landlock ABI version of the kernel
+    pub abi: u32,
+    /// Filesystem access rights actually restricted
+    pub fs: AccessFs,
+    /// Network access rights actually restricted
+    pub net: AccessNet,
+    /// IPC scopes actually restricted
+    pub scope: Scope,
+    /// Summary of what was enforced
+    pub enforcement: Enforcement,
+}
+
+/// A Landlock ruleset
+///
+/// A ruleset handles a set of access rights: they are denied after
+/// [`Ruleset::restrict_self`], unless allowed by a rule. Access rights that
+/// are not handled remain unrestricted.
+///
+/// In best-effort mode, access rights and scopes not supported by the kernel
+/// are dropped silently instead of failing. [`Ruleset::restrict_self`] reports
+/// what was actually enforced.
+#[derive(Debug)]
+pub struct Ruleset {
+    fd: Option<FileDesc>,
+    abi: u32,
+    best_effort: bool,
+    requested: (AccessFs, AccessNet, Scope),
+    handled: (AccessFs, AccessNet, Scope),
+}
+
+impl Ruleset {
+    /// Create a ruleset handling the access rights `fs` and `net`, and
+    /// restricting the IPC `scope`
+    ///
+    /// Unless `best_effort` is set, this fails with `EOPNOTSUPP` if the kernel
+    /// doesn't support all of them.
+    pub fn new(fs: AccessFs, net: AccessNet, scope: Scope, best_effort: bool) -> Result<Ruleset> {
+        let abi = landlock_abi()?;
+        let requested = (fs, net, scope);
+        let handled = handled(abi, requested, best_effort)?;
+
+        // the kernel refuses to create a ruleset that handles nothing
+        let fd = if handled == Default::default() {
+            None
+        } else {
+            let attr = RulesetAttr {
+                handled_access_fs: handled.0.bits(),
+                handled_access_net: handled.1.bits(),
+                scoped: handled.2.bits(),
+            };
+
+            let fd = syscall!(syscall(
+                libc::SYS_landlock_create_ruleset,
+                &attr as *const RulesetAttr,
+                mem::size_of::<RulesetAttr>(),
+                0
+            ))?;
+
+            Some(unsafe { FileDesc::from_raw_fd(fd as i32) })
+        };
+
+        Ok(Ruleset {
+            fd,
+            abi,
+            best_effort,
+            requested,
+            handled,
+        })
+    }
+
+    /// Allow `access` to the file or directory hierarchy at `path`
+    ///
+    /// In best-effort mode, access rights not handled by the ruleset are
+    /// dropped, as are directory access rights if `path` is a file.
+    pub fn add_path<P: AsRef<Path>>(&mut self, path: P, access: AccessFs) -> Result<()> {
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_PATH | libc::O_CLOEXEC)
+            .open(path)?;
+
+        let mut access = access;
+
+        if self.best_effort {
+            access &= self.handled.0;
+
+            if !file.metadata()?.is_dir() {
+                access &= AccessFs::file();
+            }
+
+            if access.is_empty() {
+                return Ok(());
+            }
+        }
+
+        let attr = PathBeneathAttr {
+            allowed_access: access.bits(),
+            parent_fd: file.as_raw_fd(),
+        };
+
+        self.add_rule(
+            LANDLOCK_RULE_PATH_BENEATH,
+            &attr as *const _ as *const c_void,
+        )
+    }
+
+    /// Allow `access` to the TCP `port`
+    ///
+    /// In best-effort mode, access rights not handled by the ruleset are
+    /// dropped.
+    pub fn add_port(&mut self, port: u16, access: AccessNet) -> Result<()> {
+        let mut access = access;
+
+        if self.best_effort {
+            access &= self.handled.1;
+
+            if access.is_empty() {
+                return Ok(());
+            }
+        }
+
+        let attr = NetPortAttr {
+            allowed_access: access.bits(),
+            port: port as u64,
+        };
+
+        self.add_rule(LANDLOCK_RULE_NET_PORT, &attr as *const _ as *const c_void)
+    }
+
+    fn add_rule(&mut self, rule_type: c_uint, attr: *const c_void) -> Result<()> {
+        let Some(fd) = &self.fd else {
+            return Ok(());
+        };
+
+        syscall!(syscall(
+            libc::SYS_landlock_add_rule,
+            fd.as_raw_fd(),
+            rule_type,
+            attr,
+            0
+        ))
+        .map(|_| ())
+    }
+
+    /// Restrict the calling thread to the ruleset
+    ///
+    /// This sets `PR_SET_NO_NEW_PRIVS` first, so no privileges are needed.
+    /// The restriction can't be removed and is inherited by children.
+    pub fn restrict_self(self) -> Result<Restricted> {
+        let (fs, net, scope) = self.handled;
+
+        let enforcement = match &self.fd {
+            None => Enforcement::None,
+            Some(fd) => {
+                set_no_new_privs()?;
+                syscall!(syscall(libc::SYS_landlock_restrict_self, fd.as_raw_fd(), 0))?;
+
+                if self.handled == self.requested {
+                    Enforcement::Full
+                } else {
+                    Enforcement::Partial
+                }
+            }
+        };
+
+        Ok(Restricted {
+            abi: self.abi,
+            fs,
+            net,
+            scope,
+            enforcement,
+        })
+    }
+}
+
+/// Return the access rights and scopes to handle for `requested`, given the
+/// Landlock ABI `abi`
+fn handled(
+    abi: u32,
+    requested: (AccessFs, AccessNet, Scope),
+    best_effort: bool,
+) -> Result<(AccessFs, AccessNet, Scope)> {
+    let (fs, net, scope) = requested;
+    let handled = (
+        fs & AccessFs::from_abi(abi),
+        net & AccessNet::from_abi(abi),
+        scope & Scope::from_abi(abi),
+    );
+
+    if best_effort || (abi > 0 && handled == requested) {
+        Ok(handled)
+    } else {
+        Err(io::Error::from_raw_os_error(libc::EOPNOTSUPP).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        io::ErrorKind,
+        net::{Ipv4Addr, TcpListener},
+    };
+
+    use anyhow::{Result, ensure};
+
+    use super::{AccessFs, AccessNet, Enforcement, Ruleset, Scope, handled, landlock_abi};
+    use crate::test_util::in_child;
+
+    #[test]
+    fn landlock_compat() -> Result<()> {
+        assert_eq!(AccessFs::from_abi(0), AccessFs::empty());
+        assert!(AccessFs::from_abi(1).contains(AccessFs::LANDLOCK_ACCESS_FS_MAKE_SYM));
+        assert!(!AccessFs::from_abi(1).contains(AccessFs::LANDLOCK_ACCESS_FS_REFER));
+        assert!(AccessFs::from_abi(3).contains(AccessFs::LANDLOCK_ACCESS_FS_TRUNCATE));
+        assert_eq!(AccessFs::from_abi(7), AccessFs::all());
+        assert_eq!(AccessNet::from_abi(3), AccessNet::empty());
+        assert_eq!(AccessNet::from_abi(4), AccessNet::all());
+        assert_eq!(Scope::from_abi(5), Scope::empty());
+
+        let requested = (
+            AccessFs::all(),
+            AccessNet::LANDLOCK_ACCESS_NET_BIND_TCP,
+            Scope::LANDLOCK_SCOPE_SIGNAL,
+        );
+
+        assert!(handled(3, requested, false).is_err());
+        assert!(handled(0, requested, false).is_err());
+        assert_eq!(handled(6, requested, false)?, requested);
+        assert_eq!(
+            handled(3, requested, true)?,
+            (AccessFs::from_abi(3), AccessNet::empty(), Scope::empty())
+        );
+        assert_eq!(handled(0, requested, true)?, Default::default());
+
+        Ok(())
+    }
+
+    #[test]
+    fn landlock_fs() -> Result<()> {
+        if landlock_abi()? == 0 {
+            return Ok(());
+        }
+
+        let allowed = tempfile::tempdir()?;
+        let denied = tempfile::tempdir()?;
+        fs::write(allowed.path().join("file"), b"allowed")?;
+        fs::write(denied.path().join("file"), b"denied")?;
+
+        in_child(|| {
+            let access = AccessFs::LANDLOCK_ACCESS_FS_READ_FILE
+                | AccessFs::LANDLOCK_ACCESS_FS_WRITE_FILE
+                | AccessFs::LANDLOCK_ACCESS_FS_READ_DIR
+                | AccessFs::LANDLOCK_ACCESS_FS_MAKE_REG;
+
+            let mut ruleset = Ruleset::new(access, AccessNet::empty(), Scope::empty(), false)?;
+            ruleset.add_path(allowed.path(), access)?;
+            // directory access rights don't apply to files
+            ensure!(
+                ruleset
+                    .add_path(denied.path().join("file"), access)
+                    .is_err()
+            );
+
+            let status = ruleset.restrict_self()?;
+            ensure!(status.enforcement == Enforcement::Full);
+            ensure!(status.fs == access);
+
+            ensure!(fs::read(allowed.path().join("file"))? == b"allowed");
+            fs::write(allowed.path().join("new"), b"new")?;
+
+            let err = fs::read(denied.path().join("file")).unwrap_err();
+            ensure!(err.kind() == ErrorKind::PermissionDenied, "{}", err);
+            let err = fs::read_dir(denied.path()).unwrap_err();
+            ensure!(err.kind() == ErrorKind::PermissionDenied, "{}", err);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn landlock_best_effort() -> Result<()> {
+        let abi = landlock_abi()?;
+
+        let dir = tempfile::tempdir()?;
+        fs::write(dir.path().join("file"), b"data")?;
+
+        in_child(|| {
+            let mut ruleset = Ruleset::new(
+                AccessFs::all(),
+                AccessNet::all(),
+                Scope::LANDLOCK_SCOPE_SIGNAL,
+                true,
+            )?;
+            // dropping the directory access rights makes this a valid rule
+            ruleset.add_path(dir.path().join("file"), AccessFs::all())?;
+
+            let status = ruleset.restrict_self()?;
+            ensure!(status.abi == abi);
+            ensure!(status.fs == AccessFs::from_abi(abi));
+            ensure!(status.net == AccessNet::from_abi(abi));
+
+            let expected = match abi {
+                0 => Enforcement::None,
+                1..6 => Enforcement::Partial,
+                _ => Enforcement::Full,
+            };
+            ensure!(status.enforcement == expected, "{:?}", status);
+
+            ensure!(fs::read(dir.path().join("file"))? == b"data");
+            if abi > 0 {
+                ensure!(fs::read_dir(dir.path()).is_err());
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn landlock_net() -> Result<()> {
+        if landlock_abi()? < 4 {
+            return Ok(());
+        }
+
+        // pick two free ports
+        let port = |_| -> Result<u16> {
+            Ok(TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?
+                .local_addr()?
+                .port())
+        };
+        let (allowed, denied) = (port(0)?, port(1)?);
+
+        in_child(|| {
+            let mut ruleset = Ruleset::new(
+                AccessFs::empty(),
+                AccessNet::LANDLOCK_ACCESS_NET_BIND_TCP,
+                Scope::empty(),
+                false,
+            )?;
+            ruleset.add_port(allowed, AccessNet::LANDLOCK_ACCESS_NET_BIND_TCP)?;
+            ensure!(ruleset.restrict_self()?.enforcement == Enforcement::Full);
+
+            TcpListener::bind((Ipv4Addr::LOCALHOST, allowed))?;
+
+            let err = TcpListener::bind((Ipv4Addr::LOCALHOST, denied)).unwrap_err();
+            ensure!(err.kind() == ErrorKind::PermissionDenied, "{}", err);
+
+            Ok(())
+        })
+    }
+}
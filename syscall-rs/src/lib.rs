@@ -21,19 +21,94 @@ macro_rules! syscall {
     }};
 }
 
+mod capability;
+mod cgroup;
 mod elf;
 mod error;
+mod eventfd;
+mod fanotify;
 mod fd;
+mod inotify;
+mod io_uring;
+mod landlock;
 mod macros;
+mod maps;
+mod memfd;
 mod memory;
+mod mount;
+mod namespace;
+mod pidfd;
+mod pipe;
+mod prctl;
+mod process;
+mod process_vm;
+// the BPF filters check the audit architecture, which is only known for these
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+mod seccomp;
 mod signal;
 mod stdio;
+#[cfg(test)]
+pub(crate) mod test_util;
+mod timerfd;
 mod wait;
 
-pub use elf::build_id;
+pub use capability::{
+    CapSet, Capabilities, Capability, ProcessCaps, SecureBits, ambient_clear, ambient_is_set,
+    ambient_lower, ambient_raise, bounding_drop, bounding_is_set, keepcaps, securebits,
+    set_keepcaps, set_securebits,
+};
+pub use cgroup::{
+    Cgroup, CgroupEvents, CgroupWatch, CpuStat, IoMax, MemoryEvents, MemoryStat, Pressure,
+    PressureStat, Resource, cgroup_mountpoint,
+};
+pub use elf::{
+    BuildId, DebugLink, DynFlags, DynFlags1, Dynamic, Elf, ElfNote, GnuProperty, Hardening,
+    LoadedObject, Relro, Segment, SegmentFlags, SegmentKind, Symbol, SymbolBinding, SymbolKind,
+    build_id, loaded_objects, process_objects,
+};
 pub use error::{Error, Result};
-pub use fd::FileDesc;
-pub use memory::{MapFlags, ProtFlags, mmap, mmap_anonymous, mprotect, munmap};
-pub use signal::{Signal, SignalFd, SignalSet, signal_block, signal_restore};
+pub use eventfd::{EfdFlags, EventFd};
+pub use fanotify::{
+    FanInitFlags, Fanotify, FanotifyEvent, FileHandle, FileId, FileIdKind, MarkFlags, MaskFlags,
+};
+pub use fd::{FileDesc, OFlags, RwfFlags};
+pub use inotify::{Inotify, InotifyEvent, InotifyFlags, WatchDescriptor, WatchMask};
+pub use io_uring::{
+    Completions, Cqe, IoUring, Sqe, SqeFlags, TimeoutFlags, Timespec, UringFeatures, UringFlags,
+};
+pub use landlock::{AccessFs, AccessNet, Enforcement, Restricted, Ruleset, Scope, landlock_abi};
+pub use maps::{MapPath, MemoryMap, MemoryMaps, MemoryUsage, Permissions, Smaps, smaps_rollup};
+pub use memfd::{MemFd, MfdFlags, SealFlags};
+pub use memory::{
+    MapFlags, Mapping, MmapAdvise, MsFlags, ProtFlags, mmap, mmap_anonymous, mprotect, munmap,
+};
+pub use mount::{FsContext, MountAttr, MountFd, OpenTreeFlags, Propagation, pivot_root};
+pub use namespace::{
+    CloneArgs, CloneFlags, Cloned, IdMap, Namespaces, clone3, open_namespace, setns, unshare,
+    write_gid_map, write_setgroups, write_uid_map,
+};
+pub use pidfd::PidFd;
+pub use pipe::{
+    SpliceFlags, copy_file_range, pipe_size, pipe2, sendfile, set_pipe_size, splice, tee, vmsplice,
+};
+pub use prctl::{
+    SpecCtrl, SpecState, Speculation, child_subreaper, dumpable, no_new_privs, parent_death_signal,
+    set_child_subreaper, set_dumpable, set_exe_file, set_no_new_privs, set_parent_death_signal,
+    set_speculation_ctrl, set_thp_disable, set_thread_name, set_timer_slack, speculation_ctrl,
+    thp_disable, thread_name, timer_slack,
+};
+pub use process::{Child, Command};
+pub use process_vm::{RemoteIoVec, read_process_memory, write_process_memory};
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+pub use seccomp::{
+    Action, AddFdFlags, CmpOp, Filter, FilterFlags, Listener, Notification, Program, Response,
+    Rule, syscall_name, syscall_number,
+};
+pub use signal::{
+    AltStack, RtSignal, SaFlags, SfdFlags, SigAction, SigHandler, SigVal, Signal, SignalFd,
+    SignalInfo, SignalSet, signal_action, signal_block, signal_disposition, signal_queue,
+    signal_restore, signal_timedwait, signal_wait,
+};
 pub use stdio::Stdio;
-pub use wait::{WaitStatus, wait};
+pub use timerfd::{ClockId, Expiration, TfdFlags, TimerFd, TimerSetFlags, clock_gettime};
+pub use wait::{WaitId, WaitOptions, WaitStatus, try_wait, wait, wait_pgid, waitid, waitpid};
@@ -0,0 +1,607 @@
+//!
+//! This file is part of syscall-rs
+//!
+
+use std::{
+    ffi::OsStr,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    iter::Peekable,
+    os::unix::ffi::OsStrExt,
+    path::PathBuf,
+    str::FromStr,
+};
+
+use crate::{Error, Result};
+
+/// Access permissions of a [`MemoryMap`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Permissions {
+    /// Region is readable
+    pub read: bool,
+    /// Region is writable
+    pub write: bool,
+    /// Region is executable
+    pub exec: bool,
+    /// Region is shared, otherwise it is private (copy-on-write)
+    pub shared: bool,
+}
+
+impl FromStr for Permissions {
+    type Err = Error;
+
+    /// Parse [`Permissions`] from a string like `r-xp`
+    fn from_str(s: &str) -> Result<Permissions> {
+        let perms = s.as_bytes();
+
+        if perms.len() != 4 {
+            return Err(invalid("invalid permissions"));
+        }
+
+        Ok(Permissions {
+            read: perms[0] == b'r',
+            write: perms[1] == b'w',
+            exec: perms[2] == b'x',
+            shared: perms[3] == b's',
+        })
+    }
+}
+
+/// What a [`MemoryMap`] is backed by
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MapPath {
+    /// Anonymous mapping
+    Anonymous,
+    /// Process heap
+    Heap,
+    /// Main thread stack
+    Stack,
+    /// Virtual dynamic shared object
+    Vdso,
+    /// Variables of the vDSO
+    Vvar,
+    /// Legacy virtual system call page
+    Vsyscall,
+    /// Mapping backed by a file
+    File(PathBuf),
+    /// Mapping backed by a file which has been deleted
+    Deleted(PathBuf),
+    /// Any other pseudo-path, like `[anon:name]` or `[uprobes]`
+    Other(String),
+}
+
+impl From<&[u8]> for MapPath {
+    /// File paths are taken as they are, they need not be valid UTF-8
+    fn from(path: &[u8]) -> Self {
+        match path {
+            b"" => MapPath::Anonymous,
+            b"[heap]" => MapPath::Heap,
+            b"[stack]" => MapPath::Stack,
+            b"[vdso]" => MapPath::Vdso,
+            b"[vvar]" => MapPath::Vvar,
+            b"[vsyscall]" => MapPath::Vsyscall,
+            p if p.starts_with(b"/") => match p.strip_suffix(b" (deleted)") {
+                Some(p) => MapPath::Deleted(OsStr::from_bytes(p).into()),
+                None => MapPath::File(OsStr::from_bytes(p).into()),
+            },
+            p => MapPath::Other(String::from_utf8_lossy(p).into_owned()),
+        }
+    }
+}
+
+impl From<&str> for MapPath {
+    fn from(path: &str) -> Self {
+        path.as_bytes().into()
+    }
+}
+
+/// A memory region of a process as listed in `/proc/<pid>/maps`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryMap {
+    /// Start address of the region
+    pub start: u64,
+    /// End address of the region (exclusive)
+    pub end: u64,
+    /// Access permissions
+    pub perms: Permissions,
+    /// Offset into the backing file
+    pub offset: u64,
+    /// Major and minor number of the device holding the backing file
+    pub dev: (u32, u32),
+    /// Inode of the backing file, `0` for anonymous mappings
+    pub inode: u64,
+    /// What the region is backed by
+    pub path: MapPath,
+}
+
+impl MemoryMap {
+    /// Return the size of the region in bytes
+    pub fn size(&self) -> u64 {
+        self.end - self.start
+    }
+
+    /// Return `true` if `addr` lies within this region
+    pub fn contains(&self, addr: u64) -> bool {
+        self.start <= addr && addr < self.end
+    }
+}
+
+impl FromStr for MemoryMap {
+    type Err = Error;
+
+    /// Parse a [`MemoryMap`] from a line of `/proc/<pid>/maps`
+    fn from_str(line: &str) -> Result<MemoryMap> {
+        MemoryMap::from_bytes(line.as_bytes())
+    }
+}
+
+impl MemoryMap {
+    /// Parse a [`MemoryMap`] from a line of `/proc/<pid>/maps`, which may
+    /// contain a path that is not valid UTF-8
+    fn from_bytes(line: &[u8]) -> Result<MemoryMap> {
+        let mut fields = line.splitn(6, |b| *b == b' ');
+        let mut next = || {
+            fields
+                .next()
+                .and_then(|field| std::str::from_utf8(field).ok())
+                .ok_or_else(|| invalid("missing map field"))
+        };
+
+        let (start, end) = next()?
+            .split_once('-')
+            .ok_or_else(|| invalid("invalid address range"))?;
+        let perms = next()?.parse()?;
+        let offset = next()?;
+        let (major, minor) = next()?
+            .split_once(':')
+            .ok_or_else(|| invalid("invalid device"))?;
+        let inode = next()?;
+        // the path is padded with spaces and may be missing entirely
+        let path = fields.next().unwrap_or_default().trim_ascii_start();
+
+        Ok(MemoryMap {
+            start: hex(start)?,
+            end: hex(end)?,
+            perms,
+            offset: hex(offset)?,
+            dev: (hex(major)? as u32, hex(minor)? as u32),
+            inode: inode.parse().map_err(|_| invalid("invalid inode"))?,
+            path: path.into(),
+        })
+    }
+}
+
+/// Memory usage of a region as listed in `/proc/<pid>/smaps`, or of a whole
+/// process as listed in `/proc/<pid>/smaps_rollup`
+///
+/// All values are in bytes. Fields not reported by the kernel are `0`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// Size of the region
+    pub size: u64,
+    /// Resident set size
+    pub rss: u64,
+    /// Proportional set size
+    pub pss: u64,
+    /// Resident pages shared with other processes and not modified
+    pub shared_clean: u64,
+    /// Resident pages shared with other processes and modified
+    pub shared_dirty: u64,
+    /// Resident pages private to the process and not modified
+    pub private_clean: u64,
+    /// Resident pages private to the process and modified
+    pub private_dirty: u64,
+    /// Pages marked as referenced or accessed
+    pub referenced: u64,
+    /// Pages not backed by a file
+    pub anonymous: u64,
+    /// Pages backed by transparent huge pages
+    pub anon_huge_pages: u64,
+    /// Swapped out pages
+    pub swap: u64,
+    /// Proportional swap size
+    pub swap_pss: u64,
+    /// Pages locked into memory
+    pub locked: u64,
+}
+
+impl MemoryUsage {
+    /// Update the field for `key` from a `value` like `132 kB`. Unknown keys
+    /// are ignored.
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let field = match key {
+            "Size" => &mut self.size,
+            "Rss" => &mut self.rss,
+            "Pss" => &mut self.pss,
+            "Shared_Clean" => &mut self.shared_clean,
+            "Shared_Dirty" => &mut self.shared_dirty,
+            "Private_Clean" => &mut self.private_clean,
+            "Private_Dirty" => &mut self.private_dirty,
+            "Referenced" => &mut self.referenced,
+            "Anonymous" => &mut self.anonymous,
+            "AnonHugePages" => &mut self.anon_huge_pages,
+            "Swap" => &mut self.swap,
+            "SwapPss" => &mut self.swap_pss,
+            "Locked" => &mut self.locked,
+            _ => return Ok(()),
+        };
+
+        let kb = value
+            .trim()
+            .strip_suffix(" kB")
+            .ok_or_else(|| invalid("invalid memory size"))?;
+
+        *field = kb
+            .trim()
+            .parse::<u64>()
+            .map_err(|_| invalid("invalid memory size"))?
+            * 1024;
+
+        Ok(())
+    }
+}
+
+/// Iterator over the lines of a reader as bytes, without the newline
+#[derive(Debug)]
+struct ByteLines<R>(R);
+
+impl<R: BufRead> Iterator for ByteLines<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = Vec::new();
+
+        match self.0.read_until(b'\n', &mut line) {
+            Ok(0) => None,
+            Ok(_) => {
+                if line.last() == Some(&b'\n') {
+                    line.pop();
+                }
+                Some(Ok(line))
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// Iterator over the [`MemoryMap`]s of `/proc/<pid>/maps`
+#[derive(Debug)]
+pub struct MemoryMaps<R> {
+    lines: ByteLines<R>,
+}
+
+impl MemoryMaps<BufReader<File>> {
+    /// Return the [`MemoryMap`]s of the process `pid`, or of the calling
+    /// process in case `pid` is `None`
+    pub fn new<P>(pid: P) -> Result<Self>
+    where
+        P: Into<Option<libc::pid_t>>,
+    {
+        Ok(MemoryMaps::from_reader(BufReader::new(File::open(
+            proc_path(pid.into(), "maps"),
+        )?)))
+    }
+}
+
+impl<R: BufRead> MemoryMaps<R> {
+    /// Return the [`MemoryMap`]s read from `reader` in `/proc/<pid>/maps`
+    /// format
+    pub fn from_reader(reader: R) -> Self {
+        MemoryMaps {
+            lines: ByteLines(reader),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for MemoryMaps<R> {
+    type Item = Result<MemoryMap>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.lines.next().map(|line| {
+            line.map_err(Error::from)
+                .and_then(|line| MemoryMap::from_bytes(&line))
+        })
+    }
+}
+
+/// Iterator over the [`MemoryMap`]s and their [`MemoryUsage`] of
+/// `/proc/<pid>/smaps`
+#[derive(Debug)]
+pub struct Smaps<R: BufRead> {
+    lines: Peekable<ByteLines<R>>,
+}
+
+impl Smaps<BufReader<File>> {
+    /// Return the memory regions of the process `pid`, or of the calling
+    /// process in case `pid` is `None`
+    pub fn new<P>(pid: P) -> Result<Self>
+    where
+        P: Into<Option<libc::pid_t>>,
+    {
+        Ok(Smaps::from_reader(BufReader::new(File::open(proc_path(
+            pid.into(),
+            "smaps",
+        ))?)))
+    }
+}
+
+impl<R: BufRead> Smaps<R> {
+    /// Return the memory regions read from `reader` in `/proc/<pid>/smaps`
+    /// format
+    pub fn from_reader(reader: R) -> Self {
+        Smaps {
+            lines: ByteLines(reader).peekable(),
+        }
+    }
+
+    /// Parse the `Key: value` lines following a region header
+    fn usage(&mut self) -> Result<MemoryUsage> {
+        let mut usage = MemoryUsage::default();
+
+        while let Some(Ok(line)) = self.lines.peek() {
+            // a region header with a non UTF-8 path ends the fields as well
+            let Some((key, value)) = std::str::from_utf8(line).ok().and_then(field) else {
+                break;
+            };
+
+            usage.set(key, value)?;
+            self.lines.next();
+        }
+
+        Ok(usage)
+    }
+}
+
+impl<R: BufRead> Iterator for Smaps<R> {
+    type Item = Result<(MemoryMap, MemoryUsage)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let header = match self.lines.next()? {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e.into())),
+        };
+
+        Some(MemoryMap::from_bytes(&header).and_then(|map| self.usage().map(|usage| (map, usage))))
+    }
+}
+
+/// Return the accumulated [`MemoryUsage`] of all memory regions of the
+/// process `pid`, or of the calling process in case `pid` is `None`
+pub fn smaps_rollup<P>(pid: P) -> Result<MemoryUsage>
+where
+    P: Into<Option<libc::pid_t>>,
+{
+    let data = std::fs::read_to_string(proc_path(pid.into(), "smaps_rollup"))?;
+
+    parse_rollup(&data)
+}
+
+fn parse_rollup(data: &str) -> Result<MemoryUsage> {
+    let mut usage = MemoryUsage::default();
+
+    // skip the `[rollup]` header
+    for line in data.lines().skip(1) {
+        if let Some((key, value)) = field(line) {
+            usage.set(key, value)?;
+        }
+    }
+
+    Ok(usage)
+}
+
+/// Split a `Key: value` line, return `None` for a region header
+fn field(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(':')?;
+
+    // device numbers in a region header contain a colon as well
+    if key.contains(' ') {
+        None
+    } else {
+        Some((key, value))
+    }
+}
+
+fn proc_path(pid: Option<libc::pid_t>, file: &str) -> PathBuf {
+    match pid {
+        Some(pid) => format!("/proc/{pid}/{file}").into(),
+        None => format!("/proc/self/{file}").into(),
+    }
+}
+
+fn hex(s: &str) -> Result<u64> {
+    u64::from_str_radix(s, 16).map_err(|_| invalid("invalid hex number"))
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::Syscall(std::io::Error::new(std::io::ErrorKind::InvalidData, msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt, path::PathBuf};
+
+    use anyhow::Result;
+
+    use super::{MapPath, MemoryMap, MemoryMaps, Permissions, Smaps, parse_rollup, smaps_rollup};
+
+    const MAPS: &str = "\
+56360d441000-56360d443000 r--p 00000000 fe:00 317783                     /usr/bin/head
+56360d443000-56360d449000 r-xp 00002000 fe:00 317783                     /usr/bin/head
+55b60c9c1000-55b60c9e2000 rw-p 00000000 00:00 0                          [heap]
+7f1c2a400000-7f1c2a600000 rw-s 00000000 00:01 2050                       /memfd:buffer (deleted)
+7f1c2a7f0000-7f1c2a7f4000 rw-p 00000000 00:00 0
+7f1c2a7f4000-7f1c2a7f5000 r--p 00000000 00:00 0                          [anon:guard]
+7ffd28484000-7ffd284a5000 rw-p 00000000 00:00 0                          [stack]
+7ffd284ee000-7ffd284f2000 r--p 00000000 00:00 0                          [vvar]
+7ffd284f2000-7ffd284f4000 r-xp 00000000 00:00 0                          [vdso]
+ffffffffff600000-ffffffffff601000 --xp 00000000 00:00 0                  [vsyscall]
+";
+
+    const SMAPS: &str = "\
+55b60c9c1000-55b60c9e2000 rw-p 00000000 00:00 0                          [heap]
+Size:                132 kB
+KernelPageSize:        4 kB
+MMUPageSize:           4 kB
+Rss:                  32 kB
+Pss:                  16 kB
+Shared_Clean:          0 kB
+Shared_Dirty:          0 kB
+Private_Clean:         0 kB
+Private_Dirty:        32 kB
+Referenced:           32 kB
+Anonymous:            32 kB
+AnonHugePages:      2048 kB
+Swap:                  8 kB
+SwapPss:               4 kB
+Locked:                0 kB
+THPeligible:           0
+VmFlags: rd wr mr mw me ac
+7ffd284f2000-7ffd284f4000 r-xp 00000000 00:00 0                          [vdso]
+Size:                  8 kB
+Rss:                   4 kB
+VmFlags: rd ex mr mw me de sd
+";
+
+    const ROLLUP: &str = "\
+555998a40000-7ffd284a5000 ---p 00000000 00:00 0                          [rollup]
+Rss:                1408 kB
+Pss:                 494 kB
+Pss_Dirty:           100 kB
+Shared_Clean:       1240 kB
+Private_Dirty:       100 kB
+Anonymous:           100 kB
+Swap:                  0 kB
+";
+
+    #[test]
+    fn maps_parse() -> Result<()> {
+        let maps = MemoryMaps::from_reader(MAPS.as_bytes()).collect::<Result<Vec<_>, _>>()?;
+
+        assert_eq!(maps.len(), 10);
+
+        assert_eq!(
+            maps[1],
+            MemoryMap {
+                start: 0x56360d443000,
+                end: 0x56360d449000,
+                perms: Permissions {
+                    read: true,
+                    write: false,
+                    exec: true,
+                    shared: false,
+                },
+                offset: 0x2000,
+                dev: (0xfe, 0),
+                inode: 317783,
+                path: MapPath::File(PathBuf::from("/usr/bin/head")),
+            }
+        );
+
+        assert_eq!(maps[2].path, MapPath::Heap);
+        assert_eq!(maps[2].size(), 0x21000);
+        assert!(maps[2].contains(0x55b60c9c1000));
+        assert!(!maps[2].contains(0x55b60c9e2000));
+
+        assert_eq!(maps[3].path, MapPath::Deleted("/memfd:buffer".into()));
+        assert!(maps[3].perms.shared);
+        assert_eq!(maps[4].path, MapPath::Anonymous);
+        assert_eq!(maps[5].path, MapPath::Other("[anon:guard]".into()));
+        assert_eq!(maps[6].path, MapPath::Stack);
+        assert_eq!(maps[7].path, MapPath::Vvar);
+        assert_eq!(maps[8].path, MapPath::Vdso);
+        assert_eq!(maps[9].path, MapPath::Vsyscall);
+        assert_eq!(maps[9].end, 0xffffffffff601000);
+
+        Ok(())
+    }
+
+    #[test]
+    fn maps_parse_invalid() {
+        assert!(
+            "56360d441000 r--p 00000000 fe:00 317783"
+                .parse::<MemoryMap>()
+                .is_err()
+        );
+        assert!(
+            "56360d441000-56360d443000 r--p"
+                .parse::<MemoryMap>()
+                .is_err()
+        );
+        assert!(
+            "56360d441000-56360d443000 rw 0 fe:00 1"
+                .parse::<MemoryMap>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn maps_parse_non_utf8() -> Result<()> {
+        let line = b"7f1c2a7f0000-7f1c2a7f4000 r--p 00000000 fe:00 42    /tmp/\xff.so\n";
+        let path = MapPath::File(OsStr::from_bytes(b"/tmp/\xff.so").into());
+
+        let maps = MemoryMaps::from_reader(&line[..]).collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(maps.len(), 1);
+        assert_eq!(maps[0].path, path);
+
+        let smaps = [&line[..], b"Rss:                   4 kB\n", &line[..]].concat();
+        let smaps = Smaps::from_reader(&smaps[..]).collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(smaps.len(), 2);
+        assert_eq!(smaps[0].0.path, path);
+        assert_eq!(smaps[0].1.rss, 4 * 1024);
+        assert_eq!(smaps[1].1.rss, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn smaps_parse() -> Result<()> {
+        let smaps = Smaps::from_reader(SMAPS.as_bytes()).collect::<Result<Vec<_>, _>>()?;
+
+        assert_eq!(smaps.len(), 2);
+
+        let (map, usage) = &smaps[0];
+        assert_eq!(map.path, MapPath::Heap);
+        assert_eq!(usage.size, 132 * 1024);
+        assert_eq!(usage.rss, 32 * 1024);
+        assert_eq!(usage.pss, 16 * 1024);
+        assert_eq!(usage.anon_huge_pages, 2048 * 1024);
+        assert_eq!(usage.swap, 8 * 1024);
+        assert_eq!(usage.swap_pss, 4 * 1024);
+
+        let (map, usage) = &smaps[1];
+        assert_eq!(map.path, MapPath::Vdso);
+        assert_eq!(usage.rss, 4 * 1024);
+        assert_eq!(usage.pss, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn smaps_rollup_parse() -> Result<()> {
+        let usage = parse_rollup(ROLLUP)?;
+
+        assert_eq!(usage.rss, 1408 * 1024);
+        assert_eq!(usage.pss, 494 * 1024);
+        assert_eq!(usage.shared_clean, 1240 * 1024);
+        assert_eq!(usage.size, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn maps_self() -> Result<()> {
+        let exe = std::env::current_exe()?;
+        let maps = MemoryMaps::new(None)?.collect::<Result<Vec<_>, _>>()?;
+
+        assert!(maps.iter().any(|m| m.path == MapPath::Stack));
+        assert!(maps.iter().any(|m| m.path == MapPath::File(exe.clone())));
+
+        let smaps =
+            Smaps::new(std::process::id() as libc::pid_t)?.collect::<Result<Vec<_>, _>>()?;
+        assert!(smaps.iter().any(|(_, usage)| usage.rss > 0));
+
+        assert!(smaps_rollup(None)?.rss > 0);
+
+        Ok(())
+    }
+}
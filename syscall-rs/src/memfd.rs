@@ -0,0 +1,241 @@
+//!
+//! This file is part of syscall-rs
+//!
+
+use std::{
+    ffi::CString,
+    num::NonZeroUsize,
+    os::unix::prelude::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, RawFd},
+};
+
+use libc::{c_int, c_uint};
+
+use crate::{FileDesc, MapFlags, Mapping, ProtFlags, Result, libc_bitflags};
+
+libc_bitflags! {
+    /// Flags for creating a [`MemFd`]
+    pub struct MfdFlags: c_uint {
+        /// Close the memfd on `exec`
+        MFD_CLOEXEC;
+        /// Allow sealing operations on the memfd
+        MFD_ALLOW_SEALING;
+        /// Create the memfd in the hugetlbfs filesystem
+        MFD_HUGETLB;
+        /// Create a non-executable memfd, implies `MFD_ALLOW_SEALING`
+        MFD_NOEXEC_SEAL;
+        /// Create an executable memfd
+        MFD_EXEC;
+    }
+}
+
+libc_bitflags! {
+    /// Seals restricting the operations permitted on a [`MemFd`]
+    pub struct SealFlags: c_int {
+        /// Prevent further seals from being set
+        F_SEAL_SEAL;
+        /// Prevent the file from shrinking
+        F_SEAL_SHRINK;
+        /// Prevent the file from growing
+        F_SEAL_GROW;
+        /// Prevent writes to the file, including shared writable mappings
+        F_SEAL_WRITE;
+        /// Prevent future writes, while allowing existing shared writable
+        /// mappings to be used further
+        F_SEAL_FUTURE_WRITE;
+        /// Prevent the executable permission bits from being changed
+        F_SEAL_EXEC;
+    }
+}
+
+/// Anonymous in-memory file
+///
+/// A [`MemFd`] behaves like a regular file, but lives in RAM only. Sealing
+/// allows a producer to hand out a buffer which can no longer be modified,
+/// such that a consumer does not have to guard against concurrent changes.
+#[derive(Debug)]
+pub struct MemFd(FileDesc);
+
+impl MemFd {
+    /// Create a new [`MemFd`]
+    ///
+    /// `name` is used for debugging purposes only and shows up as the target
+    /// of the `/proc/self/fd/` symlink. It does not need to be unique.
+    pub fn new(name: &str, flags: MfdFlags) -> Result<MemFd> {
+        let name = CString::new(name)?;
+
+        let fd = syscall!(memfd_create(name.as_ptr(), flags.bits()))?;
+
+        Ok(MemFd(unsafe { FileDesc::from_raw_fd(fd) }))
+    }
+
+    /// Truncate or extend the file to `len` bytes
+    pub fn set_len(&self, len: u64) -> Result<()> {
+        syscall!(ftruncate(self.as_raw_fd(), len as libc::off_t)).map(|_| ())
+    }
+
+    /// Add `seals` to the set of seals of the file
+    ///
+    /// Note that this requires the [`MemFd`] to be created with
+    /// [`MfdFlags::MFD_ALLOW_SEALING`].
+    pub fn seal(&self, seals: SealFlags) -> Result<()> {
+        syscall!(fcntl(self.as_raw_fd(), libc::F_ADD_SEALS, seals.bits())).map(|_| ())
+    }
+
+    /// Return the current set of seals of the file
+    pub fn seals(&self) -> Result<SealFlags> {
+        let seals = syscall!(fcntl(self.as_raw_fd(), libc::F_GET_SEALS))?;
+
+        Ok(SealFlags::from_bits_truncate(seals))
+    }
+
+    /// Map the first `len` bytes of the file into memory
+    ///
+    /// Use [`MapFlags::MAP_SHARED`] for changes to become visible to other
+    /// users of the file.
+    ///
+    /// ### Safety
+    ///
+    /// See [`Mapping::file()`]. A sealed memfd, e.g. with
+    /// [`SealFlags::F_SEAL_WRITE`], rules out modifications by other users.
+    pub unsafe fn map(
+        &self,
+        len: NonZeroUsize,
+        prot: ProtFlags,
+        flags: MapFlags,
+    ) -> Result<Mapping> {
+        unsafe { Mapping::file(self, 0, len, prot, flags) }
+    }
+}
+
+impl AsRef<FileDesc> for MemFd {
+    fn as_ref(&self) -> &FileDesc {
+        &self.0
+    }
+}
+
+impl AsRawFd for MemFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl AsFd for MemFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+impl IntoRawFd for MemFd {
+    fn into_raw_fd(self) -> RawFd {
+        self.0.into_raw_fd()
+    }
+}
+
+impl From<MemFd> for FileDesc {
+    fn from(memfd: MemFd) -> Self {
+        memfd.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use anyhow::Result;
+
+    use crate::{MapFlags, MemFd, MfdFlags, ProtFlags, SealFlags};
+
+    const LEN: NonZeroUsize = NonZeroUsize::new(4096).unwrap();
+
+    #[test]
+    fn memfd_map() -> Result<()> {
+        let memfd = MemFd::new("buffer", MfdFlags::MFD_CLOEXEC)?;
+        memfd.set_len(LEN.get() as u64)?;
+
+        // safety: the slices of both mappings are never alive at the same time
+        let mut producer = unsafe {
+            memfd.map(
+                LEN,
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                MapFlags::MAP_SHARED,
+            )?
+        };
+        let consumer = unsafe { memfd.map(LEN, ProtFlags::PROT_READ, MapFlags::MAP_SHARED)? };
+
+        producer.as_mut_slice().unwrap()[..5].copy_from_slice(b"hello");
+
+        assert_eq!(&consumer.as_slice().unwrap()[..5], b"hello");
+
+        Ok(())
+    }
+
+    #[test]
+    fn memfd_seal() -> Result<()> {
+        let memfd = MemFd::new("sealed", MfdFlags::MFD_ALLOW_SEALING)?;
+        memfd.set_len(LEN.get() as u64)?;
+
+        assert_eq!(memfd.seals()?, SealFlags::empty());
+
+        {
+            // safety: the memfd is private to this test
+            let mut map = unsafe {
+                memfd.map(
+                    LEN,
+                    ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                    MapFlags::MAP_SHARED,
+                )?
+            };
+            map.as_mut_slice().unwrap()[0] = 42;
+
+            // can't seal for writing while a shared writable mapping exists
+            assert_eq!(
+                format!("{}", memfd.seal(SealFlags::F_SEAL_WRITE).err().unwrap()),
+                "System call error: Device or resource busy (os error 16)"
+            );
+        }
+
+        memfd.seal(SealFlags::F_SEAL_SHRINK | SealFlags::F_SEAL_GROW | SealFlags::F_SEAL_WRITE)?;
+        memfd.seal(SealFlags::F_SEAL_SEAL)?;
+
+        assert_eq!(
+            memfd.seals()?,
+            SealFlags::F_SEAL_SHRINK
+                | SealFlags::F_SEAL_GROW
+                | SealFlags::F_SEAL_WRITE
+                | SealFlags::F_SEAL_SEAL
+        );
+
+        assert!(memfd.set_len(2 * LEN.get() as u64).is_err());
+        assert!(
+            unsafe {
+                memfd.map(
+                    LEN,
+                    ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                    MapFlags::MAP_SHARED,
+                )
+            }
+            .is_err()
+        );
+
+        // safety: the memfd is sealed for writing
+        let map = unsafe { memfd.map(LEN, ProtFlags::PROT_READ, MapFlags::MAP_SHARED)? };
+        assert_eq!(map.as_slice().unwrap()[0], 42);
+
+        Ok(())
+    }
+
+    #[test]
+    fn memfd_seal_not_allowed() -> Result<()> {
+        let memfd = MemFd::new("unsealable", MfdFlags::empty())?;
+
+        // sealing is prevented by a default F_SEAL_SEAL
+        assert_eq!(memfd.seals()?, SealFlags::F_SEAL_SEAL);
+
+        assert_eq!(
+            format!("{}", memfd.seal(SealFlags::F_SEAL_WRITE).err().unwrap()),
+            "System call error: Operation not permitted (os error 1)"
+        );
+
+        Ok(())
+    }
+}
@@ -7,11 +7,12 @@ use std::{
     os::unix::io::{AsFd, AsRawFd},
     ptr,
     ptr::NonNull,
+    slice,
 };
 
 use libc::{c_int, c_void, off_t, size_t};
 
-use crate::{Error, Result, libc_bitflags};
+use crate::{Error, Result, libc_bitflags, libc_enum};
 
 libc_bitflags! {
     /// Desired memory protection of a memory mapping.
@@ -157,3 +158,351 @@ pub fn mprotect(addr: NonNull<c_void>, len: size_t, prot: ProtFlags) -> Result<(
 pub fn munmap(addr: NonNull<c_void>, len: size_t) -> Result<()> {
     syscall!(munmap(addr.as_ptr(), len)).map(|_| ())
 }
+
+libc_bitflags! {
+    /// Flags for [`Mapping::sync()`]
+    pub struct MsFlags: c_int {
+        /// Schedule an update, but return immediately
+        MS_ASYNC;
+        /// Request an update and wait for it to complete
+        MS_SYNC;
+        /// Invalidate other mappings of the same file
+        MS_INVALIDATE;
+    }
+}
+
+libc_enum! {
+    /// Usage advice for a [`Mapping`], see `madvise(2)`
+    #[repr(i32)]
+    #[non_exhaustive]
+    #[allow(non_camel_case_types)]
+    pub enum MmapAdvise {
+        /// No special treatment
+        MADV_NORMAL,
+        /// Expect random page references
+        MADV_RANDOM,
+        /// Expect sequential page references
+        MADV_SEQUENTIAL,
+        /// Expect access in the near future
+        MADV_WILLNEED,
+        /// Do not expect access in the near future. Private anonymous pages
+        /// will be zero-filled on the next access
+        MADV_DONTNEED,
+        /// Pages may be freed lazily under memory pressure
+        MADV_FREE,
+        /// Free pages and their backing store
+        MADV_REMOVE,
+        /// Do not make pages available to a child after `fork(2)`
+        MADV_DONTFORK,
+        /// Undo the effect of `MADV_DONTFORK`
+        MADV_DOFORK,
+        /// Enable kernel samepage merging
+        MADV_MERGEABLE,
+        /// Undo the effect of `MADV_MERGEABLE`
+        MADV_UNMERGEABLE,
+        /// Enable transparent huge pages
+        MADV_HUGEPAGE,
+        /// Undo the effect of `MADV_HUGEPAGE`
+        MADV_NOHUGEPAGE,
+        /// Exclude pages from a core dump
+        MADV_DONTDUMP,
+        /// Undo the effect of `MADV_DONTDUMP`
+        MADV_DODUMP,
+        /// Zero-fill pages in a child after `fork(2)`
+        MADV_WIPEONFORK,
+        /// Undo the effect of `MADV_WIPEONFORK`
+        MADV_KEEPONFORK,
+        /// Deactivate pages
+        MADV_COLD,
+        /// Reclaim pages
+        MADV_PAGEOUT,
+    }
+}
+
+/// Owned memory mapping
+///
+/// A [`Mapping`] unmaps its memory region when dropped. The region can be
+/// accessed as a byte slice, provided the [`ProtFlags`] of the mapping
+/// permit it.
+#[derive(Debug)]
+pub struct Mapping {
+    ptr: NonNull<c_void>,
+    len: usize,
+    prot: ProtFlags,
+}
+
+// safety: a `Mapping` owns its memory region, just like a `Vec<u8>`
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Mapping {
+    /// Create an anonymous mapping of `len` bytes
+    pub fn anonymous(len: NonZeroUsize, prot: ProtFlags, flags: MapFlags) -> Result<Mapping> {
+        let ptr = mmap_anonymous(None, len, prot, flags)?;
+
+        Ok(Mapping {
+            ptr,
+            len: len.get(),
+            prot,
+        })
+    }
+
+    /// Map `len` bytes of the file `f`, starting at `offset`
+    ///
+    /// Note that `offset` must be a multiple of the page size.
+    ///
+    /// ### Safety
+    ///
+    /// The slices returned by [`Mapping::as_slice()`] and
+    /// [`Mapping::as_mut_slice()`] assume exclusive access to the memory. For
+    /// a shared mapping, the caller must ensure the file is not modified,
+    /// truncated or mapped writable elsewhere, by this or any other process,
+    /// while such a slice is in use.
+    pub unsafe fn file<F: AsFd>(
+        f: F,
+        offset: off_t,
+        len: NonZeroUsize,
+        prot: ProtFlags,
+        flags: MapFlags,
+    ) -> Result<Mapping> {
+        let ptr = mmap(None, len, prot, flags, f, offset)?;
+
+        Ok(Mapping {
+            ptr,
+            len: len.get(),
+            prot,
+        })
+    }
+
+    /// Take ownership of a memory region returned by [`mmap()`]
+    ///
+    /// ### Safety
+    ///
+    /// `ptr` and `len` must describe a mapped region which is not owned by
+    /// anyone else, and `prot` must match its memory protection.
+    pub unsafe fn from_raw(ptr: NonNull<c_void>, len: NonZeroUsize, prot: ProtFlags) -> Mapping {
+        Mapping {
+            ptr,
+            len: len.get(),
+            prot,
+        }
+    }
+
+    /// Return a pointer to the start of the mapping
+    pub fn as_ptr(&self) -> NonNull<c_void> {
+        self.ptr
+    }
+
+    /// Return the length of the mapping in bytes
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return `true` if the mapping has a length of zero bytes, which is
+    /// never the case
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return the current memory protection of the mapping
+    pub fn prot(&self) -> ProtFlags {
+        self.prot
+    }
+
+    /// Return the mapping as a byte slice, if it is readable
+    pub fn as_slice(&self) -> Option<&[u8]> {
+        self.prot
+            .contains(ProtFlags::PROT_READ)
+            .then(|| unsafe { slice::from_raw_parts(self.ptr.as_ptr() as *const u8, self.len) })
+    }
+
+    /// Return the mapping as a mutable byte slice, if it is writable
+    pub fn as_mut_slice(&mut self) -> Option<&mut [u8]> {
+        self.prot
+            .contains(ProtFlags::PROT_WRITE)
+            .then(|| unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr() as *mut u8, self.len) })
+    }
+
+    /// Change the memory protection of the mapping
+    pub fn protect(&mut self, prot: ProtFlags) -> Result<()> {
+        mprotect(self.ptr, self.len, prot)?;
+        self.prot = prot;
+        Ok(())
+    }
+
+    /// Give `advice` about the expected usage of the mapping
+    ///
+    /// Advice like [`MmapAdvise::MADV_DONTNEED`] may change the contents of
+    /// the mapping, hence this requires exclusive access.
+    pub fn advise(&mut self, advice: MmapAdvise) -> Result<()> {
+        syscall!(madvise(self.ptr.as_ptr(), self.len, advice as c_int)).map(|_| ())
+    }
+
+    /// Lock the mapping into RAM, preventing it from being paged out
+    pub fn lock(&self) -> Result<()> {
+        syscall!(mlock(self.ptr.as_ptr(), self.len)).map(|_| ())
+    }
+
+    /// Unlock the mapping, allowing it to be paged out again
+    pub fn unlock(&self) -> Result<()> {
+        syscall!(munlock(self.ptr.as_ptr(), self.len)).map(|_| ())
+    }
+
+    /// Flush changes of a file-backed mapping back to the file
+    pub fn sync(&self, flags: MsFlags) -> Result<()> {
+        syscall!(msync(self.ptr.as_ptr(), self.len, flags.bits())).map(|_| ())
+    }
+
+    /// Resize the mapping to `len` bytes, moving it to a new address if
+    /// required
+    pub fn remap(&mut self, len: NonZeroUsize) -> Result<()> {
+        let ret =
+            unsafe { libc::mremap(self.ptr.as_ptr(), self.len, len.get(), libc::MREMAP_MAYMOVE) };
+
+        if ret == libc::MAP_FAILED {
+            return Err(Error::Syscall(std::io::Error::last_os_error()));
+        }
+
+        // safety: `libc::mremap` returns a valid non-null pointer or `libc::MAP_FAILED`
+        self.ptr = unsafe { NonNull::new_unchecked(ret) };
+        self.len = len.get();
+
+        Ok(())
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // there is nothing sensible to do about a failing munmap
+        let _ = munmap(self.ptr, self.len);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::OpenOptions,
+        io::{Read, Seek, SeekFrom, Write},
+        num::NonZeroUsize,
+    };
+
+    use anyhow::Result;
+
+    use super::{MapFlags, Mapping, MmapAdvise, MsFlags, ProtFlags};
+
+    fn page_size() -> NonZeroUsize {
+        NonZeroUsize::new(syscall!(sysconf(libc::_SC_PAGE_SIZE)).unwrap() as usize).unwrap()
+    }
+
+    #[test]
+    fn mapping_anonymous() -> Result<()> {
+        let mut map = Mapping::anonymous(
+            page_size(),
+            ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+            MapFlags::MAP_PRIVATE,
+        )?;
+
+        assert_eq!(map.len(), page_size().get());
+        assert!(map.as_slice().unwrap().iter().all(|b| *b == 0));
+
+        map.as_mut_slice().unwrap()[..5].copy_from_slice(b"hello");
+        assert_eq!(&map.as_slice().unwrap()[..5], b"hello");
+
+        map.lock()?;
+        map.unlock()?;
+
+        // private anonymous pages are zero-filled again
+        map.advise(MmapAdvise::MADV_DONTNEED)?;
+        assert_eq!(&map.as_slice().unwrap()[..5], &[0; 5]);
+
+        map.protect(ProtFlags::PROT_READ)?;
+        assert!(map.as_mut_slice().is_none());
+        assert!(map.as_slice().is_some());
+
+        map.protect(ProtFlags::PROT_NONE)?;
+        assert!(map.as_slice().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn mapping_not_writable() -> Result<()> {
+        let mut map = Mapping::anonymous(page_size(), ProtFlags::PROT_READ, MapFlags::MAP_PRIVATE)?;
+
+        assert!(map.as_mut_slice().is_none());
+        assert_eq!(map.as_slice().unwrap()[0], 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn mapping_remap() -> Result<()> {
+        let page = page_size().get();
+
+        let mut map = Mapping::anonymous(
+            page_size(),
+            ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+            MapFlags::MAP_PRIVATE,
+        )?;
+
+        map.as_mut_slice().unwrap()[0] = 42;
+
+        map.remap(NonZeroUsize::new(4 * page).unwrap())?;
+        assert_eq!(map.len(), 4 * page);
+        assert_eq!(map.as_slice().unwrap()[0], 42);
+
+        map.as_mut_slice().unwrap()[4 * page - 1] = 7;
+
+        Ok(())
+    }
+
+    #[test]
+    fn mapping_file() -> Result<()> {
+        let mut file = tempfile::tempfile()?;
+        file.write_all(b"hello file")?;
+
+        // safety: the temporary file is not modified while mapped
+        let map = unsafe {
+            Mapping::file(
+                &file,
+                0,
+                NonZeroUsize::new(10).unwrap(),
+                ProtFlags::PROT_READ,
+                MapFlags::MAP_PRIVATE,
+            )?
+        };
+
+        assert_eq!(map.as_slice(), Some(&b"hello file"[..]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn mapping_file_sync() -> Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+        file.as_file().set_len(page_size().get() as u64)?;
+
+        // safety: the file is only read after the slice is gone
+        let mut map = unsafe {
+            Mapping::file(
+                file.as_file(),
+                0,
+                page_size(),
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                MapFlags::MAP_SHARED,
+            )?
+        };
+
+        map.as_mut_slice().unwrap()[..4].copy_from_slice(b"sync");
+        map.sync(MsFlags::MS_SYNC)?;
+
+        let mut other = OpenOptions::new().read(true).open(file.path())?;
+        let mut buf = [0u8; 4];
+        other.seek(SeekFrom::Start(0))?;
+        other.read_exact(&mut buf)?;
+
+        assert_eq!(&buf, b"sync");
+
+        Ok(())
+    }
+}
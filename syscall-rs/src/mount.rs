@@ -0,0 +1,529 @@
+//!
+//! This file is part of syscall-rs
+//!
+
+use std::{
+    ffi::CString,
+    mem,
+    os::unix::{
+        ffi::OsStrExt,
+        prelude::{AsFd, AsRawFd, BorrowedFd, FromRawFd, RawFd},
+    },
+    path::Path,
+    ptr,
+};
+
+use libc::{c_char, c_int, c_uint};
+
+use crate::{FileDesc, Result, libc_bitflags, libc_enum};
+
+// fd-based mount API, see `include/uapi/linux/mount.h`
+
+const FSOPEN_CLOEXEC: c_uint = 0x1;
+const FSMOUNT_CLOEXEC: c_uint = 0x1;
+
+const FSCONFIG_SET_FLAG: c_uint = 0;
+const FSCONFIG_SET_STRING: c_uint = 1;
+const FSCONFIG_SET_FD: c_uint = 5;
+const FSCONFIG_CMD_CREATE: c_uint = 6;
+
+const MOVE_MOUNT_F_EMPTY_PATH: c_uint = 0x4;
+
+libc_bitflags! {
+    /// Attributes of a mount, see `mount_setattr(2)`
+    pub struct MountAttr: u64 {
+        /// Mount read-only
+        MOUNT_ATTR_RDONLY;
+        /// Ignore set-user-ID and set-group-ID bits
+        MOUNT_ATTR_NOSUID;
+        /// Disallow access to device special files
+        MOUNT_ATTR_NODEV;
+        /// Disallow execution of programs
+        MOUNT_ATTR_NOEXEC;
+        /// Don't update access times
+        MOUNT_ATTR_NOATIME;
+        /// Always update access times
+        MOUNT_ATTR_STRICTATIME;
+        /// Don't update access times of directories
+        MOUNT_ATTR_NODIRATIME;
+        /// Mount is idmapped, see [`MountFd::idmap()`]
+        MOUNT_ATTR_IDMAP;
+        /// Don't follow symbolic links
+        MOUNT_ATTR_NOSYMFOLLOW;
+    }
+}
+
+libc_bitflags! {
+    /// Flags for [`MountFd::open_tree()`]
+    pub struct OpenTreeFlags: c_uint {
+        /// Create a detached copy of the mount, i.e. a bind mount
+        OPEN_TREE_CLONE;
+        /// Close the mount fd on `exec`
+        OPEN_TREE_CLOEXEC;
+        /// Copy the whole mount tree, requires `OPEN_TREE_CLONE`
+        AT_RECURSIVE as c_uint;
+        /// Don't dereference the path if it is a symbolic link
+        AT_SYMLINK_NOFOLLOW as c_uint;
+        /// Don't trigger automounts
+        AT_NO_AUTOMOUNT as c_uint;
+    }
+}
+
+libc_enum! {
+    /// Mount propagation type, see `mount_namespaces(7)`
+    #[repr(u64)]
+    #[non_exhaustive]
+    #[allow(non_camel_case_types)]
+    pub enum Propagation {
+        /// Mount and unmount events don't propagate
+        MS_PRIVATE,
+        /// Events propagate into, but not out of the mount
+        MS_SLAVE,
+        /// Events propagate into and out of the mount
+        MS_SHARED,
+        /// Like `MS_PRIVATE`, and the mount can't be bind mounted
+        MS_UNBINDABLE,
+    }
+}
+
+/// Filesystem context created by [`FsContext::open()`]
+///
+/// A context is configured using `fsconfig(2)`, the filesystem instance is
+/// created using [`FsContext::create()`] and finally turned into a detached
+/// mount using [`FsContext::mount()`].
+#[derive(Debug)]
+pub struct FsContext(FileDesc);
+
+impl FsContext {
+    /// Return a new context for the filesystem type `fstype`, e.g. `tmpfs`
+    pub fn open(fstype: &str) -> Result<FsContext> {
+        let fstype = CString::new(fstype)?;
+
+        let fd = syscall!(syscall(libc::SYS_fsopen, fstype.as_ptr(), FSOPEN_CLOEXEC))?;
+
+        Ok(FsContext(unsafe { FileDesc::from_raw_fd(fd as RawFd) }))
+    }
+
+    /// Set the boolean parameter `key`, e.g. `ro`
+    pub fn set_flag(&self, key: &str) -> Result<()> {
+        let key = CString::new(key)?;
+
+        self.config(FSCONFIG_SET_FLAG, key.as_ptr(), ptr::null(), 0)
+    }
+
+    /// Set the parameter `key` to `value`, e.g. `size` and `1M`
+    pub fn set_string(&self, key: &str, value: &str) -> Result<()> {
+        let key = CString::new(key)?;
+        let value = CString::new(value)?;
+
+        self.config(FSCONFIG_SET_STRING, key.as_ptr(), value.as_ptr(), 0)
+    }
+
+    /// Set the parameter `key` to the file descriptor `fd`
+    pub fn set_fd<Fd: AsFd>(&self, key: &str, fd: Fd) -> Result<()> {
+        let key = CString::new(key)?;
+
+        self.config(
+            FSCONFIG_SET_FD,
+            key.as_ptr(),
+            ptr::null(),
+            fd.as_fd().as_raw_fd(),
+        )
+    }
+
+    /// Create the filesystem instance
+    pub fn create(&self) -> Result<()> {
+        self.config(FSCONFIG_CMD_CREATE, ptr::null(), ptr::null(), 0)
+    }
+
+    /// Create a detached mount of the filesystem instance with the mount
+    /// attributes `attrs`
+    pub fn mount(&self, attrs: MountAttr) -> Result<MountFd> {
+        let fd = syscall!(syscall(
+            libc::SYS_fsmount,
+            self.0.as_raw_fd(),
+            FSMOUNT_CLOEXEC,
+            attrs.bits() as c_uint
+        ))?;
+
+        Ok(MountFd(unsafe { FileDesc::from_raw_fd(fd as RawFd) }))
+    }
+
+    fn config(
+        &self,
+        cmd: c_uint,
+        key: *const c_char,
+        value: *const c_char,
+        aux: c_int,
+    ) -> Result<()> {
+        syscall!(syscall(
+            libc::SYS_fsconfig,
+            self.0.as_raw_fd(),
+            cmd,
+            key,
+            value,
+            aux
+        ))
+        .map(|_| ())
+    }
+}
+
+impl AsRawFd for FsContext {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl AsFd for FsContext {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+/// File descriptor referring to a mount
+///
+/// A [`MountFd`] either refers to an attached mount, or to a detached mount
+/// created by [`FsContext::mount()`] or by [`MountFd::open_tree()`] using
+/// [`OpenTreeFlags::OPEN_TREE_CLONE`]. A detached mount is invisible until it
+/// is attached using [`MountFd::move_to()`], and it is unmounted once the
+/// last reference to it is closed.
+#[derive(Debug)]
+pub struct MountFd(FileDesc);
+
+impl MountFd {
+    /// Open the mount at `path`
+    ///
+    /// With [`OpenTreeFlags::OPEN_TREE_CLONE`], return a detached bind mount
+    /// of `path` instead.
+    pub fn open_tree<P: AsRef<Path>>(path: P, flags: OpenTreeFlags) -> Result<MountFd> {
+        let path = cstring(path.as_ref())?;
+
+        let flags = flags | OpenTreeFlags::OPEN_TREE_CLOEXEC;
+
+        let fd = syscall!(syscall(
+            libc::SYS_open_tree,
+            libc::AT_FDCWD,
+            path.as_ptr(),
+            flags.bits()
+        ))?;
+
+        Ok(MountFd(unsafe { FileDesc::from_raw_fd(fd as RawFd) }))
+    }
+
+    /// Attach the mount at `path`, or move it there if it is attached
+    /// already
+    pub fn move_to<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = cstring(path.as_ref())?;
+
+        syscall!(syscall(
+            libc::SYS_move_mount,
+            self.0.as_raw_fd(),
+            c"".as_ptr(),
+            libc::AT_FDCWD,
+            path.as_ptr(),
+            MOVE_MOUNT_F_EMPTY_PATH
+        ))
+        .map(|_| ())
+    }
+
+    /// Set the attributes `set` and clear the attributes `clear` of the
+    /// mount, and of all mounts below it if `recursive` is `true`
+    ///
+    /// Access time attributes are mutually exclusive, to change them
+    /// `clear` must contain [`MountAttr::MOUNT_ATTR_NOATIME`] and
+    /// [`MountAttr::MOUNT_ATTR_STRICTATIME`].
+    pub fn set_attr(&self, set: MountAttr, clear: MountAttr, recursive: bool) -> Result<()> {
+        let mut attr: libc::mount_attr = unsafe { mem::zeroed() };
+        attr.attr_set = set.bits();
+        attr.attr_clr = clear.bits();
+
+        self.setattr(&attr, recursive)
+    }
+
+    /// Change the propagation type of the mount, and of all mounts below it
+    /// if `recursive` is `true`
+    pub fn set_propagation(&self, propagation: Propagation, recursive: bool) -> Result<()> {
+        let mut attr: libc::mount_attr = unsafe { mem::zeroed() };
+        attr.propagation = propagation as u64;
+
+        self.setattr(&attr, recursive)
+    }
+
+    /// Idmap the mount using the ID mappings of the user namespace `userns`,
+    /// see [`crate::open_namespace()`]
+    ///
+    /// Only detached mounts, which have never been attached before, can be
+    /// idmapped.
+    pub fn idmap<Fd: AsFd>(&self, userns: Fd, recursive: bool) -> Result<()> {
+        let mut attr: libc::mount_attr = unsafe { mem::zeroed() };
+        attr.attr_set = MountAttr::MOUNT_ATTR_IDMAP.bits();
+        attr.userns_fd = userns.as_fd().as_raw_fd() as u64;
+
+        self.setattr(&attr, recursive)
+    }
+
+    fn setattr(&self, attr: &libc::mount_attr, recursive: bool) -> Result<()> {
+        let mut flags = libc::AT_EMPTY_PATH;
+        if recursive {
+            flags |= libc::AT_RECURSIVE;
+        }
+
+        syscall!(syscall(
+            libc::SYS_mount_setattr,
+            self.0.as_raw_fd(),
+            c"".as_ptr(),
+            flags as c_uint,
+            attr as *const libc::mount_attr,
+            mem::size_of::<libc::mount_attr>()
+        ))
+        .map(|_| ())
+    }
+}
+
+impl AsRawFd for MountFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl AsFd for MountFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+/// Make `new_root` the root mount of the calling process and move the old
+/// root mount to `put_old`, see `pivot_root(2)`
+///
+/// `new_root` must be a mount point, and `put_old` must be at or below
+/// `new_root`.
+pub fn pivot_root<P1, P2>(new_root: P1, put_old: P2) -> Result<()>
+where
+    P1: AsRef<Path>,
+    P2: AsRef<Path>,
+{
+    let new_root = cstring(new_root.as_ref())?;
+    let put_old = cstring(put_old.as_ref())?;
+
+    syscall!(syscall(
+        libc::SYS_pivot_root,
+        new_root.as_ptr(),
+        put_old.as_ptr()
+    ))
+    .map(|_| ())
+}
+
+fn cstring(path: &Path) -> Result<CString> {
+    Ok(CString::new(path.as_os_str().as_bytes())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::ErrorKind, os::unix::fs::MetadataExt, path::Path, process::exit};
+
+    use anyhow::{Result, ensure};
+
+    use crate::{
+        CloneArgs, Cloned, FsContext, IdMap, MountAttr, MountFd, Namespaces, OpenTreeFlags,
+        Propagation, WaitStatus, clone3, open_namespace, pivot_root, test_util::in_child, unshare,
+        wait, write_gid_map, write_setgroups, write_uid_map,
+    };
+
+    /// Run `f` in a forked child, as root of a new user and mount namespace
+    ///
+    /// This does not require any privileges.
+    fn in_sandbox(f: impl FnOnce() -> Result<()>) -> Result<()> {
+        in_child(|| {
+            let (uid, gid) = unsafe { (libc::geteuid(), libc::getegid()) };
+
+            unshare(Namespaces::CLONE_NEWUSER | Namespaces::CLONE_NEWNS)?;
+
+            write_setgroups(None, false)?;
+            write_gid_map(None, &[IdMap::single(0, gid)])?;
+            write_uid_map(None, &[IdMap::single(0, uid)])?;
+
+            f()
+        })
+    }
+
+    fn statvfs(path: &Path) -> Result<u64> {
+        let path = super::cstring(path)?;
+        let mut buf = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+
+        syscall!(statvfs(path.as_ptr(), buf.as_mut_ptr()))?;
+
+        Ok(unsafe { buf.assume_init() }.f_flag)
+    }
+
+    #[test]
+    fn mount_fsopen() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        in_sandbox(|| {
+            let fs = FsContext::open("tmpfs")?;
+            fs.set_string("size", "1M")?;
+            fs.set_string("mode", "0755")?;
+            ensure!(fs.set_string("no-such-option", "1").is_err());
+            fs.create()?;
+
+            let mnt = fs.mount(MountAttr::MOUNT_ATTR_NOSUID | MountAttr::MOUNT_ATTR_NODEV)?;
+            mnt.move_to(dir.path())?;
+
+            fs::write(dir.path().join("file"), b"tmpfs")?;
+
+            let flags = statvfs(dir.path())?;
+            ensure!(flags & libc::ST_NOSUID != 0);
+            ensure!(flags & libc::ST_NODEV != 0);
+            ensure!(flags & libc::ST_RDONLY == 0);
+
+            // remount read-only
+            mnt.set_attr(MountAttr::MOUNT_ATTR_RDONLY, MountAttr::empty(), false)?;
+            ensure!(statvfs(dir.path())? & libc::ST_RDONLY != 0);
+
+            let err = fs::write(dir.path().join("other"), b"ro").unwrap_err();
+            ensure!(err.raw_os_error() == Some(libc::EROFS));
+
+            Ok(())
+        })?;
+
+        // the mount did not leak out of the sandbox
+        assert!(!dir.path().join("file").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn mount_open_tree() -> Result<()> {
+        let src = tempfile::tempdir()?;
+        let dst = tempfile::tempdir()?;
+
+        fs::write(src.path().join("file"), b"bind")?;
+
+        in_sandbox(|| {
+            let tree = MountFd::open_tree(
+                src.path(),
+                OpenTreeFlags::OPEN_TREE_CLONE | OpenTreeFlags::AT_RECURSIVE,
+            )?;
+
+            // a detached mount is not visible yet
+            ensure!(!dst.path().join("file").exists());
+
+            tree.set_attr(
+                MountAttr::MOUNT_ATTR_RDONLY | MountAttr::MOUNT_ATTR_NOSUID,
+                MountAttr::empty(),
+                true,
+            )?;
+            tree.move_to(dst.path())?;
+
+            ensure!(fs::read(dst.path().join("file"))? == b"bind");
+
+            let err = fs::write(dst.path().join("file"), b"rw").unwrap_err();
+            ensure!(err.raw_os_error() == Some(libc::EROFS));
+
+            // the source is still writable
+            fs::write(src.path().join("file"), b"rw")?;
+            ensure!(fs::read(dst.path().join("file"))? == b"rw");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn mount_idmap() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mnt = tempfile::tempdir()?;
+
+        fs::write(dir.path().join("file"), b"idmap")?;
+
+        // mapping arbitrary IDs requires privileges in the parent namespace,
+        // so the user namespace is created by a child of the test process
+        let (rx, tx) = crate::pipe2(crate::OFlags::O_CLOEXEC)?;
+
+        let mut args = CloneArgs::new();
+        args.namespaces(Namespaces::CLONE_NEWUSER);
+
+        let holder = match unsafe { clone3(&args)? } {
+            Cloned::Parent { pid, .. } => pid,
+            Cloned::Child => {
+                // wait until the parent is done
+                drop(tx);
+                let _ = rx.read(&mut [0u8]);
+                exit(0)
+            }
+        };
+        drop(rx);
+
+        // the file is owned by the effective IDs, map them to other IDs
+        let (uid, gid) = unsafe { (libc::geteuid(), libc::getegid()) };
+        let (mapped_uid, mapped_gid) = (uid ^ 1000, gid ^ 1000);
+
+        let res = (|| -> Result<()> {
+            match write_uid_map(holder, &[IdMap::single(uid, mapped_uid)]) {
+                // no CAP_SETUID
+                Err(crate::Error::Syscall(err)) if err.raw_os_error() == Some(libc::EPERM) => {
+                    return Ok(());
+                }
+                res => res?,
+            }
+            write_gid_map(holder, &[IdMap::single(gid, mapped_gid)])?;
+
+            let userns = open_namespace(holder, Namespaces::CLONE_NEWUSER)?;
+
+            in_child(|| {
+                unshare(Namespaces::CLONE_NEWNS)?;
+                MountFd::open_tree("/", OpenTreeFlags::empty())?
+                    .set_propagation(Propagation::MS_PRIVATE, true)?;
+
+                let tree = MountFd::open_tree(dir.path(), OpenTreeFlags::OPEN_TREE_CLONE)?;
+
+                match tree.idmap(&userns, false) {
+                    Err(crate::Error::Syscall(err)) if err.kind() == ErrorKind::InvalidInput => {
+                        // the filesystem does not support idmapped mounts
+                        return Ok(());
+                    }
+                    res => res?,
+                }
+
+                tree.move_to(mnt.path())?;
+
+                // files show up as owned by the mapped IDs
+                let meta = fs::metadata(mnt.path().join("file"))?;
+                ensure!(meta.uid() == mapped_uid && meta.gid() == mapped_gid);
+                ensure!(fs::metadata(dir.path().join("file"))?.uid() == uid);
+
+                Ok(())
+            })
+        })();
+
+        drop(tx);
+        assert_eq!(wait(holder)?, WaitStatus::Exited(holder, 0));
+
+        res
+    }
+
+    #[test]
+    fn mount_pivot_root() -> Result<()> {
+        let root = tempfile::tempdir()?;
+
+        in_sandbox(|| {
+            let fs = FsContext::open("tmpfs")?;
+            fs.create()?;
+            fs.mount(MountAttr::empty())?.move_to(root.path())?;
+
+            fs::create_dir(root.path().join("old"))?;
+            fs::write(root.path().join("marker"), b"new root")?;
+
+            pivot_root(root.path(), root.path().join("old"))?;
+            std::env::set_current_dir("/")?;
+
+            ensure!(fs::read("/marker")? == b"new root");
+            ensure!(
+                Path::new("/old")
+                    .join(root.path().strip_prefix("/")?)
+                    .exists()
+            );
+
+            Ok(())
+        })
+    }
+}
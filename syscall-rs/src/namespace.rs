@@ -0,0 +1,504 @@
+//!
+//! This file is part of syscall-rs
+//!
+
+use std::{
+    fs,
+    io::Write,
+    mem,
+    os::unix::prelude::{AsFd, AsRawFd, FromRawFd, OwnedFd, RawFd},
+};
+
+use bitflags::bitflags;
+use libc::c_int;
+
+use crate::{Error, FileDesc, PidFd, Result, Signal, libc_bitflags};
+
+libc_bitflags! {
+    /// Linux namespace types
+    pub struct Namespaces: c_int {
+        /// Cgroup namespace
+        CLONE_NEWCGROUP;
+        /// IPC namespace, i.e. System V IPC and POSIX message queues
+        CLONE_NEWIPC;
+        /// Network namespace
+        CLONE_NEWNET;
+        /// Mount namespace
+        CLONE_NEWNS;
+        /// PID namespace
+        CLONE_NEWPID;
+        /// Time namespace, i.e. `CLOCK_MONOTONIC` and `CLOCK_BOOTTIME` offsets
+        CLONE_NEWTIME;
+        /// User namespace
+        CLONE_NEWUSER;
+        /// UTS namespace, i.e. hostname and domain name
+        CLONE_NEWUTS;
+    }
+}
+
+impl Namespaces {
+    /// Return the name of the namespace in `/proc/<pid>/ns`, if `self` is a
+    /// single namespace type
+    pub fn name(&self) -> Option<&'static str> {
+        Some(match *self {
+            Namespaces::CLONE_NEWCGROUP => "cgroup",
+            Namespaces::CLONE_NEWIPC => "ipc",
+            Namespaces::CLONE_NEWNET => "net",
+            Namespaces::CLONE_NEWNS => "mnt",
+            Namespaces::CLONE_NEWPID => "pid",
+            Namespaces::CLONE_NEWTIME => "time",
+            Namespaces::CLONE_NEWUSER => "user",
+            Namespaces::CLONE_NEWUTS => "uts",
+            _ => return None,
+        })
+    }
+}
+
+bitflags! {
+    /// Flags for [`CloneArgs`], in addition to the namespaces to create
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct CloneFlags: u64 {
+        /// The child shares the file descriptor table with the parent
+        const CLONE_FILES = libc::CLONE_FILES as u64;
+        /// The child shares filesystem information, e.g. the working
+        /// directory, with the parent
+        const CLONE_FS = libc::CLONE_FS as u64;
+        /// The child becomes a sibling of the caller
+        const CLONE_PARENT = libc::CLONE_PARENT as u64;
+        /// The child shares System V semaphore adjustments with the parent
+        const CLONE_SYSVSEM = libc::CLONE_SYSVSEM as u64;
+        /// The parent is suspended until the child calls `execve(2)` or
+        /// exits
+        const CLONE_VFORK = libc::CLONE_VFORK as u64;
+        /// Reset all signal handlers of the child to their default
+        const CLONE_CLEAR_SIGHAND = 0x100000000;
+    }
+}
+
+// libc defines this as an overflowing `c_int`
+const CLONE_INTO_CGROUP: u64 = 0x200000000;
+
+/// Arguments for [`clone3()`]
+///
+/// Without further configuration, [`clone3()`] behaves like `fork(2)`.
+#[derive(Debug)]
+pub struct CloneArgs {
+    flags: CloneFlags,
+    namespaces: Namespaces,
+    exit_signal: Option<Signal>,
+    pidfd: bool,
+    cgroup: Option<RawFd>,
+}
+
+impl Default for CloneArgs {
+    fn default() -> Self {
+        CloneArgs::new()
+    }
+}
+
+impl CloneArgs {
+    /// Return new [`CloneArgs`], the child will signal its termination using
+    /// `SIGCHLD`
+    pub fn new() -> CloneArgs {
+        CloneArgs {
+            flags: CloneFlags::empty(),
+            namespaces: Namespaces::empty(),
+            exit_signal: Some(Signal::SIGCHLD),
+            pidfd: false,
+            cgroup: None,
+        }
+    }
+
+    /// Set additional clone flags
+    pub fn flags(&mut self, flags: CloneFlags) -> &mut CloneArgs {
+        self.flags = flags;
+        self
+    }
+
+    /// Create the child in new `namespaces`
+    pub fn namespaces(&mut self, namespaces: Namespaces) -> &mut CloneArgs {
+        self.namespaces = namespaces;
+        self
+    }
+
+    /// Signal sent to the parent when the child terminates, or `None` for no
+    /// signal at all
+    pub fn exit_signal(&mut self, signal: Option<Signal>) -> &mut CloneArgs {
+        self.exit_signal = signal;
+        self
+    }
+
+    /// Return a [`PidFd`] for the child, i.e. `CLONE_PIDFD`
+    pub fn pidfd(&mut self, pidfd: bool) -> &mut CloneArgs {
+        self.pidfd = pidfd;
+        self
+    }
+
+    /// Place the child in the cgroup v2 directory `cgroup` instead of the
+    /// cgroup of the parent, i.e. `CLONE_INTO_CGROUP`
+    ///
+    /// `cgroup` must stay open until [`clone3()`] has been called.
+    pub fn cgroup<Fd: AsFd>(&mut self, cgroup: Fd) -> &mut CloneArgs {
+        self.cgroup = Some(cgroup.as_fd().as_raw_fd());
+        self
+    }
+}
+
+/// Result of [`clone3()`]
+#[derive(Debug)]
+pub enum Cloned {
+    /// Returned in the parent
+    Parent {
+        /// Process ID of the child
+        pid: libc::pid_t,
+        /// [`PidFd`] of the child, if requested using [`CloneArgs::pidfd()`]
+        pidfd: Option<PidFd>,
+    },
+    /// Returned in the child
+    Child,
+}
+
+/// Create a child process, see `clone3(2)`
+///
+/// ### Safety
+///
+/// Like with `fork(2)`, the child of a multi-threaded parent may only call
+/// async-signal-safe functions until it calls `execve(2)`.
+pub unsafe fn clone3(args: &CloneArgs) -> Result<Cloned> {
+    let mut pidfd: c_int = -1;
+
+    let mut raw: libc::clone_args = unsafe { mem::zeroed() };
+    raw.flags = args.flags.bits() | args.namespaces.bits() as u64;
+    raw.exit_signal = args.exit_signal.map_or(0, |s| s.as_raw() as u64);
+
+    if args.pidfd {
+        raw.flags |= libc::CLONE_PIDFD as u64;
+        raw.pidfd = &mut pidfd as *mut c_int as u64;
+    }
+
+    if let Some(cgroup) = args.cgroup {
+        raw.flags |= CLONE_INTO_CGROUP;
+        raw.cgroup = cgroup as u64;
+    }
+
+    let pid = syscall!(syscall(
+        libc::SYS_clone3,
+        &mut raw as *mut libc::clone_args,
+        mem::size_of::<libc::clone_args>()
+    ))? as libc::pid_t;
+
+    if pid == 0 {
+        return Ok(Cloned::Child);
+    }
+
+    let pidfd = args
+        .pidfd
+        .then(|| PidFd::from_raw(unsafe { FileDesc::from_raw_fd(pidfd) }, pid));
+
+    Ok(Cloned::Parent { pid, pidfd })
+}
+
+/// Move the calling thread into new `namespaces`
+///
+/// Note that creating a new user namespace fails with `EINVAL` if the caller
+/// is multi-threaded.
+pub fn unshare(namespaces: Namespaces) -> Result<()> {
+    syscall!(unshare(namespaces.bits())).map(|_| ())
+}
+
+/// Move the calling thread into the namespace referred to by `fd`
+///
+/// `fd` is usually obtained using [`open_namespace()`]. If `namespace` is
+/// not empty, the call fails unless `fd` refers to a namespace of that type.
+pub fn setns<Fd: AsFd>(fd: Fd, namespace: Namespaces) -> Result<()> {
+    syscall!(setns(fd.as_fd().as_raw_fd(), namespace.bits())).map(|_| ())
+}
+
+/// Open the `namespace` of process `pid`, or of the calling process if
+/// `pid` is `None`
+///
+/// `namespace` must be a single namespace type.
+pub fn open_namespace<P>(pid: P, namespace: Namespaces) -> Result<FileDesc>
+where
+    P: Into<Option<libc::pid_t>>,
+{
+    let name = namespace
+        .name()
+        .ok_or_else(|| Error::from("not a single namespace type"))?;
+
+    let path = proc_path(pid.into(), &format!("ns/{}", name));
+
+    Ok(OwnedFd::from(fs::File::open(path)?).into())
+}
+
+/// Single ID range of a user namespace, see `user_namespaces(7)`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdMap {
+    /// First ID inside the namespace
+    pub inside: u32,
+    /// First ID in the parent namespace
+    pub outside: u32,
+    /// Number of IDs
+    pub count: u32,
+}
+
+impl IdMap {
+    /// Map the single ID `outside` to `inside`
+    pub fn single(inside: u32, outside: u32) -> IdMap {
+        IdMap {
+            inside,
+            outside,
+            count: 1,
+        }
+    }
+}
+
+/// Write the UID map of the user namespace of process `pid`, or of the
+/// calling process if `pid` is `None`
+///
+/// A map can only be written once. Without `CAP_SETUID` in the parent
+/// namespace, the map must consist of a single range of length one for the
+/// effective UID of the writer.
+pub fn write_uid_map<P>(pid: P, map: &[IdMap]) -> Result<()>
+where
+    P: Into<Option<libc::pid_t>>,
+{
+    write_id_map(pid.into(), "uid_map", map)
+}
+
+/// Write the GID map of the user namespace of process `pid`, or of the
+/// calling process if `pid` is `None`
+///
+/// Without `CAP_SETGID` in the parent namespace, `setgroups(2)` must be
+/// denied using [`write_setgroups()`] first.
+pub fn write_gid_map<P>(pid: P, map: &[IdMap]) -> Result<()>
+where
+    P: Into<Option<libc::pid_t>>,
+{
+    write_id_map(pid.into(), "gid_map", map)
+}
+
+/// Allow or deny `setgroups(2)` in the user namespace of process `pid`, or of
+/// the calling process if `pid` is `None`
+pub fn write_setgroups<P>(pid: P, allow: bool) -> Result<()>
+where
+    P: Into<Option<libc::pid_t>>,
+{
+    let value = if allow { "allow" } else { "deny" };
+
+    Ok(fs::write(proc_path(pid.into(), "setgroups"), value)?)
+}
+
+fn write_id_map(pid: Option<libc::pid_t>, file: &str, map: &[IdMap]) -> Result<()> {
+    let map = map
+        .iter()
+        .map(|m| format!("{} {} {}\n", m.inside, m.outside, m.count))
+        .collect::<String>();
+
+    // the map must be written using a single write(2)
+    let mut f = fs::OpenOptions::new()
+        .write(true)
+        .open(proc_path(pid, file))?;
+
+    if f.write(map.as_bytes())? != map.len() {
+        return Err("short write of id map".into());
+    }
+
+    Ok(())
+}
+
+fn proc_path(pid: Option<libc::pid_t>, file: &str) -> String {
+    match pid {
+        Some(pid) => format!("/proc/{}/{}", pid, file),
+        None => format!("/proc/self/{}", file),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::CStr,
+        fs,
+        path::{Path, PathBuf},
+        process::exit,
+    };
+
+    use anyhow::{Result, ensure};
+
+    use crate::{
+        CloneArgs, Cloned, Error, IdMap, Namespaces, WaitStatus, cgroup_mountpoint, clone3,
+        open_namespace, setns, test_util::in_child, unshare, wait, write_gid_map, write_setgroups,
+        write_uid_map,
+    };
+
+    fn ns_id(name: &str) -> Result<PathBuf> {
+        Ok(fs::read_link(Path::new("/proc/self/ns").join(name))?)
+    }
+
+    fn hostname() -> Result<String> {
+        let mut buf = [0u8; 256];
+        syscall!(gethostname(
+            buf.as_mut_ptr() as *mut libc::c_char,
+            buf.len()
+        ))?;
+        Ok(CStr::from_bytes_until_nul(&buf)?.to_str()?.to_string())
+    }
+
+    #[test]
+    fn namespace_names() {
+        assert_eq!(Namespaces::CLONE_NEWNS.name(), Some("mnt"));
+        assert_eq!(Namespaces::CLONE_NEWUSER.name(), Some("user"));
+        assert_eq!(
+            (Namespaces::CLONE_NEWNET | Namespaces::CLONE_NEWUTS).name(),
+            None
+        );
+        assert!(open_namespace(None, Namespaces::empty()).is_err());
+    }
+
+    #[test]
+    fn namespace_user() -> Result<()> {
+        in_child(|| {
+            let outer = ns_id("user")?;
+            let (uid, gid) = unsafe { (libc::geteuid(), libc::getegid()) };
+
+            unshare(Namespaces::CLONE_NEWUSER)?;
+            ensure!(ns_id("user")? != outer);
+
+            write_setgroups(None, false)?;
+            write_gid_map(None, &[IdMap::single(0, gid)])?;
+            write_uid_map(None, &[IdMap::single(0, uid)])?;
+
+            ensure!(unsafe { libc::getuid() } == 0);
+            ensure!(unsafe { libc::getgid() } == 0);
+            ensure!(fs::read_to_string("/proc/self/setgroups")?.trim() == "deny");
+            ensure!(
+                fs::read_to_string("/proc/self/uid_map")?
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    == ["0", &uid.to_string(), "1"]
+            );
+
+            // maps can only be written once
+            ensure!(write_uid_map(None, &[IdMap::single(0, uid)]).is_err());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn namespace_setns() -> Result<()> {
+        let name = hostname()?;
+
+        // a child holds a new user and UTS namespace, which we own
+        let (rx, tx) = crate::pipe2(crate::OFlags::O_CLOEXEC)?;
+
+        let mut args = CloneArgs::new();
+        args.namespaces(Namespaces::CLONE_NEWUSER | Namespaces::CLONE_NEWUTS);
+
+        let holder = match unsafe { clone3(&args) } {
+            Ok(Cloned::Parent { pid, .. }) => pid,
+            Ok(Cloned::Child) => {
+                // wait until the parent is done
+                drop(tx);
+                let _ = rx.read(&mut [0u8]);
+                exit(0)
+            }
+            // user namespaces are restricted
+            Err(Error::Syscall(err)) if err.raw_os_error() == Some(libc::EPERM) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        drop(rx);
+
+        let res = in_child(|| {
+            let user = open_namespace(holder, Namespaces::CLONE_NEWUSER)?;
+            let uts = open_namespace(holder, Namespaces::CLONE_NEWUTS)?;
+
+            // the namespace type must match
+            ensure!(setns(&uts, Namespaces::CLONE_NEWNET).is_err());
+
+            setns(&user, Namespaces::CLONE_NEWUSER)?;
+            setns(&uts, Namespaces::CLONE_NEWUTS)?;
+            ensure!(ns_id("uts")? == fs::read_link(format!("/proc/{holder}/ns/uts"))?);
+
+            let new = b"sandbox";
+            syscall!(sethostname(new.as_ptr() as *const libc::c_char, new.len()))?;
+            ensure!(hostname()? == "sandbox");
+
+            Ok(())
+        });
+
+        drop(tx);
+        assert_eq!(wait(holder)?, WaitStatus::Exited(holder, 0));
+
+        res?;
+
+        // our own namespace is left untouched
+        ensure!(hostname()? == name);
+
+        Ok(())
+    }
+
+    #[test]
+    fn namespace_clone3() -> Result<()> {
+        // a new user namespace allows this without privileges
+        let mut args = CloneArgs::new();
+        args.namespaces(
+            Namespaces::CLONE_NEWUSER | Namespaces::CLONE_NEWPID | Namespaces::CLONE_NEWUTS,
+        )
+        .pidfd(true);
+
+        let cloned = match unsafe { clone3(&args) } {
+            Ok(cloned) => cloned,
+            // user namespaces are restricted
+            Err(Error::Syscall(err)) if err.raw_os_error() == Some(libc::EPERM) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+
+        match cloned {
+            Cloned::Parent { pid, pidfd } => {
+                let pidfd = pidfd.unwrap();
+                assert_eq!(pidfd.pid(), pid);
+                assert_eq!(pidfd.wait()?, WaitStatus::Exited(pid, 0));
+            }
+            // the child is the init process of the new PID namespace
+            Cloned::Child => exit(if unsafe { libc::getpid() } == 1 { 0 } else { 1 }),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn namespace_clone3_cgroup() -> Result<()> {
+        let Ok(root) = cgroup_mountpoint() else {
+            return Ok(());
+        };
+
+        let cgroup = root.join(format!("syscall-rs-{}", std::process::id()));
+
+        match fs::create_dir(&cgroup) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => return Ok(()),
+            Err(err) => return Err(err.into()),
+        }
+        let dir = fs::File::open(&cgroup)?;
+
+        let mut args = CloneArgs::new();
+        args.cgroup(&dir);
+
+        match unsafe { clone3(&args)? } {
+            Cloned::Parent { pid, pidfd } => {
+                assert!(pidfd.is_none());
+                assert_eq!(wait(pid)?, WaitStatus::Exited(pid, 0));
+            }
+            Cloned::Child => {
+                let procs = fs::read_to_string("/proc/self/cgroup").unwrap_or_default();
+                let expected = format!("0::/syscall-rs-{}\n", unsafe { libc::getppid() });
+                exit(if procs.ends_with(&expected) { 0 } else { 1 })
+            }
+        }
+
+        fs::remove_dir(&cgroup)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,230 @@
+//!
+//! This file is part of syscall-rs
+//!
+
+use std::{
+    os::unix::prelude::{AsRawFd, FromRawFd, RawFd},
+    ptr,
+};
+
+use mio::{Interest, Registry, Token, event, unix::SourceFd};
+
+use crate::{Error, FileDesc, Result, Signal, WaitId, WaitOptions, WaitStatus, waitid};
+
+/// File descriptor referring to a process.
+///
+/// Unlike a raw `pid_t`, a [`PidFd`] is immune to PID reuse: it will always
+/// refer to the very process it has been opened for. A [`PidFd`] becomes
+/// readable once the process terminates and can therefore be registered with
+/// a [`mio::Poll`].
+#[derive(Debug)]
+pub struct PidFd {
+    fd: FileDesc,
+    pid: libc::pid_t,
+}
+
+impl PidFd {
+    /// Return a [`PidFd`] for the process with ID `pid`.
+    ///
+    /// If `nonblocking` is `true`, [`PidFd::wait()`] will fail with `EAGAIN`
+    /// instead of blocking while the process is still running.
+    pub fn open(pid: libc::pid_t, nonblocking: bool) -> Result<PidFd> {
+        let flags = if nonblocking { libc::PIDFD_NONBLOCK } else { 0 };
+
+        let fd = syscall!(syscall(libc::SYS_pidfd_open, pid, flags))?;
+
+        Ok(PidFd {
+            fd: unsafe { FileDesc::from_raw_fd(fd as RawFd) },
+            pid,
+        })
+    }
+
+    /// Return a [`PidFd`] for a pidfd returned by the kernel, e.g. by
+    /// `clone3(2)`
+    pub(crate) fn from_raw(fd: FileDesc, pid: libc::pid_t) -> PidFd {
+        PidFd { fd, pid }
+    }
+
+    /// Return the process ID this [`PidFd`] has been opened for
+    pub fn pid(&self) -> libc::pid_t {
+        self.pid
+    }
+
+    /// Send `signal` to the process
+    pub fn send_signal(&self, signal: Signal) -> Result<()> {
+        syscall!(syscall(
+            libc::SYS_pidfd_send_signal,
+            self.fd.as_raw_fd(),
+            signal.as_raw(),
+            ptr::null::<libc::siginfo_t>(),
+            0
+        ))
+        .map(|_| ())
+    }
+
+    /// Wait for the process to terminate and return its [`WaitStatus`].
+    ///
+    /// Note that this only works for child processes of the caller, and it
+    /// will **block** unless the [`PidFd`] has been opened as nonblocking.
+    pub fn wait(&self) -> Result<WaitStatus> {
+        let siginfo = waitid(WaitId::PidFd(self.fd.as_raw_fd()), WaitOptions::WEXITED)?;
+
+        match siginfo {
+            Some(siginfo) => WaitStatus::from_siginfo(&siginfo),
+            None => Err(Error::Syscall(std::io::Error::from_raw_os_error(
+                libc::EAGAIN,
+            ))),
+        }
+    }
+}
+
+impl AsRawFd for PidFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl event::Source for PidFd {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> std::io::Result<()> {
+        SourceFd(&self.fd.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> std::io::Result<()> {
+        SourceFd(&self.fd.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> std::io::Result<()> {
+        SourceFd(&self.fd.as_raw_fd()).deregister(registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{process::exit, time::Duration};
+
+    use anyhow::Result;
+    use mio::{Events, Interest, Poll, Token};
+
+    use crate::{PidFd, Signal, WaitStatus, wait};
+
+    fn sleeper() -> Result<libc::pid_t> {
+        Ok(match syscall!(fork())? {
+            // parent
+            pid if pid != 0 => pid,
+            // child
+            _ => loop {
+                std::thread::sleep(Duration::from_millis(5));
+            },
+        })
+    }
+
+    #[test]
+    fn pidfd_wait_exit() -> Result<()> {
+        let child = match syscall!(fork())? {
+            // parent
+            pid if pid != 0 => pid,
+            // child
+            _ => {
+                exit(42);
+            }
+        };
+
+        let pidfd = PidFd::open(child, false)?;
+
+        assert_eq!(pidfd.pid(), child);
+        assert_eq!(pidfd.wait()?, WaitStatus::Exited(child, 42));
+
+        Ok(())
+    }
+
+    #[test]
+    fn pidfd_send_signal() -> Result<()> {
+        let child = sleeper()?;
+
+        let pidfd = PidFd::open(child, false)?;
+
+        pidfd.send_signal(Signal::SIGKILL)?;
+
+        assert_eq!(
+            pidfd.wait()?,
+            WaitStatus::Signaled(child, Signal::SIGKILL, false)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn pidfd_nonblocking() -> Result<()> {
+        let child = sleeper()?;
+
+        let pidfd = PidFd::open(child, true)?;
+
+        assert_eq!(
+            format!("{}", pidfd.wait().err().unwrap()),
+            "System call error: Resource temporarily unavailable (os error 11)"
+        );
+
+        pidfd.send_signal(Signal::SIGKILL)?;
+
+        assert_eq!(
+            wait(child)?,
+            WaitStatus::Signaled(child, Signal::SIGKILL, false)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn pidfd_poll() -> Result<()> {
+        const CHILD: Token = Token(7);
+
+        let child = sleeper()?;
+
+        let mut pidfd = PidFd::open(child, true)?;
+
+        let mut poll = Poll::new()?;
+        let mut events = Events::with_capacity(1);
+
+        poll.registry()
+            .register(&mut pidfd, CHILD, Interest::READABLE)?;
+
+        // child is still running
+        poll.poll(&mut events, Some(Duration::from_millis(10)))?;
+        assert!(events.is_empty());
+
+        pidfd.send_signal(Signal::SIGTERM)?;
+
+        poll.poll(&mut events, Some(Duration::from_secs(5)))?;
+
+        let evt = events.iter().next().unwrap();
+        assert_eq!(evt.token(), CHILD);
+        assert!(evt.is_readable());
+
+        assert_eq!(
+            pidfd.wait()?,
+            WaitStatus::Signaled(child, Signal::SIGTERM, false)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn pidfd_unknown() {
+        let res = PidFd::open(-1, false);
+
+        assert_eq!(
+            format!("{}", res.err().unwrap()),
+            "System call error: Invalid argument (os error 22)"
+        );
+    }
+}
@@ -0,0 +1,405 @@
+//!
+//! This file is part of syscall-rs
+//!
+
+use std::{
+    io::{ErrorKind, IoSlice},
+    os::unix::prelude::{AsFd, AsRawFd, FromRawFd, RawFd},
+    ptr,
+};
+
+use libc::c_uint;
+
+use crate::{Error, FileDesc, OFlags, Result, libc_bitflags};
+
+libc_bitflags! {
+    /// Flags for [`splice()`], [`tee()`] and [`vmsplice()`]
+    pub struct SpliceFlags: c_uint {
+        /// Hint to move pages instead of copying
+        SPLICE_F_MOVE;
+        /// Don't block on I/O
+        SPLICE_F_NONBLOCK;
+        /// More data will be coming in a subsequent splice
+        SPLICE_F_MORE;
+        /// Hint to gift the user pages to the kernel, [`vmsplice()`] only
+        SPLICE_F_GIFT;
+    }
+}
+
+/// Create a pipe and return its `(read, write)` ends
+///
+/// Only [`OFlags::O_CLOEXEC`], [`OFlags::O_DIRECT`] and [`OFlags::O_NONBLOCK`]
+/// are valid `flags`.
+pub fn pipe2(flags: OFlags) -> Result<(FileDesc, FileDesc)> {
+    let mut fds = [0 as RawFd; 2];
+
+    syscall!(pipe2(fds.as_mut_ptr(), flags.bits()))?;
+
+    Ok(unsafe { (FileDesc::from_raw_fd(fds[0]), FileDesc::from_raw_fd(fds[1])) })
+}
+
+/// Return the capacity of the pipe `fd` in bytes
+pub fn pipe_size<Fd: AsFd>(fd: Fd) -> Result<usize> {
+    let size = syscall!(fcntl(fd.as_fd().as_raw_fd(), libc::F_GETPIPE_SZ))?;
+
+    Ok(size as usize)
+}
+
+/// Set the capacity of the pipe `fd` to at least `size` bytes
+///
+/// Return the actual capacity, which is rounded up to a power of two pages.
+/// Unprivileged processes can not exceed `/proc/sys/fs/pipe-max-size`.
+pub fn set_pipe_size<Fd: AsFd>(fd: Fd, size: usize) -> Result<usize> {
+    let size = syscall!(fcntl(
+        fd.as_fd().as_raw_fd(),
+        libc::F_SETPIPE_SZ,
+        size as libc::c_int
+    ))?;
+
+    Ok(size as usize)
+}
+
+/// Move up to `len` bytes from `fd_in` to `fd_out` without copying them
+/// through userspace
+///
+/// One of the file descriptors must refer to a pipe. An offset must be `None`
+/// for a pipe. Otherwise data is transferred from or to the given offset,
+/// which is advanced accordingly, instead of the current file offset.
+///
+/// Partial transfers are retried until `len` bytes have been moved, the end
+/// of input has been reached, or a nonblocking transfer would block. Return
+/// the number of bytes moved.
+pub fn splice<In: AsFd, Out: AsFd>(
+    fd_in: In,
+    off_in: Option<&mut u64>,
+    fd_out: Out,
+    off_out: Option<&mut u64>,
+    len: usize,
+    flags: SpliceFlags,
+) -> Result<usize> {
+    let off_in = offset(off_in);
+    let off_out = offset(off_out);
+
+    transfer(len, |len| {
+        syscall!(splice(
+            fd_in.as_fd().as_raw_fd(),
+            off_in,
+            fd_out.as_fd().as_raw_fd(),
+            off_out,
+            len,
+            flags.bits()
+        ))
+        .map(|n| n as usize)
+    })
+}
+
+/// Duplicate up to `len` bytes from the pipe `fd_in` to the pipe `fd_out`
+/// without consuming them
+///
+/// Unlike the other helpers, this does not retry partial transfers, since
+/// a subsequent `tee(2)` would duplicate the same data again. Return the
+/// number of bytes duplicated.
+pub fn tee<In: AsFd, Out: AsFd>(
+    fd_in: In,
+    fd_out: Out,
+    len: usize,
+    flags: SpliceFlags,
+) -> Result<usize> {
+    loop {
+        match syscall!(tee(
+            fd_in.as_fd().as_raw_fd(),
+            fd_out.as_fd().as_raw_fd(),
+            len,
+            flags.bits()
+        )) {
+            Ok(n) => return Ok(n as usize),
+            Err(Error::Syscall(err)) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Splice the user memory `bufs` into the pipe `fd`
+///
+/// Partial transfers are retried until all of `bufs` has been spliced or a
+/// nonblocking transfer would block. Return the number of bytes spliced.
+///
+/// Note that without [`SpliceFlags::SPLICE_F_GIFT`] the pipe references the
+/// pages of `bufs`, so they must not be modified until the data has been
+/// consumed from the pipe.
+pub fn vmsplice<Fd: AsFd>(fd: Fd, bufs: &[IoSlice], flags: SpliceFlags) -> Result<usize> {
+    let mut bufs = bufs.to_vec();
+    let mut bufs = bufs.as_mut_slice();
+    let len = bufs.iter().map(|buf| buf.len()).sum();
+
+    transfer(len, |_| {
+        let n = syscall!(vmsplice(
+            fd.as_fd().as_raw_fd(),
+            bufs.as_ptr() as *const libc::iovec,
+            bufs.len(),
+            flags.bits()
+        ))? as usize;
+
+        IoSlice::advance_slices(&mut bufs, n);
+
+        Ok(n)
+    })
+}
+
+/// Copy up to `count` bytes from `fd_in` to `fd_out` within the kernel
+///
+/// If `offset` is `Some`, data is read from the given offset, which is
+/// advanced accordingly, instead of the current file offset of `fd_in`.
+///
+/// Partial transfers are retried until `count` bytes have been copied, the
+/// end of input has been reached, or a nonblocking transfer would block.
+/// Return the number of bytes copied.
+pub fn sendfile<Out: AsFd, In: AsFd>(
+    fd_out: Out,
+    fd_in: In,
+    offset: Option<&mut u64>,
+    count: usize,
+) -> Result<usize> {
+    let offset = self::offset(offset);
+
+    transfer(count, |count| {
+        syscall!(sendfile(
+            fd_out.as_fd().as_raw_fd(),
+            fd_in.as_fd().as_raw_fd(),
+            offset,
+            count
+        ))
+        .map(|n| n as usize)
+    })
+}
+
+/// Copy up to `len` bytes from the file `fd_in` to the file `fd_out` within
+/// the kernel
+///
+/// Offsets behave like the ones of [`splice()`]. Filesystems supporting it
+/// may share the data blocks instead of copying them.
+///
+/// Partial transfers are retried until `len` bytes have been copied or the
+/// end of input has been reached. Return the number of bytes copied.
+pub fn copy_file_range<In: AsFd, Out: AsFd>(
+    fd_in: In,
+    off_in: Option<&mut u64>,
+    fd_out: Out,
+    off_out: Option<&mut u64>,
+    len: usize,
+) -> Result<usize> {
+    let off_in = offset(off_in);
+    let off_out = offset(off_out);
+
+    transfer(len, |len| {
+        syscall!(copy_file_range(
+            fd_in.as_fd().as_raw_fd(),
+            off_in,
+            fd_out.as_fd().as_raw_fd(),
+            off_out,
+            len,
+            0
+        ))
+        .map(|n| n as usize)
+    })
+}
+
+/// Call `f` with the number of bytes left until `len` bytes have been
+/// transferred, `f` returns `0` or it would block after a partial transfer
+fn transfer<F>(len: usize, mut f: F) -> Result<usize>
+where
+    F: FnMut(usize) -> Result<usize>,
+{
+    let mut total = 0;
+
+    while total < len {
+        match f(len - total) {
+            Ok(0) => break,
+            Ok(n) => total += n,
+            Err(Error::Syscall(err)) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(Error::Syscall(err)) if err.kind() == ErrorKind::WouldBlock && total > 0 => break,
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(total)
+}
+
+/// Return `offset` as pointer to be updated by the kernel, or null to use
+/// the current file offset
+fn offset(offset: Option<&mut u64>) -> *mut libc::loff_t {
+    offset.map_or(ptr::null_mut(), |offset| {
+        offset as *mut u64 as *mut libc::loff_t
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{IoSlice, Read, Seek, SeekFrom, Write},
+        os::unix::prelude::OwnedFd,
+        thread,
+    };
+
+    use anyhow::Result;
+
+    use super::{
+        SpliceFlags, copy_file_range, pipe_size, pipe2, sendfile, set_pipe_size, splice, tee,
+        vmsplice,
+    };
+    use crate::{FileDesc, OFlags};
+
+    const LEN: usize = 1 << 20;
+
+    fn tempfile(data: &[u8]) -> Result<FileDesc> {
+        let mut file = tempfile::tempfile()?;
+        file.write_all(data)?;
+        file.seek(SeekFrom::Start(0))?;
+
+        Ok(OwnedFd::from(file).into())
+    }
+
+    fn contents(fd: &FileDesc) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        let mut file = std::fs::File::from(OwnedFd::from(fd.try_clone()?));
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_end(&mut buf)?;
+
+        Ok(buf)
+    }
+
+    fn data() -> Vec<u8> {
+        (0..LEN).map(|i| i as u8).collect()
+    }
+
+    #[test]
+    fn pipe_flags() -> Result<()> {
+        let (rx, tx) = pipe2(OFlags::O_CLOEXEC | OFlags::O_NONBLOCK)?;
+
+        assert!(rx.cloexec()?);
+        assert!(tx.flags()?.contains(OFlags::O_NONBLOCK));
+
+        tx.write(b"hello")?;
+
+        let mut buf = [0u8; 8];
+        assert_eq!(rx.read(&mut buf)?, 5);
+        assert_eq!(&buf[..5], b"hello");
+
+        // rounded up to a power of two pages
+        let page = syscall!(sysconf(libc::_SC_PAGESIZE))? as usize;
+        let size = set_pipe_size(&rx, 3 * page)?;
+        assert_eq!(size, 4 * page);
+        assert_eq!(pipe_size(&tx)?, size);
+
+        Ok(())
+    }
+
+    #[test]
+    fn pipe_splice() -> Result<()> {
+        let data = data();
+        let input = tempfile(&data)?;
+        let output = tempfile(&[])?;
+
+        let (rx, tx) = pipe2(OFlags::O_CLOEXEC)?;
+
+        // the pipe holds less than LEN bytes, so drain it concurrently
+        let drain = thread::spawn(move || -> Result<Vec<u8>> {
+            let n = splice(&rx, None, &output, None, LEN, SpliceFlags::empty())?;
+            assert_eq!(n, LEN);
+
+            contents(&output)
+        });
+
+        let mut off = 0;
+        let n = splice(
+            &input,
+            Some(&mut off),
+            &tx,
+            None,
+            LEN,
+            SpliceFlags::SPLICE_F_MORE,
+        )?;
+        assert_eq!(n, LEN);
+        assert_eq!(off, LEN as u64);
+
+        assert_eq!(drain.join().unwrap()?, data);
+
+        Ok(())
+    }
+
+    #[test]
+    fn pipe_tee_vmsplice() -> Result<()> {
+        let (rx, tx) = pipe2(OFlags::O_CLOEXEC)?;
+        let (copy_rx, copy_tx) = pipe2(OFlags::O_CLOEXEC)?;
+
+        let n = vmsplice(
+            &tx,
+            &[IoSlice::new(b"hello, "), IoSlice::new(b"world")],
+            SpliceFlags::empty(),
+        )?;
+        assert_eq!(n, 12);
+
+        assert_eq!(tee(&rx, &copy_tx, 64, SpliceFlags::empty())?, 12);
+
+        let mut buf = [0u8; 12];
+        (&rx).read_exact(&mut buf)?;
+        assert_eq!(&buf, b"hello, world");
+        (&copy_rx).read_exact(&mut buf)?;
+        assert_eq!(&buf, b"hello, world");
+
+        Ok(())
+    }
+
+    #[test]
+    fn pipe_nonblocking_partial() -> Result<()> {
+        let (rx, tx) = pipe2(OFlags::O_CLOEXEC | OFlags::O_NONBLOCK)?;
+        let size = pipe_size(&tx)?;
+
+        let data = data();
+        let n = vmsplice(&tx, &[IoSlice::new(&data)], SpliceFlags::SPLICE_F_NONBLOCK)?;
+
+        // the pipe is full, possibly with partial pages
+        assert!(n > 0 && n <= size);
+
+        let mut buf = vec![0u8; n];
+        (&rx).read_exact(&mut buf)?;
+        assert_eq!(buf, data[..n]);
+
+        // nothing has been transferred yet, so the error is reported
+        let output = tempfile(&[])?;
+        let (empty, _tx) = pipe2(OFlags::O_CLOEXEC | OFlags::O_NONBLOCK)?;
+        assert!(
+            splice(
+                &empty,
+                None,
+                &output,
+                None,
+                1,
+                SpliceFlags::SPLICE_F_NONBLOCK
+            )
+            .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn file_copy() -> Result<()> {
+        let data = data();
+        let input = tempfile(&data)?;
+
+        let output = tempfile(&[])?;
+        let mut off_in = 16;
+        let n = copy_file_range(&input, Some(&mut off_in), &output, None, LEN)?;
+        assert_eq!(n, LEN - 16);
+        assert_eq!(off_in, LEN as u64);
+        assert_eq!(contents(&output)?, &data[16..]);
+
+        let output = tempfile(&[])?;
+        let n = sendfile(&output, &input, None, 2 * LEN)?;
+        assert_eq!(n, LEN);
+        assert_eq!(contents(&output)?, data);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,366 @@
+//!
+//! This file is part of syscall-rs
+//!
+
+use std::{
+    ffi::{CStr, CString},
+    os::unix::prelude::{AsFd, AsRawFd},
+    time::Duration,
+};
+
+use bitflags::bitflags;
+use libc::{c_int, c_ulong};
+
+use crate::{Result, Signal};
+
+/// Length of a thread name, including the terminating NUL
+const TASK_COMM_LEN: usize = 16;
+
+// speculation control, not consistently defined by `libc`
+const PR_GET_SPECULATION_CTRL: c_int = 52;
+const PR_SET_SPECULATION_CTRL: c_int = 53;
+
+/// Call `prctl(2)` with the unused arguments set to zero
+pub(crate) fn prctl(option: c_int, arg2: c_ulong, arg3: c_ulong) -> Result<c_int> {
+    syscall!(prctl(option, arg2, arg3, 0 as c_ulong, 0 as c_ulong))
+}
+
+/// Make the calling process a child subreaper, or stop being one
+///
+/// Orphaned descendants of a subreaper are reparented to it instead of the
+/// init process, so it can wait for them.
+pub fn set_child_subreaper(enable: bool) -> Result<()> {
+    prctl(libc::PR_SET_CHILD_SUBREAPER, enable as c_ulong, 0).map(|_| ())
+}
+
+/// Return `true` if the calling process is a child subreaper
+pub fn child_subreaper() -> Result<bool> {
+    let mut enabled: c_int = 0;
+
+    prctl(
+        libc::PR_GET_CHILD_SUBREAPER,
+        &mut enabled as *mut c_int as c_ulong,
+        0,
+    )?;
+
+    Ok(enabled != 0)
+}
+
+/// Set the signal the calling thread receives when its parent thread
+/// terminates, `None` to clear it
+///
+/// The setting is cleared for children created by `fork(2)`.
+pub fn set_parent_death_signal(signal: Option<Signal>) -> Result<()> {
+    let signum = signal.map_or(0, c_int::from);
+
+    prctl(libc::PR_SET_PDEATHSIG, signum as c_ulong, 0).map(|_| ())
+}
+
+/// Return the parent death signal of the calling thread
+pub fn parent_death_signal() -> Result<Option<Signal>> {
+    let mut signum: c_int = 0;
+
+    prctl(
+        libc::PR_GET_PDEATHSIG,
+        &mut signum as *mut c_int as c_ulong,
+        0,
+    )?;
+
+    match signum {
+        0 => Ok(None),
+        signum => Signal::try_from(signum).map(Some),
+    }
+}
+
+/// Set the name of the calling thread, as shown in `/proc/self/comm`
+///
+/// Names longer than 15 bytes are truncated.
+pub fn set_thread_name(name: &str) -> Result<()> {
+    let name = CString::new(name)?;
+
+    prctl(libc::PR_SET_NAME, name.as_ptr() as c_ulong, 0).map(|_| ())
+}
+
+/// Return the name of the calling thread
+pub fn thread_name() -> Result<String> {
+    let mut buf = [0u8; TASK_COMM_LEN];
+
+    prctl(libc::PR_GET_NAME, buf.as_mut_ptr() as c_ulong, 0)?;
+
+    let name = CStr::from_bytes_until_nul(&buf).map_err(|_| "thread name not terminated")?;
+
+    Ok(name.to_string_lossy().into_owned())
+}
+
+/// Set whether the calling process is dumpable
+///
+/// Processes that are not dumpable don't produce core dumps, and their
+/// `/proc/<pid>` files are owned by root, which keeps unprivileged processes
+/// from attaching with `ptrace(2)`.
+pub fn set_dumpable(dumpable: bool) -> Result<()> {
+    prctl(libc::PR_SET_DUMPABLE, dumpable as c_ulong, 0).map(|_| ())
+}
+
+/// Return `true` if the calling process is dumpable
+pub fn dumpable() -> Result<bool> {
+    prctl(libc::PR_GET_DUMPABLE, 0, 0).map(|res| res != 0)
+}
+
+/// Set the no new privileges bit of the calling thread
+///
+/// Once set, `execve(2)` can't grant privileges anymore, e.g. by set-user-ID
+/// bits or file capabilities. The bit can't be unset and is inherited by
+/// children.
+pub fn set_no_new_privs() -> Result<()> {
+    prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0).map(|_| ())
+}
+
+/// Return the no new privileges bit of the calling thread
+pub fn no_new_privs() -> Result<bool> {
+    prctl(libc::PR_GET_NO_NEW_PRIVS, 0, 0).map(|res| res == 1)
+}
+
+/// Set the timer slack of the calling thread, i.e. how much timers may be
+/// delayed to group wake ups. Zero resets the slack to the default.
+pub fn set_timer_slack(slack: Duration) -> Result<()> {
+    prctl(libc::PR_SET_TIMERSLACK, slack.as_nanos() as c_ulong, 0).map(|_| ())
+}
+
+/// Return the timer slack of the calling thread
+pub fn timer_slack() -> Result<Duration> {
+    prctl(libc::PR_GET_TIMERSLACK, 0, 0).map(|ns| Duration::from_nanos(ns as u64))
+}
+
+/// Disable transparent huge pages for the calling process, or enable them
+/// again
+///
+/// The setting is inherited by children and preserved across `execve(2)`.
+pub fn set_thp_disable(disable: bool) -> Result<()> {
+    prctl(libc::PR_SET_THP_DISABLE, disable as c_ulong, 0).map(|_| ())
+}
+
+/// Return `true` if transparent huge pages are disabled for the calling
+/// process
+pub fn thp_disable() -> Result<bool> {
+    prctl(libc::PR_GET_THP_DISABLE, 0, 0).map(|res| res != 0)
+}
+
+/// Replace the executable shown as `/proc/self/exe` with `file`
+///
+/// `file` must be an executable regular file. This requires
+/// `CAP_SYS_RESOURCE`.
+pub fn set_exe_file<F: AsFd>(file: &F) -> Result<()> {
+    prctl(
+        libc::PR_SET_MM,
+        libc::PR_SET_MM_EXE_FILE as c_ulong,
+        file.as_fd().as_raw_fd() as c_ulong,
+    )
+    .map(|_| ())
+}
+
+/// Speculative execution misfeature, see [`speculation_ctrl()`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Speculation {
+    /// Speculative store bypass
+    StoreBypass,
+    /// Indirect branch speculation
+    IndirectBranch,
+    /// Flushing of the L1 data cache on context switch
+    L1dFlush,
+}
+
+impl Speculation {
+    fn as_raw(self) -> c_ulong {
+        match self {
+            Speculation::StoreBypass => 0,
+            Speculation::IndirectBranch => 1,
+            Speculation::L1dFlush => 2,
+        }
+    }
+}
+
+bitflags! {
+    /// Speculation control state. An empty state means the CPU is not
+    /// affected.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct SpecCtrl: c_int {
+        /// Mitigation can be controlled per thread
+        const PR_SPEC_PRCTL = 1 << 0;
+        /// Speculation is enabled, i.e. the mitigation is disabled
+        const PR_SPEC_ENABLE = 1 << 1;
+        /// Speculation is disabled, i.e. the mitigation is enabled
+        const PR_SPEC_DISABLE = 1 << 2;
+        /// Speculation is disabled and can't be enabled anymore
+        const PR_SPEC_FORCE_DISABLE = 1 << 3;
+        /// Speculation is disabled until the next `execve(2)`
+        const PR_SPEC_DISABLE_NOEXEC = 1 << 4;
+    }
+}
+
+/// Speculation control setting, see [`set_speculation_ctrl()`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpecState {
+    /// Enable speculation, i.e. disable the mitigation
+    Enable,
+    /// Disable speculation, i.e. enable the mitigation
+    Disable,
+    /// Disable speculation for good, it can't be enabled anymore
+    ForceDisable,
+    /// Disable speculation until the next `execve(2)`
+    DisableNoexec,
+}
+
+impl From<SpecState> for SpecCtrl {
+    fn from(state: SpecState) -> Self {
+        match state {
+            SpecState::Enable => SpecCtrl::PR_SPEC_ENABLE,
+            SpecState::Disable => SpecCtrl::PR_SPEC_DISABLE,
+            SpecState::ForceDisable => SpecCtrl::PR_SPEC_FORCE_DISABLE,
+            SpecState::DisableNoexec => SpecCtrl::PR_SPEC_DISABLE_NOEXEC,
+        }
+    }
+}
+
+/// Return the speculation control state of the calling thread for `which`
+pub fn speculation_ctrl(which: Speculation) -> Result<SpecCtrl> {
+    prctl(PR_GET_SPECULATION_CTRL, which.as_raw(), 0).map(SpecCtrl::from_bits_truncate)
+}
+
+/// Set the speculation control state of the calling thread for `which`
+///
+/// This requires the current state to contain [`SpecCtrl::PR_SPEC_PRCTL`].
+pub fn set_speculation_ctrl(which: Speculation, state: SpecState) -> Result<()> {
+    prctl(
+        PR_SET_SPECULATION_CTRL,
+        which.as_raw(),
+        SpecCtrl::from(state).bits() as c_ulong,
+    )
+    .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::fs::PermissionsExt, thread, time::Duration};
+
+    use anyhow::{Result, ensure};
+
+    use super::{
+        SpecCtrl, SpecState, Speculation, child_subreaper, dumpable, no_new_privs,
+        parent_death_signal, set_child_subreaper, set_dumpable, set_exe_file, set_no_new_privs,
+        set_parent_death_signal, set_speculation_ctrl, set_thp_disable, set_thread_name,
+        set_timer_slack, speculation_ctrl, thp_disable, thread_name, timer_slack,
+    };
+    use crate::{Error, Signal, test_util::in_child};
+
+    #[test]
+    fn prctl_subreaper() -> Result<()> {
+        in_child(|| {
+            ensure!(!child_subreaper()?);
+            set_child_subreaper(true)?;
+            ensure!(child_subreaper()?);
+            set_child_subreaper(false)?;
+            ensure!(!child_subreaper()?);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn prctl_pdeathsig() -> Result<()> {
+        in_child(|| {
+            ensure!(parent_death_signal()?.is_none());
+            set_parent_death_signal(Some(Signal::SIGUSR1))?;
+            ensure!(parent_death_signal()? == Some(Signal::SIGUSR1));
+            set_parent_death_signal(None)?;
+            ensure!(parent_death_signal()?.is_none());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn prctl_thread() -> Result<()> {
+        // both settings are per thread
+        thread::spawn(|| -> Result<()> {
+            set_thread_name("worker")?;
+            assert_eq!(thread_name()?, "worker");
+            assert_eq!(fs::read_to_string("/proc/thread-self/comm")?, "worker\n");
+
+            set_thread_name("a-rather-long-thread-name")?;
+            assert_eq!(thread_name()?, "a-rather-long-t");
+            assert!(set_thread_name("nul\0").is_err());
+
+            set_timer_slack(Duration::from_millis(1))?;
+            assert_eq!(timer_slack()?, Duration::from_millis(1));
+            set_timer_slack(Duration::ZERO)?;
+            assert!(timer_slack()? > Duration::ZERO);
+
+            Ok(())
+        })
+        .join()
+        .unwrap()
+    }
+
+    #[test]
+    fn prctl_process() -> Result<()> {
+        in_child(|| {
+            ensure!(dumpable()?);
+            set_dumpable(false)?;
+            ensure!(!dumpable()?);
+            set_dumpable(true)?;
+            ensure!(dumpable()?);
+
+            set_thp_disable(true)?;
+            ensure!(thp_disable()?);
+            set_thp_disable(false)?;
+            ensure!(!thp_disable()?);
+
+            ensure!(!no_new_privs()?);
+            set_no_new_privs()?;
+            ensure!(no_new_privs()?);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn prctl_exe_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let exe = dir.path().join("exe");
+
+        fs::copy("/proc/self/exe", &exe)?;
+        fs::set_permissions(&exe, fs::Permissions::from_mode(0o755))?;
+
+        in_child(|| {
+            match set_exe_file(&fs::File::open(&exe)?) {
+                Ok(()) => ensure!(fs::read_link("/proc/self/exe")? == exe),
+                // no CAP_SYS_RESOURCE
+                Err(Error::Syscall(err)) if err.raw_os_error() == Some(libc::EPERM) => {}
+                Err(err) => return Err(err.into()),
+            }
+
+            // not executable
+            let file = fs::File::open(dir.path())?;
+            ensure!(set_exe_file(&file).is_err());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn prctl_speculation() -> Result<()> {
+        let ctrl = speculation_ctrl(Speculation::StoreBypass)?;
+
+        if !ctrl.contains(SpecCtrl::PR_SPEC_PRCTL) {
+            return Ok(());
+        }
+
+        in_child(|| {
+            set_speculation_ctrl(Speculation::StoreBypass, SpecState::Disable)?;
+            let ctrl = speculation_ctrl(Speculation::StoreBypass)?;
+            ensure!(ctrl.contains(SpecCtrl::PR_SPEC_DISABLE), "{:?}", ctrl);
+
+            Ok(())
+        })
+    }
+}
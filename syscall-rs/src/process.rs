@@ -0,0 +1,520 @@
+//!
+//! This file is part of syscall-rs
+//!
+
+use std::{
+    ffi::{CString, OsStr, OsString},
+    io, mem,
+    os::unix::{
+        ffi::OsStrExt,
+        prelude::{AsRawFd, FromRawFd, RawFd},
+    },
+    path::Path,
+    ptr,
+};
+
+use crate::{Error, FileDesc, OFlags, PidFd, Result, Signal, Stdio, WaitStatus, pipe2, wait};
+
+/// A process builder, similar to [`std::process::Command`], which spawns
+/// child processes using `fork(2)` and `execvp(3)`.
+///
+/// Errors caused by the `exec` call in the child process are reported back
+/// to the parent through a `CLOEXEC` error pipe, so [`Command::spawn()`] only
+/// succeeds if the child has actually started to run `program`.
+pub struct Command {
+    program: OsString,
+    args: Vec<OsString>,
+    env: Option<Vec<(OsString, OsString)>>,
+    cwd: Option<OsString>,
+    stdin: Option<Stdio>,
+    stdout: Option<Stdio>,
+    stderr: Option<Stdio>,
+}
+
+impl Command {
+    /// Return a new [`Command`] for running `program`.
+    ///
+    /// If `program` is not an absolute path, the `PATH` will be searched.
+    /// By default, the child inherits the environment, the current working
+    /// directory and all standard I/O streams of the parent.
+    pub fn new<S: AsRef<OsStr>>(program: S) -> Command {
+        let program = program.as_ref().to_os_string();
+
+        Command {
+            args: vec![program.clone()],
+            program,
+            env: None,
+            cwd: None,
+            stdin: None,
+            stdout: None,
+            stderr: None,
+        }
+    }
+
+    /// Add an argument to pass to the program
+    pub fn arg<S: AsRef<OsStr>>(&mut self, arg: S) -> &mut Command {
+        self.args.push(arg.as_ref().to_os_string());
+        self
+    }
+
+    /// Add multiple arguments to pass to the program
+    pub fn args<I, S>(&mut self, args: I) -> &mut Command
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        for arg in args {
+            self.arg(arg);
+        }
+        self
+    }
+
+    /// Insert or update an environment variable for the child process
+    pub fn env<K, V>(&mut self, key: K, val: V) -> &mut Command
+    where
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        let (key, val) = (key.as_ref().to_os_string(), val.as_ref().to_os_string());
+        let env = self
+            .env
+            .get_or_insert_with(|| std::env::vars_os().collect());
+
+        match env.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = val,
+            None => env.push((key, val)),
+        }
+        self
+    }
+
+    /// Remove an environment variable from the child process
+    pub fn env_remove<K: AsRef<OsStr>>(&mut self, key: K) -> &mut Command {
+        self.env
+            .get_or_insert_with(|| std::env::vars_os().collect())
+            .retain(|(k, _)| k != key.as_ref());
+        self
+    }
+
+    /// Clear the entire environment of the child process
+    pub fn env_clear(&mut self) -> &mut Command {
+        self.env = Some(Vec::new());
+        self
+    }
+
+    /// Set the working directory of the child process
+    pub fn current_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Command {
+        self.cwd = Some(dir.as_ref().as_os_str().to_os_string());
+        self
+    }
+
+    /// Configure the standard input stream of the child process
+    pub fn stdin(&mut self, cfg: Stdio) -> &mut Command {
+        self.stdin = Some(cfg);
+        self
+    }
+
+    /// Configure the standard output stream of the child process
+    pub fn stdout(&mut self, cfg: Stdio) -> &mut Command {
+        self.stdout = Some(cfg);
+        self
+    }
+
+    /// Configure the standard error stream of the child process
+    pub fn stderr(&mut self, cfg: Stdio) -> &mut Command {
+        self.stderr = Some(cfg);
+        self
+    }
+
+    /// Spawn the command as a child process and return a [`Child`] handle.
+    ///
+    /// Note that the standard I/O configuration is consumed by this call,
+    /// subsequent calls will inherit all standard I/O streams again.
+    pub fn spawn(&mut self) -> Result<Child> {
+        // everything the child needs has to be allocated before the fork,
+        // since only async-signal-safe functions may be used afterwards
+        let program = cstring(&self.program)?;
+        let argv = self.args.iter().map(cstring).collect::<Result<Vec<_>>>()?;
+        let envp = self
+            .env
+            .as_ref()
+            .map(|env| {
+                env.iter()
+                    .map(|(k, v)| {
+                        let mut kv = k.clone();
+                        kv.push("=");
+                        kv.push(v);
+                        cstring(&kv)
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .transpose()?;
+        let cwd = self.cwd.as_ref().map(cstring).transpose()?;
+
+        let argv_ptrs = null_terminated(&argv);
+        let envp_ptrs = envp.as_deref().map(null_terminated);
+
+        let stdin = ChildStdio::new(self.stdin.take(), true)?;
+        let stdout = ChildStdio::new(self.stdout.take(), false)?;
+        let stderr = ChildStdio::new(self.stderr.take(), false)?;
+
+        let (err_rx, err_tx) = pipe2(OFlags::O_CLOEXEC)?;
+
+        let pid = syscall!(fork())?;
+
+        if pid == 0 {
+            // child
+            let errno = unsafe {
+                exec_child(
+                    &program,
+                    &argv_ptrs,
+                    envp_ptrs.as_deref(),
+                    cwd.as_deref(),
+                    [&stdin, &stdout, &stderr],
+                )
+            };
+
+            let bytes = errno.to_ne_bytes();
+
+            unsafe {
+                libc::write(
+                    err_tx.as_raw_fd(),
+                    bytes.as_ptr() as *const libc::c_void,
+                    bytes.len(),
+                );
+                libc::_exit(127)
+            }
+        }
+
+        // parent
+        drop(err_tx);
+
+        let mut bytes = [0u8; mem::size_of::<libc::c_int>()];
+
+        let num = loop {
+            match err_rx.read(&mut bytes) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                res => break res,
+            }
+        };
+
+        match num {
+            // successful exec closed the error pipe
+            Ok(0) => Ok(Child {
+                pid,
+                stdin: stdin.parent,
+                stdout: stdout.parent,
+                stderr: stderr.parent,
+            }),
+            Ok(num) if num == bytes.len() => {
+                // reap the failed child
+                wait(pid)?;
+
+                Err(Error::Syscall(io::Error::from_raw_os_error(
+                    libc::c_int::from_ne_bytes(bytes),
+                )))
+            }
+            Ok(_) => {
+                // the child exits right after writing the error, reap it
+                wait(pid)?;
+
+                Err(Error::Syscall(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "short read from exec error pipe",
+                )))
+            }
+            Err(e) => Err(Error::Syscall(e)),
+        }
+    }
+
+    /// Spawn the command as a child process, wait for it to finish and
+    /// return its [`WaitStatus`]
+    pub fn status(&mut self) -> Result<WaitStatus> {
+        self.spawn()?.wait()
+    }
+}
+
+/// Handle to a child process spawned by a [`Command`]
+#[derive(Debug)]
+pub struct Child {
+    pid: libc::pid_t,
+    /// Parent end of the child's stdin, if configured as [`Stdio::Pipe`]
+    pub stdin: Option<FileDesc>,
+    /// Parent end of the child's stdout, if configured as [`Stdio::Pipe`]
+    pub stdout: Option<FileDesc>,
+    /// Parent end of the child's stderr, if configured as [`Stdio::Pipe`]
+    pub stderr: Option<FileDesc>,
+}
+
+impl Child {
+    /// Return the process ID of the child
+    pub fn id(&self) -> libc::pid_t {
+        self.pid
+    }
+
+    /// Return a [`PidFd`] referring to the child
+    pub fn pidfd(&self) -> Result<PidFd> {
+        PidFd::open(self.pid, false)
+    }
+
+    /// Send `signal` to the child
+    pub fn kill(&self, signal: Signal) -> Result<()> {
+        syscall!(kill(self.pid, signal.as_raw())).map(|_| ())
+    }
+
+    /// Wait for the child to change its state and return a [`WaitStatus`].
+    ///
+    /// The stdin of the child is closed before waiting, in order to avoid
+    /// a deadlock with a child waiting for more input.
+    pub fn wait(&mut self) -> Result<WaitStatus> {
+        drop(self.stdin.take());
+        wait(self.pid)
+    }
+}
+
+/// Child side of a standard I/O stream
+struct ChildStdio {
+    /// File descriptor to be duplicated onto the stream, if any
+    child: Option<FileDesc>,
+    /// Parent end of a pipe, if any
+    parent: Option<FileDesc>,
+}
+
+impl ChildStdio {
+    fn new(cfg: Option<Stdio>, readable: bool) -> Result<ChildStdio> {
+        Ok(match cfg.unwrap_or(Stdio::Inherit) {
+            Stdio::Inherit => ChildStdio {
+                child: None,
+                parent: None,
+            },
+            Stdio::Fd(fd) => ChildStdio {
+                child: Some(fd),
+                parent: None,
+            },
+            Stdio::Null => {
+                let flags = if readable {
+                    libc::O_RDONLY
+                } else {
+                    libc::O_WRONLY
+                };
+
+                let fd = syscall!(open(c"/dev/null".as_ptr(), flags | libc::O_CLOEXEC))?;
+
+                ChildStdio {
+                    child: Some(unsafe { FileDesc::from_raw_fd(fd) }),
+                    parent: None,
+                }
+            }
+            Stdio::Pipe => {
+                let (rx, tx) = pipe2(OFlags::O_CLOEXEC)?;
+
+                if readable {
+                    ChildStdio {
+                        child: Some(rx),
+                        parent: Some(tx),
+                    }
+                } else {
+                    ChildStdio {
+                        child: Some(tx),
+                        parent: Some(rx),
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// Set up the child process and exec `program`.
+///
+/// Only returns in case of an error, with the `errno` of the failing call.
+///
+/// ### Safety
+///
+/// Must only be called in the child after a `fork(2)`. The pointer arrays
+/// must be null terminated and point into live [`CString`]s.
+unsafe fn exec_child(
+    program: &CString,
+    argv: &[*const libc::c_char],
+    envp: Option<&[*const libc::c_char]>,
+    cwd: Option<&std::ffi::CStr>,
+    stdio: [&ChildStdio; 3],
+) -> libc::c_int {
+    let errno = || {
+        io::Error::last_os_error()
+            .raw_os_error()
+            .unwrap_or(libc::EINVAL)
+    };
+
+    // move sources which are standard I/O fds themselves out of the way
+    // first, setting up a lower target could clobber them otherwise
+    let mut fds: [Option<RawFd>; 3] = [None; 3];
+
+    for (fd, io) in fds.iter_mut().zip(stdio) {
+        let Some(src) = io.child.as_ref().map(|fd| fd.as_raw_fd()) else {
+            continue;
+        };
+
+        *fd = if src < 3 {
+            match unsafe { libc::fcntl(src, libc::F_DUPFD_CLOEXEC, 3) } {
+                -1 => return errno(),
+                fd => Some(fd),
+            }
+        } else {
+            Some(src)
+        };
+    }
+
+    for (target, fd) in fds.iter().enumerate() {
+        if let Some(fd) = fd
+            && unsafe { libc::dup2(*fd, target as RawFd) } == -1
+        {
+            return errno();
+        }
+    }
+
+    if let Some(cwd) = cwd
+        && unsafe { libc::chdir(cwd.as_ptr()) } == -1
+    {
+        return errno();
+    }
+
+    unsafe {
+        match envp {
+            Some(envp) => libc::execvpe(program.as_ptr(), argv.as_ptr(), envp.as_ptr()),
+            None => libc::execvp(program.as_ptr(), argv.as_ptr()),
+        }
+    };
+
+    errno()
+}
+
+fn cstring<S: AsRef<OsStr>>(s: S) -> Result<CString> {
+    Ok(CString::new(s.as_ref().as_bytes())?)
+}
+
+fn null_terminated(strings: &[CString]) -> Vec<*const libc::c_char> {
+    strings
+        .iter()
+        .map(|s| s.as_ptr())
+        .chain(Some(ptr::null()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+
+    use anyhow::Result;
+
+    use crate::{Command, FileDesc, OFlags, Stdio, WaitStatus, pipe2, wait};
+
+    fn read_all(fd: &crate::FileDesc) -> Result<String> {
+        let mut out = Vec::new();
+        let mut buf = [0u8; 256];
+
+        loop {
+            match fd.read(&mut buf)? {
+                0 => break,
+                n => out.extend_from_slice(&buf[..n]),
+            }
+        }
+
+        Ok(String::from_utf8(out)?)
+    }
+
+    #[test]
+    fn spawn_status() -> Result<()> {
+        let status = Command::new("sh").args(["-c", "exit 42"]).status()?;
+
+        assert!(matches!(status, WaitStatus::Exited(_, 42)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn spawn_pipe() -> Result<()> {
+        let mut child = Command::new("cat")
+            .stdin(Stdio::Pipe)
+            .stdout(Stdio::Pipe)
+            .spawn()?;
+
+        child.stdin.as_ref().unwrap().write(b"hello child")?;
+
+        let status = child.wait()?;
+        let out = read_all(child.stdout.as_ref().unwrap())?;
+
+        assert_eq!(out, "hello child");
+        assert_eq!(status, WaitStatus::Exited(child.id(), 0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn spawn_env_cwd() -> Result<()> {
+        let mut child = Command::new("/bin/sh")
+            .args(["-c", "echo $FOO; pwd"])
+            .env_clear()
+            .env("FOO", "bar")
+            .current_dir("/")
+            .stdout(Stdio::Pipe)
+            .stderr(Stdio::Null)
+            .spawn()?;
+
+        child.wait()?;
+
+        assert_eq!(read_all(child.stdout.as_ref().unwrap())?, "bar\n/\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn spawn_low_fds() -> Result<()> {
+        let (rx, tx) = pipe2(OFlags::O_CLOEXEC)?;
+
+        let pid = syscall!(fork())?;
+
+        if pid == 0 {
+            // with fd 0 closed, the stdout fd of the command becomes 0, which
+            // must survive setting up stdin
+            let res = (|| -> Result<WaitStatus> {
+                syscall!(close(0))?;
+                let fd = syscall!(fcntl(tx.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 0))?;
+
+                Ok(Command::new("echo")
+                    .arg("low")
+                    .stdin(Stdio::Null)
+                    .stdout(Stdio::Fd(unsafe { FileDesc::from_raw_fd(fd) }))
+                    .status()?)
+            })();
+
+            let ok = matches!(res, Ok(WaitStatus::Exited(_, 0)));
+            unsafe { libc::_exit(if ok { 0 } else { 1 }) }
+        }
+
+        drop(tx);
+
+        assert_eq!(wait(pid)?, WaitStatus::Exited(pid, 0));
+        assert_eq!(read_all(&rx)?, "low\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn spawn_not_found() {
+        let res = Command::new("/does/not/exist").spawn();
+
+        assert_eq!(
+            format!("{}", res.err().unwrap()),
+            "System call error: No such file or directory (os error 2)"
+        );
+    }
+
+    #[test]
+    fn spawn_bad_cwd() {
+        let res = Command::new("true").current_dir("/does/not/exist").spawn();
+
+        assert_eq!(
+            format!("{}", res.err().unwrap()),
+            "System call error: No such file or directory (os error 2)"
+        );
+    }
+}
@@ -0,0 +1,341 @@
+//!
+//! This file is part of syscall-rs
+//!
+
+use std::{
+    cmp,
+    fs::{File, OpenOptions},
+    io::{IoSlice, IoSliceMut},
+    mem,
+    os::unix::fs::FileExt,
+};
+
+use crate::{Error, Result};
+
+/// Limit for the number of buffers in the `iov` buffer arrays
+const IOV_LIMIT: usize = libc::UIO_MAXIOV as usize;
+
+/// Memory region in the address space of another process
+///
+/// This is the remote counterpart of [`IoSlice`] and [`IoSliceMut`]. It is ABI
+/// compatible with `struct iovec`, but since the region is not part of our
+/// own address space, it is described by a plain address and length.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RemoteIoVec {
+    /// Start address of the region
+    pub base: usize,
+    /// Length of the region in bytes
+    pub len: usize,
+}
+
+impl RemoteIoVec {
+    /// Return a [`RemoteIoVec`] for `len` bytes starting at `base`
+    pub fn new(base: usize, len: usize) -> RemoteIoVec {
+        RemoteIoVec { base, len }
+    }
+}
+
+/// Read memory of the process `pid`
+///
+/// The regions described by `remote` are read in order and scattered across
+/// the `local` buffers, like [`FileDesc::read_vectored()`](crate::FileDesc::read_vectored)
+/// does. Return the number of bytes read, which is less than requested if a
+/// remote region is not accessible.
+///
+/// This uses `process_vm_readv(2)` and falls back to reading `/proc/<pid>/mem`
+/// if the system call is denied, e.g. by a seccomp filter. Either way, the
+/// caller requires ptrace access to `pid`.
+pub fn read_process_memory(
+    pid: libc::pid_t,
+    remote: &[RemoteIoVec],
+    local: &mut [IoSliceMut],
+) -> Result<usize> {
+    let res = syscall!(process_vm_readv(
+        pid,
+        local.as_ptr() as *const libc::iovec,
+        cmp::min(local.len(), IOV_LIMIT) as libc::c_ulong,
+        remote.as_ptr() as *const libc::iovec,
+        cmp::min(remote.len(), IOV_LIMIT) as libc::c_ulong,
+        0
+    ));
+
+    match res {
+        Ok(n) => Ok(n as usize),
+        // report why the system call failed, should the fallback fail, too
+        Err(Error::Syscall(err)) if denied(&err) => {
+            read_proc_mem(pid, remote, local).map_err(|_| Error::Syscall(err))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Write memory of the process `pid`
+///
+/// The `local` buffers are gathered and written in order to the regions
+/// described by `remote`, like [`FileDesc::write_vectored()`](crate::FileDesc::write_vectored)
+/// does. Return the number of bytes written, which is less than requested if
+/// a remote region is not accessible.
+///
+/// This uses `process_vm_writev(2)` and falls back to writing `/proc/<pid>/mem`
+/// if the system call is denied, e.g. by a seccomp filter. Either way, the
+/// caller requires ptrace access to `pid`.
+///
+/// Note that unlike the system call, the fallback is able to write to
+/// read-only mappings of `pid`.
+pub fn write_process_memory(
+    pid: libc::pid_t,
+    remote: &[RemoteIoVec],
+    local: &[IoSlice],
+) -> Result<usize> {
+    let res = syscall!(process_vm_writev(
+        pid,
+        local.as_ptr() as *const libc::iovec,
+        cmp::min(local.len(), IOV_LIMIT) as libc::c_ulong,
+        remote.as_ptr() as *const libc::iovec,
+        cmp::min(remote.len(), IOV_LIMIT) as libc::c_ulong,
+        0
+    ));
+
+    match res {
+        Ok(n) => Ok(n as usize),
+        // report why the system call failed, should the fallback fail, too
+        Err(Error::Syscall(err)) if denied(&err) => {
+            write_proc_mem(pid, remote, local).map_err(|_| Error::Syscall(err))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Return `true` if `err` indicates that the system call itself is not
+/// available, as opposed to the remote memory not being accessible
+///
+/// `EPERM` is ambiguous, it is returned if ptrace access to the remote process
+/// is denied, too. It only counts if a seccomp filter is in effect, which may
+/// block the system call.
+fn denied(err: &std::io::Error) -> bool {
+    match err.raw_os_error() {
+        Some(libc::ENOSYS) => true,
+        Some(libc::EPERM) => {
+            let mode = unsafe { libc::prctl(libc::PR_GET_SECCOMP) };
+            mode == libc::SECCOMP_MODE_FILTER as libc::c_int
+        }
+        _ => false,
+    }
+}
+
+fn read_proc_mem(
+    pid: libc::pid_t,
+    remote: &[RemoteIoVec],
+    local: &mut [IoSliceMut],
+) -> Result<usize> {
+    let mem = File::open(format!("/proc/{pid}/mem"))?;
+
+    let mut bufs = local.iter_mut().map(|buf| &mut **buf);
+    let mut buf: &mut [u8] = &mut [];
+    let mut total = 0;
+
+    for iov in remote {
+        let mut addr = iov.base;
+        let mut left = iov.len;
+
+        while left > 0 {
+            if buf.is_empty() {
+                match bufs.next() {
+                    Some(next) => buf = next,
+                    None => return Ok(total),
+                }
+                continue;
+            }
+
+            let len = cmp::min(left, buf.len());
+
+            let n = match mem.read_at(&mut buf[..len], addr as u64) {
+                Ok(0) => return Ok(total),
+                Ok(n) => n,
+                Err(err) if total == 0 => return Err(err.into()),
+                Err(_) => return Ok(total),
+            };
+
+            buf = &mut mem::take(&mut buf)[n..];
+            addr += n;
+            left -= n;
+            total += n;
+        }
+    }
+
+    Ok(total)
+}
+
+fn write_proc_mem(pid: libc::pid_t, remote: &[RemoteIoVec], local: &[IoSlice]) -> Result<usize> {
+    let mem = OpenOptions::new()
+        .write(true)
+        .open(format!("/proc/{pid}/mem"))?;
+
+    let mut bufs = local.iter().map(|buf| &**buf);
+    let mut buf: &[u8] = &[];
+    let mut total = 0;
+
+    for iov in remote {
+        let mut addr = iov.base;
+        let mut left = iov.len;
+
+        while left > 0 {
+            if buf.is_empty() {
+                match bufs.next() {
+                    Some(next) => buf = next,
+                    None => return Ok(total),
+                }
+                continue;
+            }
+
+            let len = cmp::min(left, buf.len());
+
+            let n = match mem.write_at(&buf[..len], addr as u64) {
+                Ok(0) => return Ok(total),
+                Ok(n) => n,
+                Err(err) if total == 0 => return Err(err.into()),
+                Err(_) => return Ok(total),
+            };
+
+            buf = &buf[n..];
+            addr += n;
+            left -= n;
+            total += n;
+        }
+    }
+
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{IoSlice, IoSliceMut},
+        time::Duration,
+    };
+
+    use anyhow::Result;
+
+    use super::{read_proc_mem, write_proc_mem};
+    use crate::{RemoteIoVec, Signal, WaitStatus, read_process_memory, wait, write_process_memory};
+
+    static DATA: [u8; 16] = *b"0123456789abcdef";
+
+    fn sleeper() -> Result<libc::pid_t> {
+        Ok(match syscall!(fork())? {
+            // parent
+            pid if pid != 0 => pid,
+            // child
+            _ => loop {
+                std::thread::sleep(Duration::from_millis(5));
+            },
+        })
+    }
+
+    fn kill(pid: libc::pid_t) -> Result<()> {
+        syscall!(kill(pid, libc::SIGKILL))?;
+        assert_eq!(
+            wait(pid)?,
+            WaitStatus::Signaled(pid, Signal::SIGKILL, false)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn read_child_memory() -> Result<()> {
+        let child = sleeper()?;
+
+        let base = DATA.as_ptr() as usize;
+        let remote = [RemoteIoVec::new(base, 4), RemoteIoVec::new(base + 10, 6)];
+
+        let mut head = [0u8; 6];
+        let mut tail = [0u8; 4];
+
+        let n = read_process_memory(
+            child,
+            &remote,
+            &mut [IoSliceMut::new(&mut head), IoSliceMut::new(&mut tail)],
+        )?;
+
+        assert_eq!(n, 10);
+        assert_eq!(&head, b"0123ab");
+        assert_eq!(&tail, b"cdef");
+
+        kill(child)
+    }
+
+    #[test]
+    fn write_child_memory() -> Result<()> {
+        let mut data = *b"hello, world";
+
+        let child = sleeper()?;
+
+        let remote = [RemoteIoVec::new(data.as_mut_ptr() as usize, data.len())];
+
+        let n = write_process_memory(child, &remote, &[IoSlice::new(b"HELLO")])?;
+        assert_eq!(n, 5);
+
+        let mut buf = [0u8; 12];
+        read_process_memory(child, &remote, &mut [IoSliceMut::new(&mut buf)])?;
+        assert_eq!(&buf, b"HELLO, world");
+
+        // our own copy is left untouched
+        data[0] = b'j';
+        assert_eq!(&data, b"jello, world");
+
+        kill(child)
+    }
+
+    #[test]
+    fn read_partial() -> Result<()> {
+        let pid = std::process::id() as libc::pid_t;
+
+        // the zero page is never mapped
+        let remote = [
+            RemoteIoVec::new(DATA.as_ptr() as usize, 4),
+            RemoteIoVec::new(0, 4),
+        ];
+        let mut buf = [0u8; 8];
+
+        let n = read_process_memory(pid, &remote, &mut [IoSliceMut::new(&mut buf)])?;
+        assert_eq!(n, 4);
+
+        let n = read_proc_mem(pid, &remote, &mut [IoSliceMut::new(&mut buf)])?;
+        assert_eq!(n, 4);
+
+        assert!(read_process_memory(pid, &remote[1..], &mut [IoSliceMut::new(&mut buf)]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn proc_mem_fallback() -> Result<()> {
+        let child = sleeper()?;
+
+        let mut data = [0u8; 16];
+        let base = data.as_mut_ptr() as usize;
+        let remote = [RemoteIoVec::new(base, 8), RemoteIoVec::new(base + 8, 8)];
+
+        let n = write_proc_mem(
+            child,
+            &remote,
+            &[IoSlice::new(b"abc"), IoSlice::new(b"defghijklmnop")],
+        )?;
+        assert_eq!(n, 16);
+
+        let mut a = [0u8; 5];
+        let mut b = [0u8; 11];
+        let n = read_proc_mem(
+            child,
+            &remote,
+            &mut [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)],
+        )?;
+
+        assert_eq!(n, 16);
+        assert_eq!(&a, b"abcde");
+        assert_eq!(&b, b"fghijklmnop");
+
+        kill(child)
+    }
+}
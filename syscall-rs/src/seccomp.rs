@@ -0,0 +1,1402 @@
+//!
+//! This file is part of syscall-rs
+//!
+
+use std::{
+    ffi::CString,
+    fmt, io,
+    io::IoSliceMut,
+    mem,
+    os::unix::prelude::{AsFd, AsRawFd, BorrowedFd, FromRawFd, RawFd},
+};
+
+use libc::{c_int, c_long, c_uint, c_ulong, sock_filter, sock_fprog};
+use mio::{Interest, Registry, Token, event, unix::SourceFd};
+
+use crate::{
+    Error, FileDesc, OFlags, RemoteIoVec, Result, libc_bitflags, read_process_memory,
+    set_no_new_privs,
+};
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xc000_003e;
+
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+/// On x86_64, syscalls of the x32 ABI share the audit architecture but have
+/// this bit set in their number
+#[cfg(target_arch = "x86_64")]
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+/// Maximum number of instructions in a classic BPF program
+const BPF_MAXINSNS: usize = 4096;
+
+// offsets into `struct seccomp_data`
+const DATA_NR: u32 = 0;
+const DATA_ARCH: u32 = 4;
+const DATA_IP: u32 = 8;
+const DATA_ARGS: u32 = 16;
+
+const SECCOMP_GET_ACTION_AVAIL: c_uint = 2;
+
+// `_IOWR('!', 0, struct seccomp_notif)`
+const SECCOMP_IOCTL_NOTIF_RECV: c_ulong = 0xc050_2100;
+// `_IOWR('!', 1, struct seccomp_notif_resp)`
+const SECCOMP_IOCTL_NOTIF_SEND: c_ulong = 0xc018_2101;
+// `_IOW('!', 2, __u64)`
+const SECCOMP_IOCTL_NOTIF_ID_VALID: c_ulong = 0x4008_2102;
+// `_IOW('!', 3, struct seccomp_notif_addfd)`
+const SECCOMP_IOCTL_NOTIF_ADDFD: c_ulong = 0x4018_2103;
+
+macro_rules! syscall_table {
+    ($table:ident: $($name:ident)*) => {
+        const $table: &[(&str, c_long)] = &[$((stringify!($name), libc::$name)),*];
+    };
+}
+
+syscall_table! { COMMON:
+    SYS_accept SYS_accept4 SYS_acct SYS_add_key SYS_adjtimex SYS_bind SYS_bpf SYS_brk
+    SYS_capget SYS_capset SYS_chdir SYS_chroot SYS_clock_adjtime SYS_clock_getres
+    SYS_clock_gettime SYS_clock_nanosleep SYS_clock_settime SYS_clone SYS_clone3 SYS_close
+    SYS_close_range SYS_connect SYS_copy_file_range SYS_delete_module SYS_dup SYS_dup3
+    SYS_epoll_create1 SYS_epoll_ctl SYS_epoll_pwait SYS_epoll_pwait2 SYS_eventfd2 SYS_execve
+    SYS_execveat SYS_exit SYS_exit_group SYS_faccessat SYS_faccessat2 SYS_fallocate
+    SYS_fanotify_init SYS_fanotify_mark SYS_fchdir SYS_fchmod SYS_fchmodat SYS_fchown
+    SYS_fchownat SYS_fcntl SYS_fdatasync SYS_fgetxattr SYS_finit_module SYS_flistxattr
+    SYS_flock SYS_fremovexattr SYS_fsconfig SYS_fsetxattr SYS_fsmount SYS_fsopen SYS_fspick
+    SYS_fstat SYS_fstatfs SYS_fsync SYS_ftruncate SYS_futex SYS_futex_waitv
+    SYS_get_mempolicy SYS_get_robust_list SYS_getcpu SYS_getcwd SYS_getdents64 SYS_getegid
+    SYS_geteuid SYS_getgid SYS_getgroups SYS_getitimer SYS_getpeername SYS_getpgid
+    SYS_getpid SYS_getppid SYS_getpriority SYS_getrandom SYS_getresgid SYS_getresuid
+    SYS_getrusage SYS_getsid SYS_getsockname SYS_getsockopt SYS_gettid SYS_gettimeofday
+    SYS_getuid SYS_getxattr SYS_init_module SYS_inotify_add_watch SYS_inotify_init1
+    SYS_inotify_rm_watch SYS_io_cancel SYS_io_destroy SYS_io_getevents SYS_io_setup
+    SYS_io_submit SYS_io_uring_enter SYS_io_uring_register SYS_io_uring_setup SYS_ioctl
+    SYS_ioprio_get SYS_ioprio_set SYS_kcmp SYS_kexec_file_load SYS_kexec_load SYS_keyctl
+    SYS_kill SYS_landlock_add_rule SYS_landlock_create_ruleset SYS_landlock_restrict_self
+    SYS_lgetxattr SYS_linkat SYS_listen SYS_listxattr SYS_llistxattr SYS_lookup_dcookie
+    SYS_lremovexattr SYS_lseek SYS_lsetxattr SYS_madvise SYS_mbind SYS_membarrier
+    SYS_memfd_create SYS_memfd_secret SYS_migrate_pages SYS_mincore SYS_mkdirat SYS_mknodat
+    SYS_mlock SYS_mlock2 SYS_mlockall SYS_mmap SYS_mount SYS_mount_setattr SYS_move_mount
+    SYS_move_pages SYS_mprotect SYS_mq_getsetattr SYS_mq_notify SYS_mq_open
+    SYS_mq_timedreceive SYS_mq_timedsend SYS_mq_unlink SYS_mremap SYS_mseal SYS_msgctl
+    SYS_msgget SYS_msgrcv SYS_msgsnd SYS_msync SYS_munlock SYS_munlockall SYS_munmap
+    SYS_name_to_handle_at SYS_nanosleep SYS_newfstatat SYS_nfsservctl SYS_open_by_handle_at
+    SYS_open_tree SYS_openat SYS_openat2 SYS_perf_event_open SYS_personality SYS_pidfd_getfd
+    SYS_pidfd_open SYS_pidfd_send_signal SYS_pipe2 SYS_pivot_root SYS_pkey_alloc
+    SYS_pkey_free SYS_pkey_mprotect SYS_ppoll SYS_prctl SYS_pread64 SYS_preadv SYS_preadv2
+    SYS_prlimit64 SYS_process_madvise SYS_process_mrelease SYS_process_vm_readv
+    SYS_process_vm_writev SYS_pselect6 SYS_ptrace SYS_pwrite64 SYS_pwritev SYS_pwritev2
+    SYS_quotactl SYS_quotactl_fd SYS_read SYS_readahead SYS_readlinkat SYS_readv SYS_reboot
+    SYS_recvfrom SYS_recvmmsg SYS_recvmsg SYS_remap_file_pages SYS_removexattr SYS_renameat2
+    SYS_request_key SYS_restart_syscall SYS_rseq SYS_rt_sigaction SYS_rt_sigpending
+    SYS_rt_sigprocmask SYS_rt_sigqueueinfo SYS_rt_sigreturn SYS_rt_sigsuspend
+    SYS_rt_sigtimedwait SYS_rt_tgsigqueueinfo SYS_sched_get_priority_max
+    SYS_sched_get_priority_min SYS_sched_getaffinity SYS_sched_getattr SYS_sched_getparam
+    SYS_sched_getscheduler SYS_sched_rr_get_interval SYS_sched_setaffinity SYS_sched_setattr
+    SYS_sched_setparam SYS_sched_setscheduler SYS_sched_yield SYS_seccomp SYS_semctl
+    SYS_semget SYS_semop SYS_semtimedop SYS_sendmmsg SYS_sendmsg SYS_sendto
+    SYS_set_mempolicy SYS_set_mempolicy_home_node SYS_set_robust_list SYS_set_tid_address
+    SYS_setdomainname SYS_setfsgid SYS_setfsuid SYS_setgid SYS_setgroups SYS_sethostname
+    SYS_setitimer SYS_setns SYS_setpgid SYS_setpriority SYS_setregid SYS_setresgid
+    SYS_setresuid SYS_setreuid SYS_setsid SYS_setsockopt SYS_settimeofday SYS_setuid
+    SYS_setxattr SYS_shmat SYS_shmctl SYS_shmdt SYS_shmget SYS_shutdown SYS_sigaltstack
+    SYS_signalfd4 SYS_socket SYS_socketpair SYS_splice SYS_statfs SYS_statx SYS_swapoff
+    SYS_swapon SYS_symlinkat SYS_sync SYS_syncfs SYS_sysinfo SYS_syslog SYS_tee SYS_tgkill
+    SYS_timer_create SYS_timer_delete SYS_timer_getoverrun SYS_timer_gettime
+    SYS_timer_settime SYS_timerfd_create SYS_timerfd_gettime SYS_timerfd_settime SYS_times
+    SYS_tkill SYS_truncate SYS_umask SYS_umount2 SYS_uname SYS_unlinkat SYS_unshare
+    SYS_userfaultfd SYS_utimensat SYS_vhangup SYS_vmsplice SYS_wait4 SYS_waitid SYS_write
+    SYS_writev
+}
+
+#[cfg(target_arch = "x86_64")]
+syscall_table! { ARCH:
+    SYS__sysctl SYS_access SYS_afs_syscall SYS_alarm SYS_arch_prctl SYS_chmod SYS_chown
+    SYS_creat SYS_dup2 SYS_epoll_create SYS_epoll_ctl_old SYS_epoll_wait SYS_epoll_wait_old
+    SYS_eventfd SYS_fadvise64 SYS_fchmodat2 SYS_fork SYS_futimesat SYS_get_thread_area
+    SYS_getdents SYS_getpgrp SYS_getpmsg SYS_getrlimit SYS_inotify_init SYS_ioperm SYS_iopl
+    SYS_lchown SYS_link SYS_lstat SYS_mkdir SYS_mknod SYS_modify_ldt SYS_open SYS_pause
+    SYS_pipe SYS_poll SYS_putpmsg SYS_readlink SYS_rename SYS_renameat SYS_rmdir
+    SYS_security SYS_select SYS_sendfile SYS_set_thread_area SYS_setrlimit SYS_signalfd
+    SYS_stat SYS_symlink SYS_sync_file_range SYS_sysfs SYS_time SYS_tuxcall SYS_unlink
+    SYS_uselib SYS_ustat SYS_utime SYS_utimes SYS_vfork SYS_vserver
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+const ARCH: &[(&str, c_long)] = &[];
+
+/// Return the number of the system call `name` on the current architecture,
+/// e.g. `syscall_number("getpid")`
+pub fn syscall_number(name: &str) -> Option<c_long> {
+    COMMON
+        .iter()
+        .chain(ARCH)
+        .find(|(n, _)| &n[4..] == name)
+        .map(|&(_, nr)| nr)
+}
+
+/// Return the name of the system call with number `nr` on the current
+/// architecture
+pub fn syscall_name(nr: c_long) -> Option<&'static str> {
+    COMMON
+        .iter()
+        .chain(ARCH)
+        .find(|&&(_, n)| n == nr)
+        .map(|(name, _)| &name[4..])
+}
+
+/// Action taken by a seccomp filter for a matching system call
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// Execute the system call
+    Allow,
+    /// Fail the system call with the given `errno`
+    Errno(c_int),
+    /// Kill the whole process, as if by an uncatchable `SIGSYS`
+    KillProcess,
+    /// Kill the calling thread only
+    KillThread,
+    /// Send `SIGSYS` to the calling thread, with `si_errno` set to the data
+    Trap(u16),
+    /// Notify a `ptrace` tracer, passing the data as event message
+    Trace(u16),
+    /// Execute the system call after logging it
+    Log,
+    /// Forward the system call to the user-space supervisor holding the
+    /// filter's listener
+    UserNotif,
+}
+
+impl Action {
+    /// Return the `SECCOMP_RET_*` value for the action
+    pub fn to_raw(self) -> u32 {
+        match self {
+            Action::Allow => libc::SECCOMP_RET_ALLOW,
+            Action::Errno(errno) => {
+                libc::SECCOMP_RET_ERRNO | (errno as u32 & libc::SECCOMP_RET_DATA)
+            }
+            Action::KillProcess => libc::SECCOMP_RET_KILL_PROCESS,
+            Action::KillThread => libc::SECCOMP_RET_KILL_THREAD,
+            Action::Trap(data) => libc::SECCOMP_RET_TRAP | data as u32,
+            Action::Trace(data) => libc::SECCOMP_RET_TRACE | data as u32,
+            Action::Log => libc::SECCOMP_RET_LOG,
+            Action::UserNotif => libc::SECCOMP_RET_USER_NOTIF,
+        }
+    }
+
+    /// Decode a `SECCOMP_RET_*` value, or return `None` for an unknown action
+    pub fn from_raw(ret: u32) -> Option<Action> {
+        let data = (ret & libc::SECCOMP_RET_DATA) as u16;
+
+        Some(match ret & libc::SECCOMP_RET_ACTION_FULL {
+            libc::SECCOMP_RET_ALLOW => Action::Allow,
+            libc::SECCOMP_RET_ERRNO => Action::Errno(data as c_int),
+            libc::SECCOMP_RET_KILL_PROCESS => Action::KillProcess,
+            libc::SECCOMP_RET_KILL_THREAD => Action::KillThread,
+            libc::SECCOMP_RET_TRAP => Action::Trap(data),
+            libc::SECCOMP_RET_TRACE => Action::Trace(data),
+            libc::SECCOMP_RET_LOG => Action::Log,
+            libc::SECCOMP_RET_USER_NOTIF => Action::UserNotif,
+            _ => return None,
+        })
+    }
+
+    /// Check whether the running kernel supports the action
+    pub fn available(self) -> Result<bool> {
+        let action = self.to_raw() & libc::SECCOMP_RET_ACTION_FULL;
+
+        match syscall!(syscall(
+            libc::SYS_seccomp,
+            SECCOMP_GET_ACTION_AVAIL,
+            0,
+            &action as *const u32
+        )) {
+            Ok(_) => Ok(true),
+            Err(Error::Syscall(err)) if err.raw_os_error() == Some(libc::EOPNOTSUPP) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Allow => write!(f, "ALLOW"),
+            Action::Errno(errno) => write!(f, "ERRNO({})", errno),
+            Action::KillProcess => write!(f, "KILL_PROCESS"),
+            Action::KillThread => write!(f, "KILL_THREAD"),
+            Action::Trap(data) => write!(f, "TRAP({})", data),
+            Action::Trace(data) => write!(f, "TRACE({})", data),
+            Action::Log => write!(f, "LOG"),
+            Action::UserNotif => write!(f, "USER_NOTIF"),
+        }
+    }
+}
+
+/// Comparison of a 64-bit system call argument against a constant. All
+/// comparisons are unsigned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CmpOp {
+    /// `arg == value`
+    Eq(u64),
+    /// `arg != value`
+    Ne(u64),
+    /// `arg < value`
+    Lt(u64),
+    /// `arg <= value`
+    Le(u64),
+    /// `arg > value`
+    Gt(u64),
+    /// `arg >= value`
+    Ge(u64),
+    /// `arg & mask == value`
+    MaskedEq { mask: u64, value: u64 },
+}
+
+/// A filter rule, matching a system call and optionally its arguments
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rule {
+    nr: c_long,
+    args: Vec<(u8, CmpOp)>,
+    action: Action,
+}
+
+impl Rule {
+    /// Rule for the system call with number `nr`
+    pub fn new(nr: c_long, action: Action) -> Rule {
+        Rule {
+            nr,
+            args: Vec::new(),
+            action,
+        }
+    }
+
+    /// Rule for the system call `name`, e.g. `"openat"`
+    pub fn named(name: &str, action: Action) -> Result<Rule> {
+        let nr = syscall_number(name)
+            .ok_or_else(|| Error::Other(format!("unknown system call {}", name)))?;
+
+        Ok(Rule::new(nr, action))
+    }
+
+    /// Only match if argument `index`, counting from 0, satisfies `op`.
+    /// Multiple comparisons must all match.
+    pub fn arg(&mut self, index: u8, op: CmpOp) -> &mut Rule {
+        self.args.push((index, op));
+        self
+    }
+}
+
+/// Builder for a seccomp filter.
+///
+/// Rules are checked in the order they were added, the first rule that
+/// matches a system call decides its action. System calls without a
+/// matching rule get the default action.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Filter {
+    default: Action,
+    rules: Vec<Rule>,
+}
+
+impl Filter {
+    /// New filter, taking `default` for system calls without a matching rule
+    pub fn new(default: Action) -> Filter {
+        Filter {
+            default,
+            rules: Vec::new(),
+        }
+    }
+
+    /// Add a rule to the filter
+    pub fn rule(&mut self, rule: &Rule) -> &mut Filter {
+        self.rules.push(rule.clone());
+        self
+    }
+
+    /// Compile the filter into a classic BPF program.
+    ///
+    /// The program kills the process for system calls of a foreign
+    /// architecture, so a filter can't be bypassed by e.g. the x32 ABI.
+    pub fn compile(&self) -> Result<Program> {
+        let mut prog = vec![
+            stmt(BPF_LD | BPF_W | BPF_ABS, DATA_ARCH),
+            jump(BPF_JEQ, AUDIT_ARCH, 1, 0),
+            stmt(BPF_RET, libc::SECCOMP_RET_KILL_PROCESS),
+            stmt(BPF_LD | BPF_W | BPF_ABS, DATA_NR),
+        ];
+
+        #[cfg(target_arch = "x86_64")]
+        prog.extend([
+            jump(BPF_JGE, X32_SYSCALL_BIT, 0, 1),
+            stmt(BPF_RET, libc::SECCOMP_RET_KILL_PROCESS),
+        ]);
+
+        // group rules by system call, keeping the order of first appearance
+        let mut syscalls: Vec<c_long> = Vec::new();
+        for rule in &self.rules {
+            if !syscalls.contains(&rule.nr) {
+                syscalls.push(rule.nr);
+            }
+        }
+
+        for nr in syscalls {
+            let mut body = Vec::new();
+            let mut reload = false;
+
+            for rule in self.rules.iter().filter(|r| r.nr == nr) {
+                reload |= !rule.args.is_empty();
+                body.extend(compile_rule(rule)?);
+            }
+
+            if reload {
+                // comparisons clobbered the accumulator
+                body.push(stmt(BPF_LD | BPF_W | BPF_ABS, DATA_NR));
+            }
+
+            match u8::try_from(body.len()) {
+                Ok(len) => prog.push(jump(BPF_JEQ, nr as u32, 0, len)),
+                Err(_) => prog.extend([
+                    jump(BPF_JEQ, nr as u32, 1, 0),
+                    stmt(BPF_JMP | BPF_JA, body.len() as u32),
+                ]),
+            }
+            prog.extend(body);
+        }
+
+        prog.push(stmt(BPF_RET, self.default.to_raw()));
+
+        if prog.len() > BPF_MAXINSNS {
+            return Err(Error::Other(format!(
+                "filter too large: {} instructions",
+                prog.len()
+            )));
+        }
+
+        Ok(Program(prog))
+    }
+
+    /// Compile the filter and install it for the calling process.
+    ///
+    /// See [`Program::install`].
+    pub fn install(&self, flags: FilterFlags) -> Result<()> {
+        self.compile()?.install(flags)
+    }
+
+    /// Compile the filter and install it, returning a [`Listener`].
+    ///
+    /// See [`Program::install_listener`].
+    pub fn install_listener(&self, flags: FilterFlags) -> Result<Listener> {
+        self.compile()?.install_listener(flags)
+    }
+}
+
+libc_bitflags! {
+    /// Flags for installing a seccomp filter
+    pub struct FilterFlags: c_ulong {
+        /// Synchronize all threads of the process to the new filter
+        SECCOMP_FILTER_FLAG_TSYNC;
+        /// Log all actions except `SECCOMP_RET_ALLOW`
+        SECCOMP_FILTER_FLAG_LOG;
+        /// Keep speculative store bypass mitigation disabled
+        SECCOMP_FILTER_FLAG_SPEC_ALLOW;
+        /// Return `ESRCH` instead of a thread ID if `TSYNC` fails
+        SECCOMP_FILTER_FLAG_TSYNC_ESRCH;
+        /// Wait killable for the supervisor to receive a user notification
+        SECCOMP_FILTER_FLAG_WAIT_KILLABLE_RECV;
+    }
+}
+
+/// A compiled classic BPF program, see [`Filter::compile`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Program(Vec<sock_filter>);
+
+impl Program {
+    /// Program from raw instructions, e.g. to disassemble a foreign filter
+    pub fn from_raw(insns: Vec<sock_filter>) -> Program {
+        Program(insns)
+    }
+
+    /// Return the instructions of the program
+    pub fn instructions(&self) -> &[sock_filter] {
+        &self.0
+    }
+
+    /// Install the program as seccomp filter for the calling thread, or the
+    /// whole process with [`FilterFlags::SECCOMP_FILTER_FLAG_TSYNC`].
+    ///
+    /// This sets `PR_SET_NO_NEW_PRIVS` first, so no privileges are needed.
+    /// Installed filters can't be removed, and are inherited by children.
+    pub fn install(&self, flags: FilterFlags) -> Result<()> {
+        let tid = self.load(flags.bits())?;
+
+        if tid > 0 {
+            return Err(Error::Other(format!(
+                "failed to synchronize thread {}",
+                tid
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Install the program like [`Program::install`], returning a
+    /// [`Listener`] for the system calls the filter forwards with
+    /// [`Action::UserNotif`].
+    ///
+    /// Combining this with [`FilterFlags::SECCOMP_FILTER_FLAG_TSYNC`]
+    /// requires [`FilterFlags::SECCOMP_FILTER_FLAG_TSYNC_ESRCH`] as well.
+    pub fn install_listener(&self, flags: FilterFlags) -> Result<Listener> {
+        let fd = self.load(flags.bits() | libc::SECCOMP_FILTER_FLAG_NEW_LISTENER)?;
+
+        Ok(Listener(unsafe { FileDesc::from_raw_fd(fd as RawFd) }))
+    }
+
+    fn load(&self, flags: c_ulong) -> Result<c_long> {
+        let prog = sock_fprog {
+            len: self.0.len() as u16,
+            filter: self.0.as_ptr() as *mut _,
+        };
+
+        set_no_new_privs()?;
+
+        syscall!(syscall(
+            libc::SYS_seccomp,
+            libc::SECCOMP_SET_MODE_FILTER,
+            flags,
+            &prog as *const sock_fprog
+        ))
+    }
+}
+
+impl fmt::Display for Program {
+    /// Disassemble the program, one instruction per line
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // what the accumulator was last loaded from
+        let mut acc = None;
+
+        for (pc, insn) in self.0.iter().enumerate() {
+            let (code, k) = (insn.code as u32, insn.k);
+            let target = |off: u8| pc + 1 + off as usize;
+
+            write!(f, "{:04}: ", pc)?;
+
+            match code & 0x07 {
+                BPF_LD if code & 0xe0 == BPF_ABS && code & 0x18 == BPF_W => {
+                    acc = Some(k);
+                    write!(f, "ld [{}]", k)?;
+                    match k {
+                        DATA_NR => write!(f, "  ; nr")?,
+                        DATA_ARCH => write!(f, "  ; arch")?,
+                        DATA_IP => write!(f, "  ; ip")?,
+                        k if (DATA_ARGS..DATA_ARGS + 48).contains(&k) => {
+                            let half = if (k - DATA_ARGS).is_multiple_of(8) {
+                                "lo"
+                            } else {
+                                "hi"
+                            };
+                            write!(f, "  ; args[{}].{}", (k - DATA_ARGS) / 8, half)?
+                        }
+                        _ => {}
+                    }
+                }
+                BPF_LD if code & 0xe0 == BPF_IMM => {
+                    acc = None;
+                    write!(f, "ld #{:#x}", k)?
+                }
+                BPF_LD if code & 0xe0 == BPF_LEN => {
+                    acc = None;
+                    write!(f, "ld #len")?
+                }
+                BPF_LD if code & 0xe0 == BPF_MEM => {
+                    acc = None;
+                    write!(f, "ld M[{}]", k)?
+                }
+                BPF_LDX => match code & 0xe0 {
+                    BPF_IMM => write!(f, "ldx #{:#x}", k)?,
+                    BPF_MEM => write!(f, "ldx M[{}]", k)?,
+                    BPF_LEN => write!(f, "ldx #len")?,
+                    _ => write!(f, ".word {:#06x}, {:#x}", code, k)?,
+                },
+                BPF_ST => write!(f, "st M[{}]", k)?,
+                BPF_STX => write!(f, "stx M[{}]", k)?,
+                BPF_ALU => {
+                    acc = None;
+                    let op = match code & 0xf0 {
+                        0x00 => "add",
+                        0x10 => "sub",
+                        0x20 => "mul",
+                        0x30 => "div",
+                        0x40 => "or",
+                        BPF_AND => "and",
+                        0x60 => "lsh",
+                        0x70 => "rsh",
+                        0x80 => "neg",
+                        0x90 => "mod",
+                        0xa0 => "xor",
+                        _ => "alu?",
+                    };
+                    match code & 0xf8 {
+                        0x80 => write!(f, "{}", op)?,
+                        c if c & BPF_X != 0 => write!(f, "{} x", op)?,
+                        _ => write!(f, "{} #{:#x}", op, k)?,
+                    }
+                }
+                BPF_JMP if code & 0xf0 == BPF_JA => write!(f, "ja {:04}", pc + 1 + k as usize)?,
+                BPF_JMP => {
+                    let op = match code & 0xf0 {
+                        BPF_JEQ => "jeq",
+                        BPF_JGT => "jgt",
+                        BPF_JGE => "jge",
+                        BPF_JSET => "jset",
+                        _ => "jmp?",
+                    };
+                    let src = if code & BPF_X != 0 {
+                        "x".to_string()
+                    } else {
+                        format!("#{:#x}", k)
+                    };
+                    write!(
+                        f,
+                        "{} {}, {:04}, {:04}",
+                        op,
+                        src,
+                        target(insn.jt),
+                        target(insn.jf)
+                    )?;
+                    if code & 0xf0 == BPF_JEQ && code & BPF_X == 0 {
+                        match acc {
+                            Some(DATA_NR) => {
+                                if let Some(name) = syscall_name(k as c_long) {
+                                    write!(f, "  ; {}", name)?
+                                }
+                            }
+                            Some(DATA_ARCH) if k == AUDIT_ARCH => write!(f, "  ; native")?,
+                            _ => {}
+                        }
+                    }
+                }
+                BPF_RET if code & 0x18 == BPF_A => write!(f, "ret a")?,
+                BPF_RET => match Action::from_raw(k) {
+                    Some(action) => write!(f, "ret {}", action)?,
+                    None => write!(f, "ret #{:#x}", k)?,
+                },
+                BPF_MISC if code == BPF_MISC => write!(f, "tax")?,
+                BPF_MISC if code == BPF_MISC | 0x80 => write!(f, "txa")?,
+                _ => write!(f, ".word {:#06x}, {:#x}", code, k)?,
+            }
+
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A system call forwarded to the supervisor by [`Action::UserNotif`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Notification {
+    /// Cookie identifying the notification
+    pub id: u64,
+    /// Thread ID of the caller, `0` if it is not visible in our PID
+    /// namespace
+    pub pid: libc::pid_t,
+    /// System call number
+    pub nr: c_long,
+    /// Audit architecture of the system call
+    pub arch: u32,
+    /// Instruction pointer of the caller
+    pub ip: u64,
+    /// System call arguments
+    pub args: [u64; 6],
+}
+
+impl Notification {
+    /// Read memory of the caller at `addr` into `buf`, e.g. a buffer passed
+    /// as system call argument. Return the number of bytes read.
+    ///
+    /// The caller may have been killed and its PID reused meanwhile, so
+    /// check [`Listener::id_valid`] before trusting the data.
+    pub fn read_memory(&self, addr: u64, buf: &mut [u8]) -> Result<usize> {
+        let remote = RemoteIoVec::new(addr as usize, buf.len());
+
+        read_process_memory(self.pid, &[remote], &mut [IoSliceMut::new(buf)])
+    }
+
+    /// Read a NUL terminated string of at most `max_len` bytes from memory
+    /// of the caller at `addr`, e.g. a path argument
+    ///
+    /// See [`Notification::read_memory`] for the caveats.
+    pub fn read_cstring(&self, addr: u64, max_len: usize) -> Result<CString> {
+        const PAGE: u64 = 4096;
+
+        let mut data = Vec::new();
+        let mut addr = addr;
+
+        while data.len() <= max_len {
+            // don't read across a page boundary, the next page may be unmapped
+            let mut chunk = vec![0u8; (PAGE - addr % PAGE) as usize];
+            let n = self.read_memory(addr, &mut chunk)?;
+
+            if n == 0 {
+                return Err(io::Error::from_raw_os_error(libc::EFAULT).into());
+            }
+
+            if let Some(end) = chunk[..n].iter().position(|&b| b == 0) {
+                data.extend_from_slice(&chunk[..end]);
+                break;
+            }
+
+            data.extend_from_slice(&chunk[..n]);
+            addr += n as u64;
+        }
+
+        if data.len() > max_len {
+            return Err(io::Error::from_raw_os_error(libc::ENAMETOOLONG).into());
+        }
+
+        Ok(CString::new(data)?)
+    }
+}
+
+/// Response to a [`Notification`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Response {
+    /// Let the system call return the given value
+    Value(i64),
+    /// Fail the system call with the given `errno`
+    Errno(c_int),
+    /// Execute the system call in the kernel as if the filter allowed it.
+    ///
+    /// This is not a security mechanism: the caller may have changed the
+    /// memory the arguments point to since the supervisor inspected it.
+    Continue,
+}
+
+libc_bitflags! {
+    /// Flags for [`Listener::add_fd`]
+    pub struct AddFdFlags: c_ulong {
+        /// Replace the descriptor with the given number in the caller
+        SECCOMP_ADDFD_FLAG_SETFD;
+        /// Add the descriptor and respond with its number atomically
+        SECCOMP_ADDFD_FLAG_SEND;
+    }
+}
+
+/// Listener for the system calls forwarded by a seccomp filter, see
+/// [`Program::install_listener`].
+///
+/// The listener becomes readable when a notification is pending.
+#[derive(Debug)]
+pub struct Listener(FileDesc);
+
+impl Listener {
+    /// Receive the next notification
+    ///
+    /// This function will **block** until a notification is pending. It
+    /// fails with `ENOENT` if the caller was interrupted by a signal after
+    /// the listener became readable.
+    pub fn receive(&self) -> Result<Notification> {
+        let mut notif: libc::seccomp_notif = unsafe { mem::zeroed() };
+
+        syscall!(ioctl(
+            self.0.as_raw_fd(),
+            SECCOMP_IOCTL_NOTIF_RECV,
+            &mut notif as *mut libc::seccomp_notif
+        ))?;
+
+        Ok(Notification {
+            id: notif.id,
+            pid: notif.pid as libc::pid_t,
+            nr: notif.data.nr as c_long,
+            arch: notif.data.arch,
+            ip: notif.data.instruction_pointer,
+            args: notif.data.args,
+        })
+    }
+
+    /// Check whether the notification `id` is still pending, i.e. the caller
+    /// is still waiting for the response
+    pub fn id_valid(&self, id: u64) -> Result<bool> {
+        match syscall!(ioctl(
+            self.0.as_raw_fd(),
+            SECCOMP_IOCTL_NOTIF_ID_VALID,
+            &id as *const u64
+        )) {
+            Ok(_) => Ok(true),
+            Err(Error::Syscall(err)) if err.raw_os_error() == Some(libc::ENOENT) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Respond to the notification `id`
+    pub fn respond(&self, id: u64, response: Response) -> Result<()> {
+        let mut resp = libc::seccomp_notif_resp {
+            id,
+            val: 0,
+            error: 0,
+            flags: 0,
+        };
+
+        match response {
+            Response::Value(val) => resp.val = val,
+            Response::Errno(errno) => resp.error = -errno,
+            Response::Continue => resp.flags = libc::SECCOMP_USER_NOTIF_FLAG_CONTINUE as u32,
+        }
+
+        syscall!(ioctl(
+            self.0.as_raw_fd(),
+            SECCOMP_IOCTL_NOTIF_SEND,
+            &mut resp as *mut libc::seccomp_notif_resp
+        ))
+        .map(|_| ())
+    }
+
+    /// Install a duplicate of our descriptor `fd` in the caller of the
+    /// notification `id`. Return the descriptor number in the caller.
+    ///
+    /// With [`AddFdFlags::SECCOMP_ADDFD_FLAG_SETFD`], the descriptor number
+    /// `target` is used, replacing an open descriptor. `newfd_flags` may only
+    /// contain [`OFlags::O_CLOEXEC`].
+    pub fn add_fd(
+        &self,
+        id: u64,
+        fd: RawFd,
+        target: RawFd,
+        flags: AddFdFlags,
+        newfd_flags: OFlags,
+    ) -> Result<RawFd> {
+        let addfd = libc::seccomp_notif_addfd {
+            id,
+            flags: flags.bits() as u32,
+            srcfd: fd as u32,
+            newfd: target as u32,
+            newfd_flags: newfd_flags.bits() as u32,
+        };
+
+        syscall!(ioctl(
+            self.0.as_raw_fd(),
+            SECCOMP_IOCTL_NOTIF_ADDFD,
+            &addfd as *const libc::seccomp_notif_addfd
+        ))
+    }
+}
+
+impl FromRawFd for Listener {
+    /// Return a [`Listener`] from a descriptor received from the process
+    /// that installed the filter, e.g. via `SCM_RIGHTS`
+    ///
+    /// ### Safety
+    ///
+    /// `fd` must be an open seccomp listener descriptor
+    unsafe fn from_raw_fd(fd: RawFd) -> Listener {
+        Listener(unsafe { FileDesc::from_raw_fd(fd) })
+    }
+}
+
+impl AsRawFd for Listener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl AsFd for Listener {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+impl event::Source for Listener {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> std::io::Result<()> {
+        SourceFd(&self.0.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> std::io::Result<()> {
+        SourceFd(&self.0.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> std::io::Result<()> {
+        SourceFd(&self.0.as_raw_fd()).deregister(registry)
+    }
+}
+
+// classic BPF opcodes, see `linux/bpf_common.h`
+const BPF_LD: u32 = 0x00;
+const BPF_LDX: u32 = 0x01;
+const BPF_ST: u32 = 0x02;
+const BPF_STX: u32 = 0x03;
+const BPF_ALU: u32 = 0x04;
+const BPF_JMP: u32 = 0x05;
+const BPF_RET: u32 = 0x06;
+const BPF_MISC: u32 = 0x07;
+const BPF_W: u32 = 0x00;
+const BPF_IMM: u32 = 0x00;
+const BPF_ABS: u32 = 0x20;
+const BPF_MEM: u32 = 0x60;
+const BPF_LEN: u32 = 0x80;
+const BPF_AND: u32 = 0x50;
+const BPF_JA: u32 = 0x00;
+const BPF_JEQ: u32 = 0x10;
+const BPF_JGT: u32 = 0x20;
+const BPF_JGE: u32 = 0x30;
+const BPF_JSET: u32 = 0x40;
+const BPF_X: u32 = 0x08;
+const BPF_A: u32 = 0x10;
+
+fn stmt(code: u32, k: u32) -> sock_filter {
+    sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+/// Conditional jump with immediate operand
+fn jump(op: u32, k: u32, jt: u8, jf: u8) -> sock_filter {
+    sock_filter {
+        code: (BPF_JMP | op) as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+/// Jump target within a rule, resolved once the length of the rule is known
+#[derive(Clone, Copy)]
+enum Target {
+    /// Continue with the next instruction
+    Next,
+    /// Skip the given number of instructions
+    Skip(u8),
+    /// Skip to the end of the rule, i.e. the rule doesn't match
+    Fail,
+}
+
+/// Compile `rule` into its argument comparisons followed by the return of
+/// its action. If a comparison fails, the code jumps past the return.
+fn compile_rule(rule: &Rule) -> Result<Vec<sock_filter>> {
+    let mut insns: Vec<(sock_filter, Target, Target)> = Vec::new();
+
+    let load = |insns: &mut Vec<_>, off: u32| {
+        insns.push((
+            stmt(BPF_LD | BPF_W | BPF_ABS, off),
+            Target::Next,
+            Target::Next,
+        ))
+    };
+
+    for &(index, op) in &rule.args {
+        if index > 5 {
+            return Err(Error::Other(format!("invalid argument index {}", index)));
+        }
+
+        // 64-bit arguments are compared as high and low word
+        let lo = DATA_ARGS + 8 * index as u32;
+        let (lo, hi) = if cfg!(target_endian = "little") {
+            (lo, lo + 4)
+        } else {
+            (lo + 4, lo)
+        };
+        let split = |v: u64| ((v >> 32) as u32, v as u32);
+        let cmp = |op, k, jt, jf| (jump(op, k, 0, 0), jt, jf);
+
+        use Target::{Fail, Next, Skip};
+
+        match op {
+            CmpOp::Eq(v) => {
+                let (vhi, vlo) = split(v);
+                load(&mut insns, hi);
+                insns.push(cmp(BPF_JEQ, vhi, Next, Fail));
+                load(&mut insns, lo);
+                insns.push(cmp(BPF_JEQ, vlo, Next, Fail));
+            }
+            CmpOp::Ne(v) => {
+                let (vhi, vlo) = split(v);
+                load(&mut insns, hi);
+                insns.push(cmp(BPF_JEQ, vhi, Next, Skip(2)));
+                load(&mut insns, lo);
+                insns.push(cmp(BPF_JEQ, vlo, Fail, Next));
+            }
+            CmpOp::Gt(v) | CmpOp::Ge(v) => {
+                let (vhi, vlo) = split(v);
+                let op = if let CmpOp::Gt(_) = op {
+                    BPF_JGT
+                } else {
+                    BPF_JGE
+                };
+                load(&mut insns, hi);
+                insns.push(cmp(BPF_JGT, vhi, Skip(3), Next));
+                insns.push(cmp(BPF_JEQ, vhi, Next, Fail));
+                load(&mut insns, lo);
+                insns.push(cmp(op, vlo, Next, Fail));
+            }
+            CmpOp::Lt(v) | CmpOp::Le(v) => {
+                let (vhi, vlo) = split(v);
+                // `arg < v` is `!(arg >= v)`, `arg <= v` is `!(arg > v)`
+                let op = if let CmpOp::Lt(_) = op {
+                    BPF_JGE
+                } else {
+                    BPF_JGT
+                };
+                load(&mut insns, hi);
+                insns.push(cmp(BPF_JGT, vhi, Fail, Next));
+                insns.push(cmp(BPF_JEQ, vhi, Next, Skip(2)));
+                load(&mut insns, lo);
+                insns.push(cmp(op, vlo, Fail, Next));
+            }
+            CmpOp::MaskedEq { mask, value } => {
+                let (mhi, mlo) = split(mask);
+                let (vhi, vlo) = split(value & mask);
+                load(&mut insns, hi);
+                insns.push((stmt(BPF_ALU | BPF_AND, mhi), Next, Next));
+                insns.push(cmp(BPF_JEQ, vhi, Next, Fail));
+                load(&mut insns, lo);
+                insns.push((stmt(BPF_ALU | BPF_AND, mlo), Next, Next));
+                insns.push(cmp(BPF_JEQ, vlo, Next, Fail));
+            }
+        }
+    }
+
+    insns.push((
+        stmt(BPF_RET, rule.action.to_raw()),
+        Target::Next,
+        Target::Next,
+    ));
+
+    let len = insns.len();
+    insns
+        .into_iter()
+        .enumerate()
+        .map(|(pc, (mut insn, jt, jf))| {
+            let resolve = |t| match t {
+                Target::Next => Ok(0),
+                Target::Skip(n) => Ok(n),
+                Target::Fail => u8::try_from(len - pc - 1)
+                    .map_err(|_| Error::from("too many argument comparisons in rule")),
+            };
+            insn.jt = resolve(jt)?;
+            insn.jf = resolve(jf)?;
+            Ok(insn)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::CString,
+        fs::File,
+        io::{Read, Seek, Write},
+        os::unix::{ffi::OsStrExt, io::AsRawFd, prelude::FromRawFd},
+        process::exit,
+        sync::mpsc,
+        thread,
+        time::Duration,
+    };
+
+    use anyhow::{Result, bail, ensure};
+    use libc::{c_long, sock_filter};
+    use mio::{Events, Interest, Poll, Token};
+
+    use super::{
+        AUDIT_ARCH, Action, AddFdFlags, CmpOp, DATA_ARCH, DATA_ARGS, DATA_NR, Filter, FilterFlags,
+        Listener, Program, Response, Rule, syscall_name, syscall_number,
+    };
+    use crate::{OFlags, Signal, WaitStatus, test_util::in_child, wait};
+
+    /// Run `prog` on a `seccomp_data` for system call `nr`, the way the
+    /// kernel does
+    fn run(prog: &Program, arch: u32, nr: c_long, args: [u64; 6]) -> u32 {
+        let mut data = [0u8; 64];
+        data[DATA_NR as usize..][..4].copy_from_slice(&(nr as u32).to_ne_bytes());
+        data[DATA_ARCH as usize..][..4].copy_from_slice(&arch.to_ne_bytes());
+        for (i, arg) in args.iter().enumerate() {
+            data[DATA_ARGS as usize + 8 * i..][..8].copy_from_slice(&arg.to_ne_bytes());
+        }
+
+        let insns: &[sock_filter] = prog.instructions();
+        let (mut pc, mut acc) = (0, 0u32);
+
+        loop {
+            let insn = insns[pc];
+            let cond = |taken: bool| if taken { insn.jt } else { insn.jf } as usize;
+            pc += 1;
+            match insn.code {
+                0x20 => {
+                    let k = insn.k as usize;
+                    acc = u32::from_ne_bytes(data[k..k + 4].try_into().unwrap())
+                }
+                0x54 => acc &= insn.k,
+                0x05 => pc += insn.k as usize,
+                0x15 => pc += cond(acc == insn.k),
+                0x25 => pc += cond(acc > insn.k),
+                0x35 => pc += cond(acc >= insn.k),
+                0x06 => return insn.k,
+                code => panic!("unexpected instruction {:#x}", code),
+            }
+        }
+    }
+
+    #[test]
+    fn seccomp_names() {
+        assert_eq!(syscall_number("getpid"), Some(libc::SYS_getpid));
+        assert_eq!(syscall_number("openat"), Some(libc::SYS_openat));
+        assert_eq!(syscall_number("no_such_call"), None);
+        assert_eq!(syscall_name(libc::SYS_write), Some("write"));
+        assert_eq!(syscall_name(-1), None);
+
+        assert!(Rule::named("no_such_call", Action::Allow).is_err());
+    }
+
+    #[test]
+    fn seccomp_actions() {
+        for action in [
+            Action::Allow,
+            Action::Errno(libc::EPERM),
+            Action::KillProcess,
+            Action::KillThread,
+            Action::Trap(7),
+            Action::Trace(8),
+            Action::Log,
+            Action::UserNotif,
+        ] {
+            assert_eq!(Action::from_raw(action.to_raw()), Some(action));
+        }
+
+        assert_eq!(Action::Errno(libc::EACCES).to_string(), "ERRNO(13)");
+        assert_eq!(Action::from_raw(0x1234_0000), None);
+    }
+
+    #[test]
+    fn seccomp_compile() -> Result<()> {
+        let values = [
+            0,
+            1,
+            5,
+            0xffff_ffff,
+            0x1_0000_0000,
+            0x1_0000_0001,
+            0x2_0000_0000,
+            u64::MAX,
+        ];
+
+        for v in values {
+            let ops = [
+                CmpOp::Eq(v),
+                CmpOp::Ne(v),
+                CmpOp::Lt(v),
+                CmpOp::Le(v),
+                CmpOp::Gt(v),
+                CmpOp::Ge(v),
+                CmpOp::MaskedEq {
+                    mask: 0xff_0000_00ff,
+                    value: v,
+                },
+            ];
+
+            for op in ops {
+                let prog = Filter::new(Action::Allow)
+                    .rule(Rule::new(libc::SYS_read, Action::Errno(1)).arg(2, op))
+                    .compile()?;
+
+                for arg in values {
+                    let expected = match op {
+                        CmpOp::Eq(v) => arg == v,
+                        CmpOp::Ne(v) => arg != v,
+                        CmpOp::Lt(v) => arg < v,
+                        CmpOp::Le(v) => arg <= v,
+                        CmpOp::Gt(v) => arg > v,
+                        CmpOp::Ge(v) => arg >= v,
+                        CmpOp::MaskedEq { mask, value } => arg & mask == value & mask,
+                    };
+                    let ret = run(
+                        &prog,
+                        super::AUDIT_ARCH,
+                        libc::SYS_read,
+                        [0, 0, arg, 0, 0, 0],
+                    );
+
+                    ensure!(
+                        ret == Action::Errno(1).to_raw() || !expected,
+                        "{:?} {:#x}",
+                        op,
+                        arg
+                    );
+                    ensure!(
+                        ret == Action::Allow.to_raw() || expected,
+                        "{:?} {:#x}",
+                        op,
+                        arg
+                    );
+                }
+            }
+        }
+
+        // first matching rule wins, other system calls get the default
+        let prog = Filter::new(Action::Log)
+            .rule(Rule::new(libc::SYS_write, Action::Errno(2)).arg(0, CmpOp::Eq(1)))
+            .rule(&Rule::new(libc::SYS_close, Action::Allow))
+            .rule(Rule::new(libc::SYS_write, Action::Errno(3)).arg(0, CmpOp::Le(2)))
+            .rule(&Rule::new(libc::SYS_write, Action::KillProcess))
+            .compile()?;
+
+        let arch = super::AUDIT_ARCH;
+        assert_eq!(
+            run(&prog, arch, libc::SYS_write, [1; 6]),
+            Action::Errno(2).to_raw()
+        );
+        assert_eq!(
+            run(&prog, arch, libc::SYS_write, [2; 6]),
+            Action::Errno(3).to_raw()
+        );
+        assert_eq!(
+            run(&prog, arch, libc::SYS_write, [3; 6]),
+            Action::KillProcess.to_raw()
+        );
+        assert_eq!(
+            run(&prog, arch, libc::SYS_close, [3; 6]),
+            Action::Allow.to_raw()
+        );
+        assert_eq!(
+            run(&prog, arch, libc::SYS_read, [3; 6]),
+            Action::Log.to_raw()
+        );
+
+        // foreign architectures are killed
+        assert_eq!(
+            run(&prog, 0x4000_0003, libc::SYS_read, [0; 6]),
+            Action::KillProcess.to_raw()
+        );
+        #[cfg(target_arch = "x86_64")]
+        assert_eq!(
+            run(&prog, arch, libc::SYS_read | 0x4000_0000, [0; 6]),
+            Action::KillProcess.to_raw()
+        );
+
+        assert!(
+            Filter::new(Action::Allow)
+                .rule(Rule::new(libc::SYS_read, Action::Log).arg(6, CmpOp::Eq(0)))
+                .compile()
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn seccomp_disassemble() -> Result<()> {
+        let prog = Filter::new(Action::Allow)
+            .rule(&Rule::named("getpid", Action::Errno(libc::EPERM))?)
+            .rule(Rule::named("close", Action::Trap(1))?.arg(0, CmpOp::Eq(0x1_0000_0002)))
+            .compile()?;
+
+        let text = prog.to_string();
+        let lines = text.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), prog.instructions().len());
+        assert_eq!(lines[0], "0000: ld [4]  ; arch");
+        assert!(lines[1].ends_with("0003, 0002  ; native"));
+        assert_eq!(lines[2], "0002: ret KILL_PROCESS");
+        assert_eq!(lines[3], "0003: ld [0]  ; nr");
+        assert!(text.contains(&format!("jeq #{:#x}", libc::SYS_getpid)));
+        assert!(text.contains("  ; getpid\n"));
+        assert!(text.contains("ret ERRNO(1)\n"));
+        assert!(text.contains("  ; close\n"));
+        assert!(text.contains("ld [20]  ; args[0].hi\n"));
+        assert!(text.contains("jeq #0x1, "));
+        assert!(text.contains("ld [16]  ; args[0].lo\n"));
+        assert!(text.contains("jeq #0x2, "));
+        assert!(text.contains("ret TRAP(1)\n"));
+        assert!(lines.last().unwrap().ends_with("ret ALLOW"));
+
+        let raw = Program::from_raw(vec![sock_filter {
+            code: 0xff,
+            jt: 0,
+            jf: 0,
+            k: 1,
+        }]);
+        assert_eq!(raw.to_string(), "0000: .word 0x00ff, 0x1\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn seccomp_available() -> Result<()> {
+        assert!(Action::Allow.available()?);
+        assert!(Action::Errno(libc::EPERM).available()?);
+        assert!(Action::KillProcess.available()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn seccomp_install() -> Result<()> {
+        in_child(|| {
+            Filter::new(Action::Allow)
+                .rule(&Rule::named("getppid", Action::Errno(libc::EPERM))?)
+                .rule(Rule::named("close", Action::Errno(libc::E2BIG))?.arg(0, CmpOp::Eq(1000)))
+                .rule(
+                    Rule::named("lseek", Action::Errno(libc::EOVERFLOW))?
+                        .arg(1, CmpOp::Gt(1 << 32)),
+                )
+                .install(FilterFlags::SECCOMP_FILTER_FLAG_TSYNC)?;
+
+            let err = syscall!(syscall(libc::SYS_getppid))
+                .unwrap_err()
+                .to_string();
+            ensure!(err.contains("Operation not permitted"), "{}", err);
+
+            let err = syscall!(close(1000)).unwrap_err().to_string();
+            ensure!(err.contains("Argument list too long"), "{}", err);
+            let err = syscall!(close(1001)).unwrap_err().to_string();
+            ensure!(err.contains("Bad file descriptor"), "{}", err);
+
+            let file = tempfile::tempfile()?;
+            let fd = file.as_raw_fd();
+            ensure!(syscall!(lseek(fd, 1 << 32, libc::SEEK_SET))? == 1 << 32);
+            let err = syscall!(lseek(fd, 1 << 33, libc::SEEK_SET))
+                .unwrap_err()
+                .to_string();
+            ensure!(err.contains("Value too large"), "{}", err);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn seccomp_kill() -> Result<()> {
+        for action in [Action::KillProcess, Action::Trap(0)] {
+            let pid = syscall!(fork())?;
+
+            if pid == 0 {
+                let core = libc::rlimit {
+                    rlim_cur: 0,
+                    rlim_max: 0,
+                };
+                let res = syscall!(setrlimit(libc::RLIMIT_CORE, &core)).and_then(|_| {
+                    Filter::new(Action::Allow)
+                        .rule(&Rule::new(libc::SYS_getppid, action))
+                        .install(FilterFlags::empty())
+                });
+                if res.is_ok() {
+                    let _ = syscall!(getppid());
+                }
+                exit(1);
+            }
+
+            assert!(matches!(
+                wait(pid)?,
+                WaitStatus::Signaled(p, Signal::SIGSYS, _) if p == pid
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Handle the notifications of the `seccomp_notify` test
+    fn supervise(mut listener: Listener) -> Result<()> {
+        let mut poll = Poll::new()?;
+        let mut events = Events::with_capacity(1);
+
+        poll.registry()
+            .register(&mut listener, Token(0), Interest::READABLE)?;
+
+        for _ in 0..4 {
+            poll.poll(&mut events, Some(Duration::from_secs(5)))?;
+            ensure!(!events.is_empty(), "no notification");
+
+            let req = listener.receive()?;
+            ensure!(req.arch == AUDIT_ARCH);
+
+            match syscall_name(req.nr) {
+                Some("getppid") => listener.respond(req.id, Response::Value(4242))?,
+                Some("mkdirat") => {
+                    let path = req.read_cstring(req.args[1], libc::PATH_MAX as usize)?;
+                    ensure!(listener.id_valid(req.id)?);
+
+                    let resp = if path.as_bytes() == b"/forbidden" {
+                        Response::Errno(libc::EACCES)
+                    } else {
+                        Response::Continue
+                    };
+                    listener.respond(req.id, resp)?;
+                }
+                Some("openat") => {
+                    let path = req.read_cstring(req.args[1], libc::PATH_MAX as usize)?;
+                    ensure!(path.as_bytes() == b"/magic", "{:?}", path);
+
+                    let mut file = tempfile::tempfile()?;
+                    file.write_all(b"emulated")?;
+                    file.rewind()?;
+
+                    listener.add_fd(
+                        req.id,
+                        file.as_raw_fd(),
+                        0,
+                        AddFdFlags::SECCOMP_ADDFD_FLAG_SEND,
+                        OFlags::O_CLOEXEC,
+                    )?;
+                }
+                name => bail!("unexpected system call {:?}", name),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn seccomp_notify() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let sub = dir.path().join("sub");
+
+        in_child(|| {
+            let (tx, rx) = mpsc::channel();
+
+            // the filter applies to the calling thread only, so the
+            // supervisor isn't affected by it
+            let supervisor = thread::spawn(move || supervise(rx.recv()?));
+
+            let listener = Filter::new(Action::Allow)
+                .rule(&Rule::named("getppid", Action::UserNotif)?)
+                .rule(&Rule::named("mkdirat", Action::UserNotif)?)
+                .rule(&Rule::named("openat", Action::UserNotif)?)
+                .install_listener(FilterFlags::empty())?;
+            tx.send(listener)?;
+
+            ensure!(syscall!(syscall(libc::SYS_getppid))? == 4242);
+
+            let err = syscall!(mkdirat(libc::AT_FDCWD, c"/forbidden".as_ptr(), 0o755))
+                .unwrap_err()
+                .to_string();
+            ensure!(err.contains("Permission denied"), "{}", err);
+
+            let path = CString::new(sub.as_os_str().as_bytes())?;
+            syscall!(mkdirat(libc::AT_FDCWD, path.as_ptr(), 0o755))?;
+            ensure!(sub.is_dir());
+
+            let fd = syscall!(openat(libc::AT_FDCWD, c"/magic".as_ptr(), libc::O_RDONLY))?;
+            let mut file = unsafe { File::from_raw_fd(fd) };
+            let mut buf = String::new();
+            file.read_to_string(&mut buf)?;
+            ensure!(buf == "emulated", "{}", buf);
+
+            supervisor.join().unwrap()
+        })?;
+
+        assert!(sub.is_dir());
+
+        Ok(())
+    }
+}
@@ -2,7 +2,15 @@
 //! This file is part of syscall-rs
 //!
 
-use std::{fmt, mem, os::unix::prelude::RawFd, str::FromStr};
+use std::{
+    cmp, fmt, mem,
+    num::NonZeroUsize,
+    os::unix::prelude::{AsRawFd, RawFd},
+    ptr,
+    ptr::NonNull,
+    str::FromStr,
+    time::{Duration, Instant},
+};
 
 use mio::{
     Interest, Registry, Token,
@@ -10,82 +18,144 @@ use mio::{
     unix::SourceFd,
 };
 
-use crate::{Error, Result, signal};
+use crate::{Error, MapFlags, ProtFlags, Result, libc_bitflags, mmap_anonymous, mprotect, munmap};
 
 /// Operating system signal.
 ///
-/// Note that we would want to use `lib::c_int` as the repr attribute. This is
-/// not (yet) supported, however.
-#[repr(i32)]
+/// Real-time signals are represented by [`Signal::Rt`], created using
+/// [`Signal::rt()`]. Use [`Signal::as_raw()`] to get the signal number.
 #[non_exhaustive]
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum Signal {
     /// Hangup
-    SIGHUP = libc::SIGHUP,
+    SIGHUP,
     /// Interrupt
-    SIGINT = libc::SIGINT,
+    SIGINT,
     /// Quit
-    SIGQUIT = libc::SIGQUIT,
+    SIGQUIT,
     /// Illegal instruction (not reset when caught)
-    SIGILL = libc::SIGILL,
+    SIGILL,
     /// Trace trap (not reset when caught)
-    SIGTRAP = libc::SIGTRAP,
+    SIGTRAP,
     /// Abort
-    SIGABRT = libc::SIGABRT,
+    SIGABRT,
     /// Bus error
-    SIGBUS = libc::SIGBUS,
+    SIGBUS,
     /// Floating point exception
-    SIGFPE = libc::SIGFPE,
+    SIGFPE,
     /// Kill (cannot be caught or ignored)
-    SIGKILL = libc::SIGKILL,
+    SIGKILL,
     /// User defined signal 1
-    SIGUSR1 = libc::SIGUSR1,
+    SIGUSR1,
     /// Segmentation violation
-    SIGSEGV = libc::SIGSEGV,
+    SIGSEGV,
     /// User defined signal 2
-    SIGUSR2 = libc::SIGUSR2,
+    SIGUSR2,
     /// Write on a pipe with no one to read it
-    SIGPIPE = libc::SIGPIPE,
+    SIGPIPE,
     /// Alarm clock
-    SIGALRM = libc::SIGALRM,
+    SIGALRM,
     /// Software termination signal from kill
-    SIGTERM = libc::SIGTERM,
+    SIGTERM,
     /// Stack fault (obsolete)
-    SIGSTKFLT = libc::SIGSTKFLT,
+    SIGSTKFLT,
     /// To parent on child stop or exit
-    SIGCHLD = libc::SIGCHLD,
+    SIGCHLD,
     /// Continue a stopped process
-    SIGCONT = libc::SIGCONT,
+    SIGCONT,
     /// Sendable stop signal not from tty
-    SIGSTOP = libc::SIGSTOP,
+    SIGSTOP,
     /// Stop signal from tty
-    SIGTSTP = libc::SIGTSTP,
+    SIGTSTP,
     /// To readers pgrp upon background tty read
-    SIGTTIN = libc::SIGTTIN,
+    SIGTTIN,
     /// Like TTIN if (tp->t_local&LTOSTOP)
-    SIGTTOU = libc::SIGTTOU,
+    SIGTTOU,
     /// Urgent condition on IO channel
-    SIGURG = libc::SIGURG,
+    SIGURG,
     /// Exceeded CPU time limit
-    SIGXCPU = libc::SIGXCPU,
+    SIGXCPU,
     /// Exceeded file size limit
-    SIGXFSZ = libc::SIGXFSZ,
+    SIGXFSZ,
     /// Virtual time alarm
-    SIGVTALRM = libc::SIGVTALRM,
+    SIGVTALRM,
     /// Profiling time alarm
-    SIGPROF = libc::SIGPROF,
+    SIGPROF,
     /// Window size changes
-    SIGWINCH = libc::SIGWINCH,
+    SIGWINCH,
     /// Input/output possible signal
-    SIGIO = libc::SIGIO,
+    SIGIO,
     /// Power failure imminent.
-    SIGPWR = libc::SIGPWR,
+    SIGPWR,
     /// Bad system call
-    SIGSYS = libc::SIGSYS,
+    SIGSYS,
+    /// Real-time signal `SIGRTMIN + n`
+    Rt(RtSignal),
+}
+
+/// Offset of a real-time [`Signal`] from `SIGRTMIN`
+///
+/// The offset is always within the range of real-time signals supported by
+/// the C library, see [`Signal::rt()`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct RtSignal(u8);
+
+impl RtSignal {
+    /// Return the offset from `SIGRTMIN`
+    pub fn offset(self) -> u8 {
+        self.0
+    }
 }
 
 impl Signal {
+    /// Return the real-time signal `SIGRTMIN + n`, if `n` is within the
+    /// range of real-time signals supported by the C library
+    pub fn rt(n: u8) -> Result<Signal> {
+        (libc::SIGRTMIN() + n as libc::c_int).try_into()
+    }
+
+    /// Return the signal number
+    pub fn as_raw(self) -> libc::c_int {
+        match self {
+            Signal::SIGHUP => libc::SIGHUP,
+            Signal::SIGINT => libc::SIGINT,
+            Signal::SIGQUIT => libc::SIGQUIT,
+            Signal::SIGILL => libc::SIGILL,
+            Signal::SIGTRAP => libc::SIGTRAP,
+            Signal::SIGABRT => libc::SIGABRT,
+            Signal::SIGBUS => libc::SIGBUS,
+            Signal::SIGFPE => libc::SIGFPE,
+            Signal::SIGKILL => libc::SIGKILL,
+            Signal::SIGUSR1 => libc::SIGUSR1,
+            Signal::SIGSEGV => libc::SIGSEGV,
+            Signal::SIGUSR2 => libc::SIGUSR2,
+            Signal::SIGPIPE => libc::SIGPIPE,
+            Signal::SIGALRM => libc::SIGALRM,
+            Signal::SIGTERM => libc::SIGTERM,
+            Signal::SIGSTKFLT => libc::SIGSTKFLT,
+            Signal::SIGCHLD => libc::SIGCHLD,
+            Signal::SIGCONT => libc::SIGCONT,
+            Signal::SIGSTOP => libc::SIGSTOP,
+            Signal::SIGTSTP => libc::SIGTSTP,
+            Signal::SIGTTIN => libc::SIGTTIN,
+            Signal::SIGTTOU => libc::SIGTTOU,
+            Signal::SIGURG => libc::SIGURG,
+            Signal::SIGXCPU => libc::SIGXCPU,
+            Signal::SIGXFSZ => libc::SIGXFSZ,
+            Signal::SIGVTALRM => libc::SIGVTALRM,
+            Signal::SIGPROF => libc::SIGPROF,
+            Signal::SIGWINCH => libc::SIGWINCH,
+            Signal::SIGIO => libc::SIGIO,
+            Signal::SIGPWR => libc::SIGPWR,
+            Signal::SIGSYS => libc::SIGSYS,
+            Signal::Rt(n) => libc::SIGRTMIN() + n.0 as libc::c_int,
+        }
+    }
+
     /// Return signal name
+    ///
+    /// Note that all real-time signals share the name `SIGRT`, use
+    /// [`fmt::Display`] in order to get the full name.
     pub const fn as_str(self) -> &'static str {
         match self {
             Signal::SIGHUP => "SIGHUP",
@@ -119,6 +189,7 @@ impl Signal {
             Signal::SIGIO => "SIGIO",
             Signal::SIGPWR => "SIGPWR",
             Signal::SIGSYS => "SIGSYS",
+            Signal::Rt(_) => "SIGRT",
         }
     }
 }
@@ -127,7 +198,17 @@ impl FromStr for Signal {
     type Err = Error;
 
     /// Parse [`Signal`] from a signal name
+    ///
+    /// Real-time signals are parsed from `SIGRTMIN`, `SIGRTMIN+n`, `SIGRTMAX`
+    /// and `SIGRTMAX-n`
     fn from_str(s: &str) -> Result<Signal> {
+        let invalid = || {
+            Error::Syscall(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "invalid signal",
+            ))
+        };
+
         Ok(match s {
             "SIGHUP" => Signal::SIGHUP,
             "SIGINT" => Signal::SIGINT,
@@ -160,11 +241,18 @@ impl FromStr for Signal {
             "SIGIO" => Signal::SIGIO,
             "SIGPWR" => Signal::SIGPWR,
             "SIGSYS" => Signal::SIGSYS,
+            "SIGRTMIN" => libc::SIGRTMIN().try_into()?,
+            "SIGRTMAX" => libc::SIGRTMAX().try_into()?,
             _ => {
-                return Err(Error::Syscall(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    "invalid signal",
-                )));
+                let signum = if let Some(n) = s.strip_prefix("SIGRTMIN+") {
+                    libc::SIGRTMIN() + n.parse::<libc::c_int>().map_err(|_| invalid())?
+                } else if let Some(n) = s.strip_prefix("SIGRTMAX-") {
+                    libc::SIGRTMAX() - n.parse::<libc::c_int>().map_err(|_| invalid())?
+                } else {
+                    return Err(invalid());
+                };
+
+                signum.try_into()?
             }
         })
     }
@@ -175,17 +263,58 @@ impl TryFrom<libc::c_int> for Signal {
 
     /// Try to convert `signum` into a [`Signal`]
     ///
-    /// This function only supports signal numbering for standard signals
-    /// according to the `signal(7)` man page
+    /// This function supports signal numbering for standard signals according
+    /// to the `signal(7)` man page as well as real-time signals in the range
+    /// `SIGRTMIN..=SIGRTMAX`
     fn try_from(signum: libc::c_int) -> std::result::Result<Self, Self::Error> {
-        if 0 < signum && signum < 32 {
-            Ok(unsafe { mem::transmute::<i32, signal::Signal>(signum) })
-        } else {
-            Err(Error::Syscall(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "invalid signal number",
-            )))
-        }
+        Ok(match signum {
+            libc::SIGHUP => Signal::SIGHUP,
+            libc::SIGINT => Signal::SIGINT,
+            libc::SIGQUIT => Signal::SIGQUIT,
+            libc::SIGILL => Signal::SIGILL,
+            libc::SIGTRAP => Signal::SIGTRAP,
+            libc::SIGABRT => Signal::SIGABRT,
+            libc::SIGBUS => Signal::SIGBUS,
+            libc::SIGFPE => Signal::SIGFPE,
+            libc::SIGKILL => Signal::SIGKILL,
+            libc::SIGUSR1 => Signal::SIGUSR1,
+            libc::SIGSEGV => Signal::SIGSEGV,
+            libc::SIGUSR2 => Signal::SIGUSR2,
+            libc::SIGPIPE => Signal::SIGPIPE,
+            libc::SIGALRM => Signal::SIGALRM,
+            libc::SIGTERM => Signal::SIGTERM,
+            libc::SIGSTKFLT => Signal::SIGSTKFLT,
+            libc::SIGCHLD => Signal::SIGCHLD,
+            libc::SIGCONT => Signal::SIGCONT,
+            libc::SIGSTOP => Signal::SIGSTOP,
+            libc::SIGTSTP => Signal::SIGTSTP,
+            libc::SIGTTIN => Signal::SIGTTIN,
+            libc::SIGTTOU => Signal::SIGTTOU,
+            libc::SIGURG => Signal::SIGURG,
+            libc::SIGXCPU => Signal::SIGXCPU,
+            libc::SIGXFSZ => Signal::SIGXFSZ,
+            libc::SIGVTALRM => Signal::SIGVTALRM,
+            libc::SIGPROF => Signal::SIGPROF,
+            libc::SIGWINCH => Signal::SIGWINCH,
+            libc::SIGIO => Signal::SIGIO,
+            libc::SIGPWR => Signal::SIGPWR,
+            libc::SIGSYS => Signal::SIGSYS,
+            rt if (libc::SIGRTMIN()..=libc::SIGRTMAX()).contains(&rt) => {
+                Signal::Rt(RtSignal((rt - libc::SIGRTMIN()) as u8))
+            }
+            _ => {
+                return Err(Error::Syscall(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "invalid signal number",
+                )));
+            }
+        })
+    }
+}
+
+impl From<Signal> for libc::c_int {
+    fn from(signal: Signal) -> Self {
+        signal.as_raw()
     }
 }
 
@@ -197,7 +326,11 @@ impl AsRef<str> for Signal {
 
 impl fmt::Display for Signal {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.write_str(self.as_ref())
+        match self {
+            Signal::Rt(RtSignal(0)) => f.write_str("SIGRTMIN"),
+            Signal::Rt(RtSignal(n)) => write!(f, "SIGRTMIN+{n}"),
+            _ => f.write_str(self.as_ref()),
+        }
     }
 }
 
@@ -211,7 +344,7 @@ pub struct SignalSet(libc::sigset_t);
 impl SignalSet {
     /// Initialize a set to contain all signals
     pub fn fill() -> Result<SignalSet> {
-        let mut set = mem::MaybeUninit::uninit();
+        let mut set = mem::MaybeUninit::zeroed();
 
         syscall!(sigfillset(set.as_mut_ptr()))?;
 
@@ -223,7 +356,9 @@ impl SignalSet {
     /// Note that this function will never fail, since `sigemptyset()` has no
     /// errors defined.
     pub fn empty() -> Result<SignalSet> {
-        let mut set = mem::MaybeUninit::uninit();
+        // glibc only initializes the words covering `_NSIG` signals, so zero
+        // the whole set in order to keep `PartialEq` meaningful
+        let mut set = mem::MaybeUninit::zeroed();
 
         syscall!(sigemptyset(set.as_mut_ptr()))?;
 
@@ -234,7 +369,7 @@ impl SignalSet {
     pub fn add(&mut self, signal: Signal) -> Result<()> {
         syscall!(sigaddset(
             &mut self.0 as *mut libc::sigset_t,
-            signal as libc::c_int
+            signal.as_raw()
         ))?;
 
         Ok(())
@@ -244,7 +379,7 @@ impl SignalSet {
     pub fn remove(&mut self, signal: Signal) -> Result<()> {
         syscall!(sigdelset(
             &mut self.0 as *mut libc::sigset_t,
-            signal as libc::c_int
+            signal.as_raw()
         ))?;
 
         Ok(())
@@ -254,7 +389,7 @@ impl SignalSet {
     pub fn is_member(&self, signal: Signal) -> Result<bool> {
         let res = syscall!(sigismember(
             &self.0 as *const libc::sigset_t,
-            signal as libc::c_int
+            signal.as_raw()
         ))?;
 
         if res == 1 { Ok(true) } else { Ok(false) }
@@ -283,6 +418,168 @@ impl From<&[Signal]> for SignalSet {
     }
 }
 
+libc_bitflags! {
+    /// Flags for creating a [`SignalFd`]
+    pub struct SfdFlags: libc::c_int {
+        /// Reads from the signal fd will fail with `EAGAIN` instead of
+        /// blocking if no signal is pending
+        SFD_NONBLOCK;
+        /// Close the signal fd on `exec`
+        SFD_CLOEXEC;
+    }
+}
+
+/// Information about a [`Signal`] read from a [`SignalFd`]
+///
+/// This is a subset of [`libc::signalfd_siginfo`]. Which fields carry
+/// meaningful values depends on the signal and on the way it has been sent,
+/// see `signalfd(2)` and `sigaction(2)` for details.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SignalInfo {
+    /// The signal
+    pub signal: Signal,
+    /// Signal code, e.g. `SI_USER`, `SI_QUEUE` or `CLD_EXITED` for `SIGCHLD`
+    pub code: libc::c_int,
+    /// Process ID of the sender
+    pub pid: libc::pid_t,
+    /// Real user ID of the sender
+    pub uid: libc::uid_t,
+    /// Exit status or signal of the child for `SIGCHLD`
+    pub status: libc::c_int,
+    /// Integer value sent with `sigqueue(3)`
+    pub int: libc::c_int,
+    /// Pointer value sent with `sigqueue(3)`
+    pub ptr: u64,
+    /// File descriptor for `SIGIO`
+    pub fd: libc::c_int,
+    /// Band event for `SIGIO`
+    pub band: u32,
+}
+
+impl Default for SignalInfo {
+    /// Return a [`SignalInfo`] for [`Signal::SIGHUP`] with all other fields
+    /// set to `0`, e.g. to initialize a buffer for [`SignalFd::read_signals()`]
+    fn default() -> Self {
+        SignalInfo {
+            signal: Signal::SIGHUP,
+            code: 0,
+            pid: 0,
+            uid: 0,
+            status: 0,
+            int: 0,
+            ptr: 0,
+            fd: 0,
+            band: 0,
+        }
+    }
+}
+
+impl TryFrom<&libc::signalfd_siginfo> for SignalInfo {
+    type Error = Error;
+
+    /// Try to convert a raw `siginfo` read from a signal fd into a [`SignalInfo`]
+    fn try_from(siginfo: &libc::signalfd_siginfo) -> Result<Self> {
+        Ok(SignalInfo {
+            signal: (siginfo.ssi_signo as libc::c_int).try_into()?,
+            code: siginfo.ssi_code,
+            pid: siginfo.ssi_pid as libc::pid_t,
+            uid: siginfo.ssi_uid,
+            status: siginfo.ssi_status,
+            int: siginfo.ssi_int,
+            ptr: siginfo.ssi_ptr,
+            fd: siginfo.ssi_fd,
+            band: siginfo.ssi_band,
+        })
+    }
+}
+
+impl TryFrom<&libc::siginfo_t> for SignalInfo {
+    type Error = Error;
+
+    /// Try to convert a `siginfo` as returned by e.g. [`signal_wait()`] into a
+    /// [`SignalInfo`]
+    ///
+    /// Like for a signal fd, only those fields which are valid for the kind
+    /// of signal are set, all others are `0`.
+    fn try_from(siginfo: &libc::siginfo_t) -> Result<Self> {
+        /// Layout of `siginfo_t` for `SIGIO` / `SIGPOLL`
+        #[repr(C)]
+        struct SigPoll {
+            signo: libc::c_int,
+            errno: libc::c_int,
+            code: libc::c_int,
+            band: libc::c_long,
+            fd: libc::c_int,
+        }
+
+        let mut info = SignalInfo {
+            signal: siginfo.si_signo.try_into()?,
+            code: siginfo.si_code,
+            ..Default::default()
+        };
+
+        // kernel generated signals have a positive code
+        let kernel = siginfo.si_code > 0;
+
+        unsafe {
+            match info.signal {
+                Signal::SIGCHLD if kernel => {
+                    info.pid = siginfo.si_pid();
+                    info.uid = siginfo.si_uid();
+                    info.status = siginfo.si_status();
+                }
+                Signal::SIGIO if kernel => {
+                    let poll = &*(siginfo as *const libc::siginfo_t as *const SigPoll);
+                    info.band = poll.band as u32;
+                    info.fd = poll.fd;
+                }
+                _ if matches!(siginfo.si_code, libc::SI_QUEUE | libc::SI_MESGQ) => {
+                    let value = siginfo.si_value();
+                    info.pid = siginfo.si_pid();
+                    info.uid = siginfo.si_uid();
+                    info.int = *(&value as *const libc::sigval as *const libc::c_int);
+                    info.ptr = value.sival_ptr as u64;
+                }
+                _ if !kernel => {
+                    info.pid = siginfo.si_pid();
+                    info.uid = siginfo.si_uid();
+                }
+                _ => {}
+            }
+        }
+
+        Ok(info)
+    }
+}
+
+/// Value sent along with a signal using [`signal_queue()`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SigVal {
+    /// Integer value
+    Int(libc::c_int),
+    /// Pointer value. Note that this is only meaningful if the receiver
+    /// shares the address space of the sender.
+    Ptr(*mut libc::c_void),
+}
+
+impl From<SigVal> for libc::sigval {
+    fn from(value: SigVal) -> Self {
+        let mut sigval = libc::sigval {
+            sival_ptr: ptr::null_mut(),
+        };
+
+        match value {
+            // `sival_int` is overlaying the start of `sival_ptr`
+            SigVal::Int(int) => unsafe {
+                *(&mut sigval as *mut libc::sigval as *mut libc::c_int) = int;
+            },
+            SigVal::Ptr(ptr) => sigval.sival_ptr = ptr,
+        }
+
+        sigval
+    }
+}
+
 /// Magic value for creating a new signal fd
 const SIGNALFD_NEW: libc::c_int = -1;
 
@@ -297,19 +594,26 @@ impl SignalFd {
     /// Return a [`SignalFd`] that will be able to read the signals given in
     /// the signal set `signals`.
     pub fn new(signals: SignalSet) -> Result<SignalFd> {
+        SignalFd::with_flags(signals, SfdFlags::empty())
+    }
+
+    /// Return a [`SignalFd`] that will be able to read the signals given in
+    /// the signal set `signals`, created using `flags`.
+    pub fn with_flags(signals: SignalSet, flags: SfdFlags) -> Result<SignalFd> {
         let fd = syscall!(signalfd(
             SIGNALFD_NEW,
             signals.as_ref() as *const libc::sigset_t,
-            0
+            flags.bits()
         ))?;
 
         Ok(SignalFd(fd))
     }
 
-    /// Read and return a [`Signal`]
+    /// Read and return a [`SignalInfo`]
     ///
-    /// Note this function will **block** until a signal could be read.
-    pub fn read_signal(&mut self) -> Result<Signal> {
+    /// Note this function will **block** until a signal could be read, unless
+    /// the signal fd has been created using [`SfdFlags::SFD_NONBLOCK`].
+    pub fn read_signal(&mut self) -> Result<SignalInfo> {
         let mut siginfo = mem::MaybeUninit::<libc::signalfd_siginfo>::uninit();
         let size = mem::size_of_val(&siginfo);
 
@@ -328,9 +632,66 @@ impl SignalFd {
         }
 
         let siginfo = unsafe { siginfo.assume_init() };
-        let signum = siginfo.ssi_signo as libc::c_int;
 
-        signum.try_into()
+        (&siginfo).try_into()
+    }
+
+    /// Read pending signals, up to the length of `signals`, and return the
+    /// number of signals read.
+    ///
+    /// Signals unknown to [`Signal`] are dropped.
+    ///
+    /// For a signal fd created using [`SfdFlags::SFD_NONBLOCK`], this returns
+    /// `0` if no signal is pending. Otherwise, this function will **block**
+    /// until at least one signal could be read.
+    pub fn read_signals(&mut self, signals: &mut [SignalInfo]) -> Result<usize> {
+        if signals.is_empty() {
+            return Ok(0);
+        }
+
+        let mut buf = vec![unsafe { mem::zeroed::<libc::signalfd_siginfo>() }; signals.len()];
+        let size = mem::size_of::<libc::signalfd_siginfo>();
+
+        loop {
+            // the kernel dequeues as many pending signals as fit into the buffer
+            let num = match syscall!(read(
+                self.0,
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len() * size
+            )) {
+                Ok(num) => num as usize,
+                Err(Error::Syscall(e)) if e.raw_os_error() == Some(libc::EAGAIN) => return Ok(0),
+                Err(e) => return Err(e),
+            };
+
+            // not enough signal info data
+            if num % size != 0 {
+                return Err(Error::Syscall(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "invalid signal",
+                )));
+            }
+
+            // the signals are dequeued already, so don't stop at an unknown one
+            let mut count = 0;
+
+            for siginfo in &buf[..num / size] {
+                if let Ok(info) = SignalInfo::try_from(siginfo) {
+                    signals[count] = info;
+                    count += 1;
+                }
+            }
+
+            if count > 0 {
+                return Ok(count);
+            }
+        }
+    }
+}
+
+impl AsRawFd for SignalFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
     }
 }
 
@@ -408,11 +769,352 @@ pub fn signal_restore(set: SignalSet) -> Result<SignalSet> {
     Ok(old)
 }
 
+libc_bitflags! {
+    /// Flags modifying the behaviour of a [`SigAction`]
+    pub struct SaFlags: libc::c_int {
+        /// Do not generate `SIGCHLD` when a child stops or resumes
+        SA_NOCLDSTOP;
+        /// Do not turn terminating children into zombies
+        SA_NOCLDWAIT;
+        /// Do not block the signal while its handler is running
+        SA_NODEFER;
+        /// Run the handler on an alternate signal stack, see [`AltStack`]
+        SA_ONSTACK;
+        /// Restore the default action once the handler has been entered
+        SA_RESETHAND;
+        /// Restart system calls interrupted by the signal
+        SA_RESTART;
+        /// Pass a `siginfo_t` to the handler. Set automatically for
+        /// [`SigHandler::SigAction`]
+        SA_SIGINFO;
+    }
+}
+
+/// Disposition of a signal
+#[derive(Clone, Copy, Debug)]
+pub enum SigHandler {
+    /// Default action for the signal
+    Default,
+    /// Ignore the signal
+    Ignore,
+    /// Call a plain signal handler
+    Handler(extern "C" fn(libc::c_int)),
+    /// Call a signal handler which receives the `siginfo_t` and the
+    /// `ucontext_t` of the signal as well
+    SigAction(extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void)),
+}
+
+/// Action to be taken on receipt of a [`Signal`]
+///
+/// A [`SigAction`] is installed using [`signal_action()`] and the current
+/// one can be queried using [`signal_disposition()`].
+#[derive(Clone, Copy, Debug)]
+pub struct SigAction {
+    handler: SigHandler,
+    flags: SaFlags,
+    mask: SignalSet,
+}
+
+impl SigAction {
+    /// Return a new [`SigAction`] for `handler` with no flags set and an
+    /// empty signal mask
+    pub fn new(handler: SigHandler) -> SigAction {
+        SigAction {
+            handler,
+            flags: SaFlags::empty(),
+            mask: SignalSet::empty().expect("syscall failed"),
+        }
+    }
+
+    /// Set the `flags` of this action
+    pub fn with_flags(mut self, flags: SaFlags) -> SigAction {
+        self.flags = flags;
+        self
+    }
+
+    /// Set the signals to be blocked while the handler is running
+    pub fn with_mask(mut self, mask: SignalSet) -> SigAction {
+        self.mask = mask;
+        self
+    }
+
+    /// Return the handler of this action
+    pub fn handler(&self) -> SigHandler {
+        self.handler
+    }
+
+    /// Return the flags of this action
+    pub fn flags(&self) -> SaFlags {
+        self.flags
+    }
+
+    /// Return the signals blocked while the handler is running
+    pub fn mask(&self) -> SignalSet {
+        self.mask
+    }
+
+    fn to_raw(self) -> libc::sigaction {
+        let mut action = unsafe { mem::zeroed::<libc::sigaction>() };
+
+        let mut flags = self.flags;
+
+        action.sa_sigaction = match self.handler {
+            SigHandler::Default => libc::SIG_DFL,
+            SigHandler::Ignore => libc::SIG_IGN,
+            SigHandler::Handler(f) => {
+                flags.remove(SaFlags::SA_SIGINFO);
+                f as libc::sighandler_t
+            }
+            SigHandler::SigAction(f) => {
+                flags.insert(SaFlags::SA_SIGINFO);
+                f as libc::sighandler_t
+            }
+        };
+
+        action.sa_flags = flags.bits() as _;
+        action.sa_mask = self.mask.0;
+
+        action
+    }
+
+    fn from_raw(action: &libc::sigaction) -> SigAction {
+        let flags = SaFlags::from_bits_truncate(action.sa_flags as libc::c_int);
+
+        let handler = match action.sa_sigaction {
+            libc::SIG_DFL => SigHandler::Default,
+            libc::SIG_IGN => SigHandler::Ignore,
+            f if flags.contains(SaFlags::SA_SIGINFO) => SigHandler::SigAction(unsafe {
+                mem::transmute::<
+                    libc::sighandler_t,
+                    extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void),
+                >(f)
+            }),
+            f => SigHandler::Handler(unsafe {
+                mem::transmute::<libc::sighandler_t, extern "C" fn(libc::c_int)>(f)
+            }),
+        };
+
+        // the kernel only reports the first `_NSIG` signals, so start from
+        // an empty set in order to keep `PartialEq` on the mask meaningful
+        let mut mask = SignalSet::empty().expect("syscall failed");
+        mask.0 = action.sa_mask;
+
+        SigAction {
+            handler,
+            flags,
+            mask,
+        }
+    }
+}
+
+/// Install `action` for `signal` and return the previous action.
+///
+/// ### Safety
+///
+/// A signal handler may interrupt the program at any point and must only
+/// call async-signal-safe functions, see `signal-safety(7)`. Changing the
+/// disposition of a signal also affects all threads of the process.
+pub unsafe fn signal_action(signal: Signal, action: &SigAction) -> Result<SigAction> {
+    let new = action.to_raw();
+    let mut old = mem::MaybeUninit::<libc::sigaction>::zeroed();
+
+    syscall!(sigaction(
+        signal.as_raw(),
+        &new as *const libc::sigaction,
+        old.as_mut_ptr()
+    ))?;
+
+    Ok(SigAction::from_raw(unsafe { old.assume_init_ref() }))
+}
+
+/// Return the currently installed action for `signal`
+pub fn signal_disposition(signal: Signal) -> Result<SigAction> {
+    let mut old = mem::MaybeUninit::<libc::sigaction>::zeroed();
+
+    syscall!(sigaction(signal.as_raw(), ptr::null(), old.as_mut_ptr()))?;
+
+    Ok(SigAction::from_raw(unsafe { old.assume_init_ref() }))
+}
+
+/// Alternate signal stack for the calling thread
+///
+/// Signal handlers installed with [`SaFlags::SA_ONSTACK`] run on this stack,
+/// which allows to handle e.g. a `SIGSEGV` caused by a stack overflow. The
+/// stack memory is guarded by an inaccessible page at its lower end.
+///
+/// Dropping an [`AltStack`] restores the previous alternate signal stack of
+/// the thread and unmaps the stack memory. Since the alternate signal stack
+/// is a per-thread attribute, an [`AltStack`] is neither [`Send`] nor [`Sync`].
+#[derive(Debug)]
+pub struct AltStack {
+    map: NonNull<libc::c_void>,
+    map_len: usize,
+    size: usize,
+    old: libc::stack_t,
+}
+
+impl AltStack {
+    /// Install a new alternate signal stack of at least `size` bytes for the
+    /// calling thread.
+    ///
+    /// Note that `size` is rounded up to a multiple of the page size and to
+    /// at least `SIGSTKSZ`.
+    pub fn new(size: usize) -> Result<AltStack> {
+        let page = syscall!(sysconf(libc::_SC_PAGE_SIZE))? as usize;
+        let size = cmp::max(size, libc::SIGSTKSZ).next_multiple_of(page);
+        let map_len = size + page;
+
+        let map = mmap_anonymous(
+            None,
+            NonZeroUsize::new(map_len).expect("non-zero length"),
+            ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+            MapFlags::MAP_PRIVATE,
+        )?;
+
+        let unmap = |err| {
+            let _ = munmap(map, map_len);
+            err
+        };
+
+        // guard page
+        mprotect(map, page, ProtFlags::PROT_NONE).map_err(unmap)?;
+
+        let stack = libc::stack_t {
+            ss_sp: unsafe { map.as_ptr().byte_add(page) },
+            ss_flags: 0,
+            ss_size: size,
+        };
+
+        let mut old = mem::MaybeUninit::<libc::stack_t>::zeroed();
+
+        syscall!(sigaltstack(
+            &stack as *const libc::stack_t,
+            old.as_mut_ptr()
+        ))
+        .map_err(unmap)?;
+
+        Ok(AltStack {
+            map,
+            map_len,
+            size,
+            old: unsafe { old.assume_init() },
+        })
+    }
+
+    /// Return the usable size of the alternate signal stack
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Return a pointer to the lowest address of the alternate signal stack
+    pub fn as_ptr(&self) -> *const libc::c_void {
+        unsafe { self.map.as_ptr().byte_add(self.map_len - self.size) }
+    }
+
+    /// Return `true` if the calling thread is currently executing on an
+    /// alternate signal stack
+    pub fn is_active() -> Result<bool> {
+        let mut current = mem::MaybeUninit::<libc::stack_t>::zeroed();
+
+        syscall!(sigaltstack(ptr::null(), current.as_mut_ptr()))?;
+
+        Ok(unsafe { current.assume_init() }.ss_flags & libc::SS_ONSTACK != 0)
+    }
+}
+
+impl Drop for AltStack {
+    fn drop(&mut self) {
+        // if the previous stack can't be restored, e.g. because we are still
+        // running on the alternate stack, leaking the memory is the only
+        // safe option
+        if syscall!(sigaltstack(
+            &self.old as *const libc::stack_t,
+            ptr::null_mut()
+        ))
+        .is_ok()
+        {
+            let _ = munmap(self.map, self.map_len);
+        }
+    }
+}
+
+/// Send `signal` along with `value` to the process `pid`.
+///
+/// Unlike standard signals, multiple instances of a real-time signal are
+/// queued and delivered in order. The receiver can get `value` from the
+/// [`SignalInfo`] of the signal.
+pub fn signal_queue(pid: libc::pid_t, signal: Signal, value: SigVal) -> Result<()> {
+    syscall!(sigqueue(pid, signal.as_raw(), value.into())).map(|_| ())
+}
+
+/// Wait for one of the signals in `set` to become pending and return it.
+///
+/// The signals in `set` should be blocked before calling this function, it
+/// will **block** until one of them is pending.
+pub fn signal_wait(set: &SignalSet) -> Result<SignalInfo> {
+    let mut siginfo = mem::MaybeUninit::<libc::siginfo_t>::zeroed();
+
+    loop {
+        match syscall!(sigwaitinfo(
+            &set.0 as *const libc::sigset_t,
+            siginfo.as_mut_ptr()
+        )) {
+            Err(Error::Syscall(e)) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            res => break res,
+        }
+    }?;
+
+    unsafe { siginfo.assume_init_ref() }.try_into()
+}
+
+/// Wait for at most `timeout` for one of the signals in `set` to become
+/// pending and return it.
+///
+/// Return `None` if no signal became pending within `timeout`. See
+/// [`signal_wait()`] for further details.
+pub fn signal_timedwait(set: &SignalSet, timeout: Duration) -> Result<Option<SignalInfo>> {
+    let mut siginfo = mem::MaybeUninit::<libc::siginfo_t>::zeroed();
+
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        // an interrupted wait continues with the time left only
+        let left = deadline.saturating_duration_since(Instant::now());
+
+        let timeout = libc::timespec {
+            tv_sec: left.as_secs() as libc::time_t,
+            tv_nsec: left.subsec_nanos() as _,
+        };
+
+        match syscall!(sigtimedwait(
+            &set.0 as *const libc::sigset_t,
+            siginfo.as_mut_ptr(),
+            &timeout as *const libc::timespec
+        )) {
+            Ok(_) => return Ok(Some(unsafe { siginfo.assume_init_ref() }.try_into()?)),
+            Err(Error::Syscall(e)) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(Error::Syscall(e)) if e.raw_os_error() == Some(libc::EAGAIN) => return Ok(None),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::{
+        os::unix::prelude::AsRawFd,
+        sync::atomic::{AtomicBool, AtomicI32, Ordering},
+        time::Duration,
+    };
+
     use anyhow::Result;
 
-    use super::{Signal, SignalFd, SignalSet, signal_block, signal_restore};
+    use super::{
+        AltStack, SaFlags, SfdFlags, SigAction, SigHandler, SigVal, Signal, SignalFd, SignalInfo,
+        SignalSet, signal_action, signal_block, signal_disposition, signal_queue, signal_restore,
+        signal_timedwait, signal_wait,
+    };
+    use crate::{WaitStatus, wait};
 
     #[test]
     fn signal_set_add() -> Result<()> {
@@ -462,7 +1164,7 @@ mod tests {
 
     #[test]
     fn signal_try_from() -> Result<()> {
-        let signum = Signal::SIGQUIT as libc::c_int;
+        let signum = Signal::SIGQUIT.as_raw();
         let sig: Signal = signum.try_into()?;
 
         assert_eq!(signum, libc::SIGQUIT);
@@ -525,10 +1227,268 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn signalfd_read_signals() -> Result<()> {
+        let set: SignalSet = vec![Signal::SIGALRM].as_slice().into();
+        let old = signal_block(set)?;
+
+        let mut sigfd = SignalFd::with_flags(set, SfdFlags::SFD_NONBLOCK | SfdFlags::SFD_CLOEXEC)?;
+
+        let mut infos = [SignalInfo::default(); 4];
+
+        // nothing pending yet
+        assert_eq!(sigfd.read_signals(&mut infos)?, 0);
+
+        syscall!(raise(libc::SIGALRM))?;
+
+        assert_eq!(sigfd.read_signals(&mut infos)?, 1);
+        assert_eq!(infos[0].signal, Signal::SIGALRM);
+        assert_eq!(infos[0].code, libc::SI_TKILL);
+        assert_eq!(infos[0].pid, std::process::id() as libc::pid_t);
+        assert_eq!(infos[0].uid, unsafe { libc::getuid() });
+
+        // drained
+        assert_eq!(sigfd.read_signals(&mut infos)?, 0);
+
+        let flags = syscall!(fcntl(sigfd.as_raw_fd(), libc::F_GETFD))?;
+        assert_eq!(flags & libc::FD_CLOEXEC, libc::FD_CLOEXEC);
+
+        signal_restore(old)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn signalfd_read_signals_batch() -> Result<()> {
+        let signals = [
+            Signal::SIGHUP,
+            Signal::SIGINT,
+            Signal::SIGQUIT,
+            Signal::SIGUSR1,
+            Signal::SIGUSR2,
+            Signal::SIGPIPE,
+            Signal::SIGALRM,
+            Signal::SIGTERM,
+            Signal::SIGSTKFLT,
+            Signal::SIGCHLD,
+            Signal::SIGTTIN,
+            Signal::SIGTTOU,
+            Signal::SIGURG,
+            Signal::SIGXCPU,
+            Signal::SIGXFSZ,
+            Signal::SIGVTALRM,
+            Signal::SIGPROF,
+            Signal::SIGWINCH,
+            Signal::SIGIO,
+            Signal::SIGPWR,
+        ];
+
+        let set: SignalSet = signals.as_slice().into();
+        let old = signal_block(set)?;
+
+        let mut sigfd = SignalFd::with_flags(set, SfdFlags::SFD_NONBLOCK | SfdFlags::SFD_CLOEXEC)?;
+
+        for signal in signals {
+            syscall!(raise(signal.into()))?;
+        }
+
+        // all pending signals fit into a single call
+        let mut infos = [SignalInfo::default(); 32];
+        assert_eq!(sigfd.read_signals(&mut infos)?, signals.len());
+        assert_eq!(sigfd.read_signals(&mut infos)?, 0);
+
+        signal_restore(old)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn signalfd_read_signal_nonblocking() -> Result<()> {
+        let mut sigfd = SignalFd::with_flags(
+            vec![Signal::SIGXCPU].as_slice().into(),
+            SfdFlags::SFD_NONBLOCK,
+        )?;
+
+        assert_eq!(
+            format!("{}", sigfd.read_signal().err().unwrap()),
+            "System call error: Resource temporarily unavailable (os error 11)"
+        );
+
+        Ok(())
+    }
+
     #[test]
     #[should_panic(expected = "closing invalid signal fd")]
     fn signalfd_drop_invalid() {
         let fake = SignalFd(-1);
         drop(fake);
     }
+
+    static HANDLED: AtomicI32 = AtomicI32::new(0);
+    static ON_STACK: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn handler(signum: libc::c_int) {
+        HANDLED.store(signum, Ordering::SeqCst);
+    }
+
+    extern "C" fn stack_handler(
+        _signum: libc::c_int,
+        _info: *mut libc::siginfo_t,
+        _ctx: *mut libc::c_void,
+    ) {
+        ON_STACK.store(AltStack::is_active().unwrap_or(false), Ordering::SeqCst);
+    }
+
+    #[test]
+    fn sigaction_handler() -> Result<()> {
+        let action = SigAction::new(SigHandler::Handler(handler))
+            .with_flags(SaFlags::SA_RESTART)
+            .with_mask(vec![Signal::SIGUSR2].as_slice().into());
+
+        let old = unsafe { signal_action(Signal::SIGUSR1, &action)? };
+        assert!(matches!(old.handler(), SigHandler::Default));
+
+        let current = signal_disposition(Signal::SIGUSR1)?;
+        assert!(
+            matches!(current.handler(), SigHandler::Handler(f) if std::ptr::fn_addr_eq(f, handler as extern "C" fn(libc::c_int)))
+        );
+        assert!(current.flags().contains(SaFlags::SA_RESTART));
+        assert!(!current.flags().contains(SaFlags::SA_SIGINFO));
+        assert!(current.mask().is_member(Signal::SIGUSR2)?);
+
+        syscall!(raise(libc::SIGUSR1))?;
+        assert_eq!(HANDLED.load(Ordering::SeqCst), libc::SIGUSR1);
+
+        unsafe { signal_action(Signal::SIGUSR1, &old)? };
+        assert!(matches!(
+            signal_disposition(Signal::SIGUSR1)?.handler(),
+            SigHandler::Default
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn sigaction_ignore() -> Result<()> {
+        let action = SigAction::new(SigHandler::Ignore);
+
+        let old = unsafe { signal_action(Signal::SIGWINCH, &action)? };
+
+        // must not terminate the process
+        syscall!(raise(libc::SIGWINCH))?;
+
+        assert!(matches!(
+            signal_disposition(Signal::SIGWINCH)?.handler(),
+            SigHandler::Ignore
+        ));
+
+        unsafe { signal_action(Signal::SIGWINCH, &old)? };
+
+        Ok(())
+    }
+
+    #[test]
+    fn sigaction_altstack() -> Result<()> {
+        let stack = AltStack::new(0)?;
+
+        assert!(stack.size() >= libc::SIGSTKSZ);
+        assert!(!AltStack::is_active()?);
+
+        let action =
+            SigAction::new(SigHandler::SigAction(stack_handler)).with_flags(SaFlags::SA_ONSTACK);
+
+        let old = unsafe { signal_action(Signal::SIGUSR2, &action)? };
+
+        let current = signal_disposition(Signal::SIGUSR2)?;
+        assert!(
+            current
+                .flags()
+                .contains(SaFlags::SA_SIGINFO | SaFlags::SA_ONSTACK)
+        );
+
+        syscall!(raise(libc::SIGUSR2))?;
+        assert!(ON_STACK.load(Ordering::SeqCst));
+
+        unsafe { signal_action(Signal::SIGUSR2, &old)? };
+
+        Ok(())
+    }
+
+    #[test]
+    fn signal_rt() -> Result<()> {
+        let sig: Signal = libc::SIGRTMIN().try_into()?;
+        assert_eq!(sig, Signal::rt(0)?);
+        assert!(matches!(sig, Signal::Rt(rt) if rt.offset() == 0));
+        assert_eq!(sig.to_string(), "SIGRTMIN");
+
+        let sig = Signal::rt(3)?;
+        assert_eq!(sig.as_raw(), libc::SIGRTMIN() + 3);
+        assert_eq!(sig.to_string(), "SIGRTMIN+3");
+        assert_eq!("SIGRTMIN+3".parse::<Signal>()?, sig);
+
+        let max: Signal = "SIGRTMAX".parse()?;
+        assert_eq!(max.as_raw(), libc::SIGRTMAX());
+        assert_eq!(
+            "SIGRTMAX-1".parse::<Signal>()?.as_raw(),
+            libc::SIGRTMAX() - 1
+        );
+
+        assert!(Signal::rt(u8::MAX).is_err());
+        assert!("SIGRTMIN+x".parse::<Signal>().is_err());
+
+        // signals reserved by the C library are invalid
+        assert!(Signal::try_from(32).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn signal_queue_wait() -> Result<()> {
+        let sig = Signal::rt(2)?;
+        let set: SignalSet = vec![sig].as_slice().into();
+
+        // the child inherits the blocked signal
+        let old = signal_block(set)?;
+
+        let child = match syscall!(fork())? {
+            // parent
+            pid if pid != 0 => pid,
+            // child -> exit status tells which check failed
+            _ => {
+                let parent = unsafe { libc::getppid() };
+
+                let status = match signal_wait(&set) {
+                    Ok(info)
+                        if info.signal == sig
+                            && info.code == libc::SI_QUEUE
+                            && info.pid == parent
+                            && info.int == 42 =>
+                    {
+                        match signal_timedwait(&set, Duration::from_secs(5)) {
+                            Ok(Some(info)) if info.int == -7 => {
+                                match signal_timedwait(&set, Duration::from_millis(1)) {
+                                    Ok(None) => 0,
+                                    _ => 3,
+                                }
+                            }
+                            _ => 2,
+                        }
+                    }
+                    _ => 1,
+                };
+
+                unsafe { libc::_exit(status) }
+            }
+        };
+
+        signal_restore(old)?;
+
+        // real-time signals are queued
+        signal_queue(child, sig, SigVal::Int(42))?;
+        signal_queue(child, sig, SigVal::Int(-7))?;
+
+        assert_eq!(wait(child)?, WaitStatus::Exited(child, 0));
+
+        Ok(())
+    }
 }
@@ -4,9 +4,16 @@
 
 use crate::FileDesc;
 
+/// Describes what to do with a standard I/O stream of a child process
+/// spawned by a [`Command`](crate::Command)
 pub enum Stdio {
+    /// Connect the stream to the given file descriptor
     Fd(FileDesc),
+    /// Inherit the stream from the parent process
     Inherit,
+    /// Connect the stream to `/dev/null`
     Null,
+    /// Connect the stream to a new pipe. The parent end of the pipe is
+    /// available from the returned [`Child`](crate::Child)
     Pipe,
 }
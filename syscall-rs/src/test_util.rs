@@ -0,0 +1,31 @@
+//!
+//! This file is part of syscall-rs
+//!
+
+use std::process::exit;
+
+use anyhow::Result;
+
+use crate::{WaitStatus, wait};
+
+/// Run `f` in a forked child and return whether it succeeded
+///
+/// Use this for tests changing process wide state, like namespaces,
+/// credentials or seccomp filters.
+pub(crate) fn in_child(f: impl FnOnce() -> Result<()>) -> Result<()> {
+    match syscall!(fork())? {
+        // parent
+        pid if pid != 0 => {
+            assert_eq!(wait(pid)?, WaitStatus::Exited(pid, 0));
+            Ok(())
+        }
+        // child
+        _ => match f() {
+            Ok(()) => exit(0),
+            Err(err) => {
+                eprintln!("child failed: {:?}", err);
+                exit(1)
+            }
+        },
+    }
+}
@@ -0,0 +1,328 @@
+//!
+//! This file is part of syscall-rs
+//!
+
+use std::{
+    mem,
+    os::unix::prelude::{AsFd, AsRawFd, BorrowedFd, FromRawFd, RawFd},
+    ptr,
+    time::Duration,
+};
+
+use libc::c_int;
+use mio::{Interest, Registry, Token, event, unix::SourceFd};
+
+use crate::{Error, FileDesc, Result, libc_bitflags, libc_enum};
+
+libc_enum! {
+    /// Clock driving a [`TimerFd`]
+    #[repr(i32)]
+    #[non_exhaustive]
+    #[allow(non_camel_case_types)]
+    pub enum ClockId {
+        /// Wall-clock time, affected by changes of the system time
+        CLOCK_REALTIME,
+        /// Monotonic time, not counting time the system is suspended
+        CLOCK_MONOTONIC,
+        /// Monotonic time, including time the system is suspended
+        CLOCK_BOOTTIME,
+        /// Like `CLOCK_REALTIME`, but wakes a suspended system
+        CLOCK_REALTIME_ALARM,
+        /// Like `CLOCK_BOOTTIME`, but wakes a suspended system
+        CLOCK_BOOTTIME_ALARM,
+    }
+}
+
+libc_bitflags! {
+    /// Flags for creating a [`TimerFd`]
+    pub struct TfdFlags: c_int {
+        /// Close the timer fd on `exec`
+        TFD_CLOEXEC;
+        /// Nonblocking reads
+        TFD_NONBLOCK;
+    }
+}
+
+libc_bitflags! {
+    /// Flags for arming a [`TimerFd`]
+    pub struct TimerSetFlags: c_int {
+        /// The initial expiration is an absolute time of the timer clock
+        TFD_TIMER_ABSTIME;
+        /// Cancel a `CLOCK_REALTIME` timer if the clock is set discontinuously,
+        /// requires `TFD_TIMER_ABSTIME`
+        TFD_TIMER_CANCEL_ON_SET;
+    }
+}
+
+/// When a [`TimerFd`] expires
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Expiration {
+    /// Expire once after the given duration
+    OneShot(Duration),
+    /// Expire after the first duration and then periodically after the
+    /// second duration
+    IntervalDelayed(Duration, Duration),
+    /// Expire periodically after the given duration
+    Interval(Duration),
+}
+
+impl From<Expiration> for libc::itimerspec {
+    fn from(expiration: Expiration) -> Self {
+        let (value, interval) = match expiration {
+            Expiration::OneShot(value) => (value, Duration::ZERO),
+            Expiration::IntervalDelayed(value, interval) => (value, interval),
+            Expiration::Interval(interval) => (interval, interval),
+        };
+
+        libc::itimerspec {
+            it_interval: timespec(interval),
+            it_value: timespec(value),
+        }
+    }
+}
+
+/// File descriptor for timer expiration notification
+///
+/// A [`TimerFd`] becomes readable once the timer expires, which makes it
+/// usable as timer source for event loops.
+#[derive(Debug)]
+pub struct TimerFd(FileDesc);
+
+impl TimerFd {
+    /// Return a new, disarmed [`TimerFd`] driven by `clock`
+    pub fn new(clock: ClockId, flags: TfdFlags) -> Result<TimerFd> {
+        let fd = syscall!(timerfd_create(clock as libc::clockid_t, flags.bits()))?;
+
+        Ok(TimerFd(unsafe { FileDesc::from_raw_fd(fd) }))
+    }
+
+    /// Arm the timer
+    ///
+    /// With [`TimerSetFlags::TFD_TIMER_ABSTIME`], the initial expiration is
+    /// interpreted as absolute time of the timer clock, as returned by
+    /// `clock_gettime(2)`.
+    pub fn set(&self, expiration: Expiration, flags: TimerSetFlags) -> Result<()> {
+        let new = libc::itimerspec::from(expiration);
+
+        syscall!(timerfd_settime(
+            self.0.as_raw_fd(),
+            flags.bits(),
+            &new,
+            ptr::null_mut()
+        ))
+        .map(|_| ())
+    }
+
+    /// Disarm the timer
+    pub fn unset(&self) -> Result<()> {
+        let new: libc::itimerspec = unsafe { mem::zeroed() };
+
+        syscall!(timerfd_settime(
+            self.0.as_raw_fd(),
+            0,
+            &new,
+            ptr::null_mut()
+        ))
+        .map(|_| ())
+    }
+
+    /// Return the time until the next expiration, or `None` if the timer is
+    /// disarmed
+    pub fn get(&self) -> Result<Option<Expiration>> {
+        let mut curr = mem::MaybeUninit::<libc::itimerspec>::uninit();
+
+        syscall!(timerfd_gettime(self.0.as_raw_fd(), curr.as_mut_ptr()))?;
+
+        let curr = unsafe { curr.assume_init() };
+        let value = duration(curr.it_value);
+        let interval = duration(curr.it_interval);
+
+        Ok(match (value, interval) {
+            (Duration::ZERO, _) => None,
+            (value, Duration::ZERO) => Some(Expiration::OneShot(value)),
+            (value, interval) if value == interval => Some(Expiration::Interval(interval)),
+            (value, interval) => Some(Expiration::IntervalDelayed(value, interval)),
+        })
+    }
+
+    /// Wait for the timer to expire and return the number of expirations
+    /// since the last read
+    ///
+    /// This will **block** until the timer expires, unless the [`TimerFd`] has
+    /// been created using [`TfdFlags::TFD_NONBLOCK`], in which case it fails
+    /// with `EAGAIN`.
+    pub fn read(&self) -> Result<u64> {
+        let mut buf = [0u8; 8];
+
+        if self.0.read(&mut buf)? != buf.len() {
+            return Err(Error::Syscall(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "short timerfd read",
+            )));
+        }
+
+        Ok(u64::from_ne_bytes(buf))
+    }
+}
+
+impl AsRawFd for TimerFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl AsFd for TimerFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+impl event::Source for TimerFd {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> std::io::Result<()> {
+        SourceFd(&self.0.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> std::io::Result<()> {
+        SourceFd(&self.0.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> std::io::Result<()> {
+        SourceFd(&self.0.as_raw_fd()).deregister(registry)
+    }
+}
+
+/// Return the current time of `clock`
+pub fn clock_gettime(clock: ClockId) -> Result<Duration> {
+    let mut ts = mem::MaybeUninit::<libc::timespec>::uninit();
+
+    syscall!(clock_gettime(clock as libc::clockid_t, ts.as_mut_ptr()))?;
+
+    Ok(duration(unsafe { ts.assume_init() }))
+}
+
+fn timespec(d: Duration) -> libc::timespec {
+    libc::timespec {
+        tv_sec: d.as_secs() as libc::time_t,
+        tv_nsec: d.subsec_nanos() as libc::c_long,
+    }
+}
+
+fn duration(ts: libc::timespec) -> Duration {
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use anyhow::Result;
+    use mio::{Events, Interest, Poll, Token};
+
+    use crate::{ClockId, Expiration, TfdFlags, TimerFd, TimerSetFlags, clock_gettime};
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn timerfd_oneshot() -> Result<()> {
+        let tfd = TimerFd::new(ClockId::CLOCK_MONOTONIC, TfdFlags::TFD_CLOEXEC)?;
+
+        assert_eq!(tfd.get()?, None);
+
+        let start = Instant::now();
+        tfd.set(Expiration::OneShot(20 * MS), TimerSetFlags::empty())?;
+
+        assert!(matches!(tfd.get()?, Some(Expiration::OneShot(d)) if d <= 20 * MS));
+
+        assert_eq!(tfd.read()?, 1);
+        assert!(start.elapsed() >= 20 * MS);
+
+        // one-shot timers disarm after expiration
+        assert_eq!(tfd.get()?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn timerfd_interval() -> Result<()> {
+        let tfd = TimerFd::new(ClockId::CLOCK_BOOTTIME, TfdFlags::TFD_NONBLOCK)?;
+
+        tfd.set(Expiration::Interval(5 * MS), TimerSetFlags::empty())?;
+        assert!(matches!(
+            tfd.get()?,
+            Some(Expiration::IntervalDelayed(_, d)) | Some(Expiration::Interval(d)) if d == 5 * MS
+        ));
+
+        std::thread::sleep(30 * MS);
+
+        // expirations accumulate until read
+        assert!(tfd.read()? >= 3);
+
+        tfd.unset()?;
+        assert_eq!(tfd.get()?, None);
+
+        assert_eq!(
+            format!("{}", tfd.read().err().unwrap()),
+            "System call error: Resource temporarily unavailable (os error 11)"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn timerfd_abstime() -> Result<()> {
+        let tfd = TimerFd::new(ClockId::CLOCK_REALTIME, TfdFlags::empty())?;
+
+        let deadline = clock_gettime(ClockId::CLOCK_REALTIME)? + 10 * MS;
+        tfd.set(
+            Expiration::OneShot(deadline),
+            TimerSetFlags::TFD_TIMER_ABSTIME,
+        )?;
+
+        assert_eq!(tfd.read()?, 1);
+        assert!(clock_gettime(ClockId::CLOCK_REALTIME)? >= deadline);
+
+        // a deadline in the past expires immediately
+        tfd.set(
+            Expiration::OneShot(Duration::from_secs(1)),
+            TimerSetFlags::TFD_TIMER_ABSTIME,
+        )?;
+        assert_eq!(tfd.read()?, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn timerfd_poll() -> Result<()> {
+        const TIMER: Token = Token(2);
+
+        let mut tfd = TimerFd::new(ClockId::CLOCK_MONOTONIC, TfdFlags::TFD_NONBLOCK)?;
+
+        let mut poll = Poll::new()?;
+        let mut events = Events::with_capacity(1);
+
+        poll.registry()
+            .register(&mut tfd, TIMER, Interest::READABLE)?;
+
+        tfd.set(Expiration::OneShot(10 * MS), TimerSetFlags::empty())?;
+
+        poll.poll(&mut events, Some(Duration::from_secs(5)))?;
+
+        let evt = events.iter().next().unwrap();
+        assert_eq!(evt.token(), TIMER);
+        assert!(evt.is_readable());
+
+        assert_eq!(tfd.read()?, 1);
+
+        Ok(())
+    }
+}
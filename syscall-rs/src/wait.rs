@@ -2,7 +2,50 @@
 //! This file is part of syscall-rs
 //!
 
-use crate::{Result, Signal};
+use std::{mem, os::unix::prelude::RawFd};
+
+use libc::c_int;
+
+use crate::{Error, Result, Signal, libc_bitflags};
+
+libc_bitflags! {
+    /// Options controlling the behaviour of [`waitpid()`] and [`waitid()`]
+    pub struct WaitOptions: c_int {
+        /// Return immediately if no child status is available
+        WNOHANG;
+        /// Also report children which have been stopped
+        WUNTRACED;
+        /// Report children which have been stopped by a signal. Same as
+        /// `WUNTRACED`, but meant to be used with [`waitid()`]
+        WSTOPPED;
+        /// Report children which have terminated
+        WEXITED;
+        /// Report stopped children which have been resumed by `SIGCONT`
+        WCONTINUED;
+        /// Leave the child in a waitable state, such that a later wait call
+        /// can be used to retrieve the child status again
+        WNOWAIT;
+        /// Do not wait for children of other threads in the same thread group
+        __WNOTHREAD;
+        /// Wait for all children, regardless of type (clone or non-clone)
+        __WALL;
+        /// Wait for clone children only
+        __WCLONE;
+    }
+}
+
+/// Selects the children to wait for using [`waitid()`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WaitId {
+    /// Wait for any child
+    All,
+    /// Wait for the child with the given process ID
+    Pid(libc::pid_t),
+    /// Wait for any child in the given process group
+    Pgid(libc::pid_t),
+    /// Wait for the child referred to by the given pidfd
+    PidFd(RawFd),
+}
 
 /// A [`WaitStatus`] is the result of [`wait()`]ing for a child process
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -17,6 +60,14 @@ pub enum WaitStatus {
     /// Process is still alive, but was stopped by `Signal`
     Stopped(libc::pid_t, Signal),
 
+    /// Traced process was stopped by a `PTRACE_EVENT_*` event. The third
+    /// field is the event, e.g. [`libc::PTRACE_EVENT_EXEC`].
+    PtraceEvent(libc::pid_t, Signal, libc::c_int),
+
+    /// Traced process was stopped at a system call entry or exit. This is
+    /// only reported if `PTRACE_O_TRACESYSGOOD` has been set.
+    PtraceSyscall(libc::pid_t),
+
     /// Process was stopped but has resumed execution after receiving a
     /// `SIGCONT` signal
     Continued(libc::pid_t),
@@ -28,19 +79,73 @@ impl WaitStatus {
     /// This function is using the standard set of wait status related macros in
     /// order to dissect `status`
     pub fn from_raw(pid: libc::pid_t, status: libc::c_int) -> Result<WaitStatus> {
-        Ok(if libc::WIFEXITED(status) {
-            WaitStatus::Exited(pid, libc::WEXITSTATUS(status))
+        if libc::WIFEXITED(status) {
+            Ok(WaitStatus::Exited(pid, libc::WEXITSTATUS(status)))
         } else if libc::WIFSIGNALED(status) {
-            WaitStatus::Signaled(
+            Ok(WaitStatus::Signaled(
                 pid,
                 libc::WTERMSIG(status).try_into()?,
                 libc::WCOREDUMP(status),
-            )
+            ))
         } else if libc::WIFSTOPPED(status) {
-            WaitStatus::Stopped(pid, libc::WSTOPSIG(status).try_into()?)
+            // the ptrace event is stored in the bits above the stop signal
+            WaitStatus::from_stop(pid, status >> 8)
+        } else if libc::WIFCONTINUED(status) {
+            Ok(WaitStatus::Continued(pid))
+        } else {
+            Err(Error::Syscall(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "invalid wait status",
+            )))
+        }
+    }
+
+    /// Convert a `siginfo` as returned by [`libc::waitid()`] into a [`WaitStatus`]
+    ///
+    /// This function is dissecting the `si_code` and `si_status` fields
+    /// of `siginfo` for a `SIGCHLD` signal
+    pub fn from_siginfo(siginfo: &libc::siginfo_t) -> Result<WaitStatus> {
+        let (pid, status) = unsafe { (siginfo.si_pid(), siginfo.si_status()) };
+
+        Ok(match siginfo.si_code {
+            libc::CLD_EXITED => WaitStatus::Exited(pid, status),
+            libc::CLD_KILLED => WaitStatus::Signaled(pid, status.try_into()?, false),
+            libc::CLD_DUMPED => WaitStatus::Signaled(pid, status.try_into()?, true),
+            libc::CLD_STOPPED | libc::CLD_TRAPPED => WaitStatus::from_stop(pid, status)?,
+            libc::CLD_CONTINUED => WaitStatus::Continued(pid),
+            _ => {
+                return Err(Error::Syscall(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "invalid child signal code",
+                )));
+            }
+        })
+    }
+
+    /// Return the process ID of the child this [`WaitStatus`] belongs to
+    pub fn pid(&self) -> libc::pid_t {
+        match *self {
+            WaitStatus::Exited(pid, _)
+            | WaitStatus::Signaled(pid, _, _)
+            | WaitStatus::Stopped(pid, _)
+            | WaitStatus::PtraceEvent(pid, _, _)
+            | WaitStatus::PtraceSyscall(pid)
+            | WaitStatus::Continued(pid) => pid,
+        }
+    }
+
+    /// Dissect a stop `status`, where the lowest byte is the stop signal and
+    /// the next byte is an optional ptrace event
+    fn from_stop(pid: libc::pid_t, status: libc::c_int) -> Result<WaitStatus> {
+        let signum = status & 0xff;
+        let event = (status >> 8) & 0xff;
+
+        Ok(if signum == libc::SIGTRAP | 0x80 {
+            WaitStatus::PtraceSyscall(pid)
+        } else if event != 0 {
+            WaitStatus::PtraceEvent(pid, signum.try_into()?, event)
         } else {
-            assert!(libc::WIFCONTINUED(status));
-            WaitStatus::Continued(pid)
+            WaitStatus::Stopped(pid, signum.try_into()?)
         })
     }
 }
@@ -67,11 +172,104 @@ where
     WaitStatus::from_raw(res, status)
 }
 
+/// Check whether one of the children of the calling process has changed its
+/// state without blocking.
+///
+/// Return `None` if no child status is available (yet). See [`wait()`] for
+/// the meaning of `pid`.
+pub fn try_wait<P>(pid: P) -> Result<Option<WaitStatus>>
+where
+    P: Into<Option<libc::pid_t>>,
+{
+    waitpid(pid, WaitOptions::WNOHANG | WaitOptions::WUNTRACED)
+}
+
+/// Wait for any child in the process group `pgid` to change its state.
+///
+/// Return `None` if `WNOHANG` is part of `options` and no child status is
+/// available.
+pub fn wait_pgid(pgid: libc::pid_t, options: WaitOptions) -> Result<Option<WaitStatus>> {
+    waitpid(-pgid, options)
+}
+
+/// Wait for a child of the calling process to change its state as specified
+/// by `options`.
+///
+/// The meaning of `pid` follows `waitpid(2)`: a value greater than `0` selects
+/// the child with that process ID, `0` any child in the process group of the
+/// caller and a value less than `-1` any child in the process group `-pid`.
+/// In case `pid` is `None`, wait for **any** child.
+///
+/// Return `None` if `WNOHANG` is part of `options` and no child status is
+/// available.
+pub fn waitpid<P>(pid: P, options: WaitOptions) -> Result<Option<WaitStatus>>
+where
+    P: Into<Option<libc::pid_t>>,
+{
+    let mut status: i32 = 0;
+
+    let res = syscall!(waitpid(
+        pid.into().unwrap_or(-1_i32),
+        &mut status as &mut libc::c_int,
+        options.bits()
+    ))?;
+
+    match res {
+        0 => Ok(None),
+        pid => WaitStatus::from_raw(pid, status).map(Some),
+    }
+}
+
+/// Wait for the children selected by `id` to change their state as specified
+/// by `options` and return the full `siginfo` of the state change.
+///
+/// Note that `options` must contain at least one of `WEXITED`, `WSTOPPED` or
+/// `WCONTINUED`. Return `None` if `WNOHANG` is part of `options` and no child
+/// status is available. Use [`WaitStatus::from_siginfo()`] to get a
+/// [`WaitStatus`] from the result.
+pub fn waitid(id: WaitId, options: WaitOptions) -> Result<Option<libc::siginfo_t>> {
+    let (idtype, id) = match id {
+        WaitId::All => (libc::P_ALL, 0),
+        WaitId::Pid(pid) => (libc::P_PID, pid as libc::id_t),
+        WaitId::Pgid(pgid) => (libc::P_PGID, pgid as libc::id_t),
+        WaitId::PidFd(fd) => (libc::P_PIDFD, fd as libc::id_t),
+    };
+
+    let mut siginfo = unsafe { mem::zeroed::<libc::siginfo_t>() };
+
+    syscall!(waitid(
+        idtype,
+        id,
+        &mut siginfo as *mut libc::siginfo_t,
+        options.bits()
+    ))?;
+
+    // with WNOHANG, a zero pid indicates that no child was waitable
+    if unsafe { siginfo.si_pid() } == 0 {
+        Ok(None)
+    } else {
+        Ok(Some(siginfo))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::process::exit;
+    use std::{process::exit, time::Duration};
+
+    use crate::{
+        Result, Signal, WaitId, WaitOptions, WaitStatus, try_wait, wait, wait_pgid, waitid, waitpid,
+    };
 
-    use crate::{Result, Signal, WaitStatus, wait};
+    fn sleeper() -> Result<libc::pid_t> {
+        Ok(match syscall!(fork())? {
+            // parent
+            pid if pid != 0 => pid,
+            // child
+            _ => loop {
+                std::thread::sleep(Duration::from_millis(5));
+            },
+        })
+    }
 
     /// This test is inherently flaky and **must not** run together with other
     /// tests. Otherwise, it will most likely fail by [`wait()`] returning the
@@ -133,7 +331,7 @@ mod tests {
             },
         };
 
-        syscall!(kill(child, Signal::SIGSTOP as libc::c_int))?;
+        syscall!(kill(child, Signal::SIGSTOP.as_raw()))?;
 
         if let WaitStatus::Stopped(pid, signal) = wait(child)? {
             assert_eq!(pid, child);
@@ -143,6 +341,10 @@ mod tests {
             unreachable!("wait() returned an unexpected wait status");
         }
 
+        // don't leave a stopped orphan behind
+        syscall!(kill(child, Signal::SIGKILL.as_raw()))?;
+        wait(child)?;
+
         Ok(())
     }
 
@@ -157,7 +359,7 @@ mod tests {
             },
         };
 
-        syscall!(kill(child, Signal::SIGKILL as libc::c_int))?;
+        syscall!(kill(child, Signal::SIGKILL.as_raw()))?;
 
         if let WaitStatus::Signaled(pid, signal, core) = wait(child)? {
             assert_eq!(pid, child);
@@ -182,7 +384,7 @@ mod tests {
             },
         };
 
-        syscall!(kill(child, Signal::SIGSTOP as libc::c_int))?;
+        syscall!(kill(child, Signal::SIGSTOP.as_raw()))?;
 
         if let WaitStatus::Stopped(pid, signal) = wait(child)? {
             assert_eq!(pid, child);
@@ -192,7 +394,7 @@ mod tests {
             unreachable!("wait() returned an unexpected wait status");
         }
 
-        syscall!(kill(child, Signal::SIGKILL as libc::c_int))?;
+        syscall!(kill(child, Signal::SIGKILL.as_raw()))?;
 
         if let WaitStatus::Signaled(pid, signal, core) = wait(child)? {
             assert_eq!(pid, child);
@@ -215,4 +417,195 @@ mod tests {
             "System call error: No child processes (os error 10)"
         );
     }
+
+    #[test]
+    fn try_wait_running() -> Result<()> {
+        let child = sleeper()?;
+
+        assert_eq!(try_wait(child)?, None);
+
+        syscall!(kill(child, Signal::SIGKILL.as_raw()))?;
+
+        let status = loop {
+            if let Some(status) = try_wait(child)? {
+                break status;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        };
+
+        assert_eq!(status, WaitStatus::Signaled(child, Signal::SIGKILL, false));
+
+        Ok(())
+    }
+
+    #[test]
+    fn waitpid_continued() -> Result<()> {
+        let child = sleeper()?;
+
+        syscall!(kill(child, Signal::SIGSTOP.as_raw()))?;
+        assert_eq!(
+            waitpid(child, WaitOptions::WUNTRACED)?,
+            Some(WaitStatus::Stopped(child, Signal::SIGSTOP))
+        );
+
+        syscall!(kill(child, Signal::SIGCONT.as_raw()))?;
+        assert_eq!(
+            waitpid(child, WaitOptions::WCONTINUED)?,
+            Some(WaitStatus::Continued(child))
+        );
+
+        syscall!(kill(child, Signal::SIGKILL.as_raw()))?;
+        wait(child)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn wait_process_group() -> Result<()> {
+        let child = match syscall!(fork())? {
+            // parent
+            pid if pid != 0 => pid,
+            // child
+            _ => {
+                unsafe { libc::setpgid(0, 0) };
+                loop {
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+            }
+        };
+
+        // avoid racing the child
+        let _ = syscall!(setpgid(child, child));
+
+        syscall!(kill(child, Signal::SIGKILL.as_raw()))?;
+
+        assert_eq!(
+            wait_pgid(child, WaitOptions::empty())?,
+            Some(WaitStatus::Signaled(child, Signal::SIGKILL, false))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn waitid_siginfo() -> Result<()> {
+        let child = match syscall!(fork())? {
+            // parent
+            pid if pid != 0 => pid,
+            // child
+            _ => {
+                exit(7);
+            }
+        };
+
+        // peek at the status first, leaving the child waitable
+        let siginfo = waitid(
+            WaitId::Pid(child),
+            WaitOptions::WEXITED | WaitOptions::WNOWAIT,
+        )?
+        .unwrap();
+
+        assert_eq!(siginfo.si_signo, libc::SIGCHLD);
+        assert_eq!(siginfo.si_code, libc::CLD_EXITED);
+        assert_eq!(unsafe { siginfo.si_uid() }, unsafe { libc::getuid() });
+
+        let siginfo = waitid(WaitId::Pid(child), WaitOptions::WEXITED)?.unwrap();
+
+        assert_eq!(
+            WaitStatus::from_siginfo(&siginfo)?,
+            WaitStatus::Exited(child, 7)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn waitid_nohang() -> Result<()> {
+        let child = sleeper()?;
+
+        assert!(
+            waitid(
+                WaitId::Pid(child),
+                WaitOptions::WEXITED | WaitOptions::WNOHANG
+            )?
+            .is_none()
+        );
+
+        syscall!(kill(child, Signal::SIGKILL.as_raw()))?;
+        wait(child)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn wait_ptrace_syscall() -> Result<()> {
+        let child = match syscall!(fork())? {
+            // parent
+            pid if pid != 0 => pid,
+            // child
+            _ => unsafe {
+                libc::ptrace(libc::PTRACE_TRACEME, 0, 0, 0);
+                libc::raise(libc::SIGSTOP);
+                libc::_exit(0)
+            },
+        };
+
+        assert_eq!(wait(child)?, WaitStatus::Stopped(child, Signal::SIGSTOP));
+
+        syscall!(ptrace(
+            libc::PTRACE_SETOPTIONS,
+            child,
+            0,
+            libc::PTRACE_O_TRACESYSGOOD | libc::PTRACE_O_EXITKILL
+        ))?;
+        syscall!(ptrace(libc::PTRACE_SYSCALL, child, 0, 0))?;
+
+        assert_eq!(wait(child)?, WaitStatus::PtraceSyscall(child));
+
+        syscall!(kill(child, Signal::SIGKILL.as_raw()))?;
+        assert_eq!(
+            wait(child)?,
+            WaitStatus::Signaled(child, Signal::SIGKILL, false)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn wait_ptrace_event() -> Result<()> {
+        let path = c"/bin/true";
+        let argv = [path.as_ptr(), std::ptr::null()];
+
+        let child = match syscall!(fork())? {
+            // parent
+            pid if pid != 0 => pid,
+            // child
+            _ => unsafe {
+                libc::ptrace(libc::PTRACE_TRACEME, 0, 0, 0);
+                libc::raise(libc::SIGSTOP);
+                libc::execv(path.as_ptr(), argv.as_ptr());
+                libc::_exit(1)
+            },
+        };
+
+        assert_eq!(wait(child)?, WaitStatus::Stopped(child, Signal::SIGSTOP));
+
+        syscall!(ptrace(
+            libc::PTRACE_SETOPTIONS,
+            child,
+            0,
+            libc::PTRACE_O_TRACEEXEC | libc::PTRACE_O_EXITKILL
+        ))?;
+        syscall!(ptrace(libc::PTRACE_CONT, child, 0, 0))?;
+
+        assert_eq!(
+            wait(child)?,
+            WaitStatus::PtraceEvent(child, Signal::SIGTRAP, libc::PTRACE_EVENT_EXEC)
+        );
+
+        syscall!(kill(child, Signal::SIGKILL.as_raw()))?;
+        wait(child)?;
+
+        Ok(())
+    }
 }
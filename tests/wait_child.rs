@@ -3,12 +3,12 @@
 //!
 
 use anyhow::{bail, Result};
-use syscall::{signal_block, syscall, wait, Signal, SignalFd, WaitStatus};
+use syscall::{set_child_subreaper, signal_block, syscall, wait, Signal, SignalFd, WaitStatus};
 
 #[test]
 fn wait_child() -> Result<()> {
     // make sure, we do get SIGCHILD
-    syscall!(prctl(libc::PR_SET_CHILD_SUBREAPER, 1, 0, 0, 0))?;
+    set_child_subreaper(true)?;
 
     // don't handle SIGCHILD the usual way
     signal_block(vec![Signal::SIGCHLD].as_slice().into())?;
@@ -26,7 +26,7 @@ fn wait_child() -> Result<()> {
 
     let mut sigfd = SignalFd::new(vec![Signal::SIGCHLD].as_slice().into())?;
 
-    match sigfd.read_signal()? {
+    match sigfd.read_signal()?.signal {
         Signal::SIGCHLD => match wait(child)? {
             WaitStatus::Exited(pid, status) => {
                 assert_eq!(child, pid);